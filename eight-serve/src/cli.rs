@@ -1,16 +1,32 @@
-use clap::{value_parser, Parser};
+use clap::{value_parser, Parser, Subcommand};
+use eight::embedded::storage::filesystem::Layout;
 use std::net::Ipv4Addr;
 
 /// Simple CLI program to host eight on your server.
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub(crate) struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Path to create storage in.
     ///
     /// Will create memory storage if not specified.
     #[arg(short, long)]
     pub directory: Option<String>,
 
+    /// Directory layout of filesystem storage.
+    ///
+    /// "nested:<width>", "nested:<width>:<depth>" or "hashed:<buckets>"
+    #[arg(long, default_value = "nested:2", value_parser = parse_layout)]
+    pub layout: Layout,
+
+    /// Read filesystem storage values written before checksums, instead of failing with a corrupted value error.
+    ///
+    /// Run "fsck --repair" to add checksums to them instead.
+    #[arg(long)]
+    pub legacy_values: bool,
+
     /// Permission level as number.
     ///
     /// Guest (0), Admin (1), Owner (2)
@@ -24,4 +40,136 @@ pub(crate) struct Args {
     /// IPv4 address to listen.
     #[arg(short, long, default_value_t = Ipv4Addr::new(0, 0, 0, 0), value_parser = value_parser!(Ipv4Addr))]
     pub bind: Ipv4Addr,
+
+    /// Directory to take scheduled snapshots and backups into.
+    ///
+    /// Snapshots and backups are disabled if not specified.
+    #[arg(long)]
+    pub snapshot_dir: Option<String>,
+
+    /// Seconds between scheduled snapshots.
+    #[arg(long, default_value_t = 3600, value_parser = value_parser!(u64).range(1..))]
+    pub snapshot_interval: u64,
+
+    /// How many of the newest snapshots to keep.
+    #[arg(long, default_value_t = 24, value_parser = value_parser!(u64).range(1..))]
+    pub snapshot_keep: u64,
+
+    /// How many requests can wait in queue.
+    #[arg(long, default_value_t = 1024, value_parser = value_parser!(u64).range(1..))]
+    pub queue_capacity: u64,
+
+    /// How many requests can run at the same time.
+    ///
+    /// No limit if not specified.
+    #[arg(long, value_parser = value_parser!(u64).range(1..))]
+    pub max_in_flight: Option<u64>,
+
+    /// How many blocking requests, like "blpop", can wait at the same time. Requests over the limit are rejected.
+    ///
+    /// No limit if not specified.
+    #[arg(long, value_parser = value_parser!(u64).range(1..))]
+    pub max_blocking: Option<u64>,
+
+    /// Reject requests with "503 Service Unavailable" while queue is full, instead of waiting for room.
+    #[arg(long)]
+    pub reject_overloaded: bool,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum Command {
+    /// Check filesystem storage for corrupt values, stray files and orphaned directories.
+    ///
+    /// Don't run it while a server is using the same directory.
+    Fsck {
+        /// Path of the storage to check.
+        directory: String,
+
+        /// Directory layout of the storage.
+        #[arg(long, default_value = "nested:2", value_parser = parse_layout)]
+        layout: Layout,
+
+        /// Delete corrupt values, stray files and orphaned directories.
+        #[arg(long, conflicts_with = "quarantine")]
+        repair: bool,
+
+        /// Move corrupt values and stray files into given directory, then repair.
+        #[arg(long)]
+        quarantine: Option<String>,
+    },
+    /// Move every value of filesystem storage into another directory layout.
+    ///
+    /// Don't run it while a server is using the same directory.
+    Migrate {
+        /// Path of the storage to migrate.
+        directory: String,
+
+        /// Current directory layout.
+        #[arg(long, value_parser = parse_layout)]
+        from: Layout,
+
+        /// New directory layout.
+        #[arg(long, value_parser = parse_layout)]
+        to: Layout,
+    },
+    /// Write every key-value of filesystem storage into a dump.
+    ///
+    /// Use `/dump` path to back up a running server instead.
+    Dump {
+        /// Path of the storage to dump.
+        directory: String,
+
+        /// Directory layout of the storage.
+        #[arg(long, default_value = "nested:2", value_parser = parse_layout)]
+        layout: Layout,
+
+        /// File to write dump into. Writes to standard output if not specified.
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Restore a dump into filesystem storage. Existing keys are kept unless dump has them.
+    ///
+    /// Use `/restore` path to restore into a running server instead.
+    Restore {
+        /// Path of the storage to restore into.
+        directory: String,
+
+        /// Directory layout of the storage.
+        #[arg(long, default_value = "nested:2", value_parser = parse_layout)]
+        layout: Layout,
+
+        /// File to read dump from. Reads from standard input if not specified.
+        #[arg(short, long)]
+        input: Option<String>,
+    },
+}
+
+fn parse_layout(value: &str) -> Result<Layout, String> {
+    let parts = value.split(':').collect::<Vec<_>>();
+    let number = |part: &str| {
+        part.parse::<usize>()
+            .ok()
+            .filter(|number| *number > 0)
+            .ok_or(format!("\"{part}\" is not a positive number"))
+    };
+
+    match parts[..] {
+        ["nested", width] => Ok(Layout::Nested {
+            width: number(width)?,
+            depth: None,
+        }),
+        ["nested", width, depth] => Ok(Layout::Nested {
+            width: number(width)?,
+            depth: Some(number(depth)?),
+        }),
+        ["hashed", buckets] => Ok(Layout::Hashed {
+            buckets: number(buckets)?
+                .try_into()
+                .map_err(|_| "Too many buckets".to_string())?,
+        }),
+        _ => Err(
+            "Expected \"nested:<width>\", \"nested:<width>:<depth>\" or \"hashed:<buckets>\""
+                .to_string(),
+        ),
+    }
 }
@@ -1,13 +1,16 @@
 use clap::Parser;
 use eight::{
     embedded::{
-        server::{Permission, Server},
-        storage::{filesystem, memory},
+        server::{Limits, Overflow, Permission, Server},
+        storage::{
+            filesystem::{self, Repair},
+            memory, metrics,
+        },
     },
     expose::{self, ConfigBuilder},
 };
-use std::net::SocketAddr;
-use tokio::signal;
+use std::{net::SocketAddr, time::Duration};
+use tokio::{fs::File, io, signal};
 
 mod cli;
 
@@ -15,10 +18,28 @@ mod cli;
 async fn main() -> Result<(), &'static str> {
     let args = cli::Args::parse();
 
+    if let Some(command) = args.command {
+        return run_command(command).await;
+    }
+
+    let limits = Limits {
+        queue: args.queue_capacity as usize,
+        in_flight: args.max_in_flight.map(|in_flight| in_flight as usize),
+        blocking: args.max_blocking.map(|blocking| blocking as usize),
+        overflow: if args.reject_overloaded {
+            Overflow::Reject
+        } else {
+            Overflow::Wait
+        },
+    };
+
     let server = if let Some(directory) = args.directory {
-        Server::new(filesystem::Storage::from_path(directory))
+        let storage = filesystem::Storage::from_path(directory)
+            .set_layout(args.layout)
+            .set_legacy_values(args.legacy_values);
+        Server::with_limits(metrics::Storage::new(storage), limits)
     } else {
-        Server::new(memory::Storage::new())
+        Server::with_limits(metrics::Storage::new(memory::Storage::new()), limits)
     };
 
     let addr = SocketAddr::from((args.bind.octets(), args.port));
@@ -29,6 +50,17 @@ async fn main() -> Result<(), &'static str> {
         _ => Err("Invalid permission value. For more information, try '--help'."),
     }?;
 
+    if let Some(directory) = args.snapshot_dir {
+        server.set_snapshot_directory(&directory).await;
+        server
+            .schedule_snapshots(
+                directory,
+                Duration::from_secs(args.snapshot_interval),
+                args.snapshot_keep as usize,
+            )
+            .await;
+    }
+
     let config = ConfigBuilder::from_server(server)
         .set_permission(permission)
         .bind(addr)
@@ -39,3 +71,104 @@ async fn main() -> Result<(), &'static str> {
 
     Ok(())
 }
+
+async fn run_command(command: cli::Command) -> Result<(), &'static str> {
+    match command {
+        cli::Command::Fsck {
+            directory,
+            layout,
+            repair,
+            quarantine,
+        } => {
+            let repair = match (repair, quarantine) {
+                (_, Some(path)) => Repair::Quarantine(path.into()),
+                (true, None) => Repair::Fix,
+                (false, None) => Repair::None,
+            };
+
+            let report = filesystem::Storage::from_path(directory)
+                .set_layout(layout)
+                .fsck(repair)
+                .await
+                .map_err(|_| "Checking storage failed.")?;
+
+            println!("{} healthy value(s)", report.values);
+
+            let problems = [
+                ("corrupt", &report.corrupt),
+                ("unverified", &report.unverified),
+                ("stray", &report.stray),
+                ("orphaned", &report.orphaned),
+            ];
+
+            for (kind, paths) in problems {
+                for path in paths {
+                    println!("{kind}: {}", path.display());
+                }
+            }
+
+            if report.is_clean() {
+                Ok(())
+            } else {
+                Err("Storage has problems.")
+            }
+        }
+        cli::Command::Migrate {
+            directory,
+            from,
+            to,
+        } => {
+            filesystem::Storage::from_path(directory)
+                .set_layout(from)
+                .migrate(to)
+                .await
+                .map_err(|_| "Migrating storage failed.")?;
+
+            Ok(())
+        }
+        cli::Command::Dump {
+            directory,
+            layout,
+            output,
+        } => {
+            let server = Server::new(filesystem::Storage::from_path(directory).set_layout(layout));
+
+            let result = match output {
+                Some(path) => {
+                    let file = File::create(path)
+                        .await
+                        .map_err(|_| "Creating output file failed.")?;
+
+                    server.dump(file).await
+                }
+                None => server.dump(io::stdout()).await,
+            };
+
+            result.map_err(|_| "Dumping storage failed.")?;
+            Ok(())
+        }
+        cli::Command::Restore {
+            directory,
+            layout,
+            input,
+        } => {
+            let server = Server::new(filesystem::Storage::from_path(directory).set_layout(layout));
+
+            let result = match input {
+                Some(path) => {
+                    let file = File::open(path)
+                        .await
+                        .map_err(|_| "Opening input file failed.")?;
+
+                    server.restore(file).await
+                }
+                None => server.restore(io::stdin()).await,
+            };
+
+            let count = result.map_err(|_| "Restoring dump failed.")?;
+            println!("{count} value(s) restored");
+
+            Ok(())
+        }
+    }
+}
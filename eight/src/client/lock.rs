@@ -0,0 +1,195 @@
+//! Lease lock helper built on `LOCK`, `RENEW` and `UNLOCK` commands.
+
+use super::{http, messaging::QueryBuilder};
+use crate::{embedded::messaging::Response, err};
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use tokio::{
+    runtime::Handle,
+    task::JoinHandle,
+    time::{self, Instant},
+};
+
+/// Lock held on server. Lease is renewed in the background and lock is released on drop.
+///
+/// ```no_run
+/// # async fn hi() -> eight::client::Result<()> {
+/// use eight::client::{http::Client, lock::Lock};
+/// use std::time::Duration;
+///
+/// let client = Client::new("http://localhost:3000/");
+/// let lock = Lock::acquire(&client, "cron:backup", "machine-1", Duration::from_secs(10)).await?;
+///
+/// // pass fencing token along with writes to guarded resources
+/// let token = lock.token();
+///
+/// lock.release().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Lock {
+    client: http::Client,
+    name: String,
+    owner: String,
+    token: u64,
+    // when lease ends unless renewed, none once it is lost
+    lease: Arc<Mutex<Option<Instant>>>,
+    renewal: JoinHandle<()>,
+    released: bool,
+}
+
+impl Lock {
+    /// Acquire lock for owner, lease is renewed every third of it until lock is dropped.
+    ///
+    /// Fails with [`super::Error::LockFail`] if lock is held by another owner.
+    pub async fn acquire(
+        client: &http::Client,
+        name: &str,
+        owner: &str,
+        ttl: Duration,
+    ) -> super::Result<Self> {
+        // lease starts on server after request is sent, so it ends no later than this
+        let started = Instant::now();
+        let response = call(client, "lock $name $owner $ttl;", name, owner, ttl).await?;
+
+        let Response::Number(token) = response else {
+            return Err(err!(client, LockFail));
+        };
+
+        let lease = Arc::new(Mutex::new(Some(started + ttl)));
+
+        let renewal = tokio::spawn({
+            let (client, lease) = (client.clone(), lease.clone());
+            let (name, owner) = (name.to_string(), owner.to_string());
+
+            async move {
+                let mut interval = time::interval((ttl / 3).max(Duration::from_millis(1)));
+                interval.tick().await;
+
+                loop {
+                    interval.tick().await;
+
+                    let Some(expires) = *lease.lock().unwrap_or_else(PoisonError::into_inner)
+                    else {
+                        break;
+                    };
+
+                    let attempt = Instant::now();
+                    let renew = call(&client, "renew $name $owner $ttl;", &name, &owner, ttl);
+
+                    // a failed request is retried until lease would have ended on server
+                    let renewed = match time::timeout_at(expires, renew).await {
+                        Ok(Ok(Response::Number(_))) => Some(attempt + ttl),
+                        Ok(Err(_)) if Instant::now() < expires => continue,
+                        _ => None,
+                    };
+
+                    *lease.lock().unwrap_or_else(PoisonError::into_inner) = renewed;
+
+                    if renewed.is_none() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            client: client.clone(),
+            name: name.to_string(),
+            owner: owner.to_string(),
+            token: token as u64,
+            lease,
+            renewal,
+            released: false,
+        })
+    }
+
+    /// Fencing token of lock, grows every time lock changes hands.
+    ///
+    /// Token is kept as the value of lock name, deleting that key or flushing server starts tokens from one (1) again.
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// Returns false if lease is lost, like when renewal couldn't reach server before lease ended.
+    pub fn is_held(&self) -> bool {
+        self.lease
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some_and(|expires| Instant::now() < expires)
+    }
+
+    /// Stop renewing and release lock.
+    ///
+    /// Fails with [`super::Error::UnlockFail`] if lock is no longer held by owner.
+    pub async fn release(mut self) -> super::Result<()> {
+        self.released = true;
+        self.renewal.abort();
+
+        match call(
+            &self.client,
+            "unlock $name $owner;",
+            &self.name,
+            &self.owner,
+            Duration::ZERO,
+        )
+        .await?
+        {
+            Response::Ok => Ok(()),
+            _ => Err(err!(client, UnlockFail)),
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        self.renewal.abort();
+
+        if self.released {
+            return;
+        }
+
+        // without a runtime lock is left to expire
+        if let Ok(handle) = Handle::try_current() {
+            let (client, name, owner) =
+                (self.client.clone(), self.name.clone(), self.owner.clone());
+
+            handle.spawn(async move {
+                call(
+                    &client,
+                    "unlock $name $owner;",
+                    &name,
+                    &owner,
+                    Duration::ZERO,
+                )
+                .await
+            });
+        }
+    }
+}
+
+async fn call(
+    client: &http::Client,
+    query: &str,
+    name: &str,
+    owner: &str,
+    ttl: Duration,
+) -> super::Result<Response> {
+    let request = QueryBuilder::new()
+        .add_query(query)
+        .bind("name", name)
+        .bind("owner", owner)
+        .bind("ttl", ttl.as_millis().to_string().as_str())
+        .set_random_id()
+        .collect();
+
+    client
+        .execute(request)
+        .await?
+        .results
+        .pop()
+        .ok_or(err!(client, ReadBodyFail))
+}
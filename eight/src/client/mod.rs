@@ -6,6 +6,7 @@ mod result;
 pub use result::*;
 
 pub mod http;
+pub mod lock;
 pub mod messaging;
 pub mod websocket;
 
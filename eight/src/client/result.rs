@@ -18,4 +18,10 @@ pub enum Error {
     WebSocketRecvFail,
     #[error("Receive message form WebSocket timeout")]
     WebSocketRecvTimeout,
+    #[error("Subscribing to channel failed")]
+    SubscribeFail,
+    #[error("Acquiring lock failed")]
+    LockFail,
+    #[error("Releasing lock failed")]
+    UnlockFail,
 }
@@ -1,9 +1,15 @@
-use super::{http, messaging, websocket};
+use super::{http, lock::Lock, messaging, websocket};
 use crate::{
-    embedded::{server::Server, storage::memory},
+    embedded::{messaging::Response, server::Server, storage::memory},
     expose,
 };
-use std::net::SocketAddr;
+use futures::{future, StreamExt};
+use std::{net::SocketAddr, time::Duration};
+use tokio::{
+    io,
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
 
 #[tokio::test]
 async fn http_client() -> super::Result<()> {
@@ -15,6 +21,7 @@ async fn http_client() -> super::Result<()> {
         .collect();
 
     tokio::spawn(expose::expose(expose_config));
+    tokio::task::yield_now().await;
 
     let client = http::Client::new("http://localhost:42069");
 
@@ -43,6 +50,7 @@ async fn websocket_client() -> super::Result<()> {
         .collect();
 
     tokio::spawn(expose::expose(expose_config));
+    tokio::task::yield_now().await;
 
     let client = websocket::Client::connect("ws://localhost:42070").await?;
     client.start().await;
@@ -61,3 +69,220 @@ async fn websocket_client() -> super::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn websocket_subscription() -> super::Result<()> {
+    let storage = memory::Storage::new();
+    let server = Server::new(storage);
+
+    let expose_config = expose::ConfigBuilder::from_server(server)
+        .bind(SocketAddr::from(([127, 0, 0, 1], 42075)))
+        .collect();
+
+    tokio::spawn(expose::expose(expose_config));
+    tokio::task::yield_now().await;
+
+    let client = websocket::Client::connect("ws://localhost:42075").await?;
+    client.start().await;
+
+    let mut news = Box::pin(client.subscribe("news").await?);
+    let mut users = Box::pin(client.pattern_subscribe("user:*").await?);
+
+    let request = messaging::QueryBuilder::new()
+        .add_query("publish news $message;")
+        .add_query("publish user:1 online;")
+        .add_query("publish weather rainy;")
+        .bind("message", "hello")
+        .set_random_id()
+        .collect();
+
+    client.call(request).await?;
+
+    let message = news.next().await.unwrap();
+    assert_eq!(
+        (message.channel.as_str(), message.message.as_str()),
+        ("news", "hello")
+    );
+
+    let message = users.next().await.unwrap();
+    assert_eq!(message.channel, "user:1".to_string());
+    assert_eq!(message.pattern, Some("user:*".to_string()));
+
+    client.unsubscribe("news").await?;
+    assert!(news.next().await.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn websocket_blocking_pop() -> super::Result<()> {
+    let storage = memory::Storage::new();
+    let server = Server::new(storage);
+
+    let expose_config = expose::ConfigBuilder::from_server(server)
+        .bind(SocketAddr::from(([127, 0, 0, 1], 42076)))
+        .collect();
+
+    tokio::spawn(expose::expose(expose_config));
+    tokio::task::yield_now().await;
+
+    let client = websocket::Client::connect("ws://localhost:42076").await?;
+    client.start().await;
+
+    let pop = messaging::QueryBuilder::from_id("pop")
+        .add_query("blpop jobs 0;")
+        .collect();
+
+    let push = messaging::QueryBuilder::from_id("push")
+        .add_query("rpush jobs job;")
+        .collect();
+
+    // push is answered on the same connection while pop is waiting
+    let (pop, push) = futures::join!(client.call(pop), client.call(push));
+
+    assert_eq!(push?.results, vec![Response::Number(1)]);
+    assert_eq!(pop?.results, vec![Response::Text("job".to_string())]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lease_lock() -> super::Result<()> {
+    let storage = memory::Storage::new();
+    let server = Server::new(storage);
+
+    let expose_config = expose::ConfigBuilder::from_server(server)
+        .bind(SocketAddr::from(([127, 0, 0, 1], 42077)))
+        .collect();
+
+    tokio::spawn(expose::expose(expose_config));
+    tokio::task::yield_now().await;
+
+    let client = http::Client::new("http://localhost:42077");
+    let ttl = Duration::from_millis(600);
+
+    let lock = Lock::acquire(&client, "cron", "machine-1", ttl).await?;
+    assert_eq!(lock.token(), 1);
+
+    // lease is renewed in the background
+    tokio::time::sleep(ttl * 3).await;
+    assert!(lock.is_held());
+    assert_eq!(
+        Lock::acquire(&client, "cron", "machine-2", ttl)
+            .await
+            .unwrap_err(),
+        super::Error::LockFail
+    );
+
+    lock.release().await?;
+
+    let lock = Lock::acquire(&client, "cron", "machine-2", ttl).await?;
+    assert_eq!(lock.token(), 2);
+
+    // dropping releases lock
+    drop(lock);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let lock = Lock::acquire(&client, "cron", "machine-1", ttl).await?;
+    assert_eq!(lock.token(), 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lease_lock_lost_in_partition() -> super::Result<()> {
+    let storage = memory::Storage::new();
+    let server = Server::new(storage);
+
+    let expose_config = expose::ConfigBuilder::from_server(server)
+        .bind(SocketAddr::from(([127, 0, 0, 1], 42079)))
+        .collect();
+
+    tokio::spawn(expose::expose(expose_config));
+    tokio::task::yield_now().await;
+
+    let partition = proxy(42080, 42079).await;
+    let client = http::Client::new("http://localhost:42080");
+    let ttl = Duration::from_millis(600);
+
+    let lock = Lock::acquire(&client, "cron", "machine-1", ttl).await?;
+
+    tokio::time::sleep(ttl).await;
+    assert!(lock.is_held());
+
+    // renewals hang from now on, lease ends on server meanwhile
+    partition.send(true).ok();
+    tokio::time::sleep(ttl * 2).await;
+    assert!(!lock.is_held());
+
+    Ok(())
+}
+
+// forwards connections to target until partitioned, then keeps them open without answering
+async fn proxy(port: u16, target: u16) -> watch::Sender<bool> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+    let (partition, receiver) = watch::channel(false);
+
+    tokio::spawn({
+        async move {
+            while let Ok((mut inbound, _)) = listener.accept().await {
+                let mut partitioned = receiver.clone();
+
+                tokio::spawn(async move {
+                    if !*partitioned.borrow() {
+                        let mut outbound = TcpStream::connect(("127.0.0.1", target)).await?;
+
+                        tokio::select! {
+                            _ = io::copy_bidirectional(&mut inbound, &mut outbound) => return Ok(()),
+                            _ = partitioned.changed() => {}
+                        }
+                    }
+
+                    future::pending::<io::Result<()>>().await
+                });
+            }
+        }
+    });
+
+    partition
+}
+
+#[tokio::test]
+async fn websocket_stream() -> super::Result<()> {
+    let storage = memory::Storage::new();
+    let server = Server::new(storage);
+
+    let expose_config = expose::ConfigBuilder::from_server(server)
+        .bind(SocketAddr::from(([127, 0, 0, 1], 42078)))
+        .collect();
+
+    tokio::spawn(expose::expose(expose_config));
+    tokio::task::yield_now().await;
+
+    let client = websocket::Client::connect("ws://localhost:42078").await?;
+    client.start().await;
+
+    let add = |value: &str| {
+        messaging::QueryBuilder::new()
+            .add_query("xadd events value $value;")
+            .bind("value", value)
+            .set_random_id()
+            .collect()
+    };
+
+    client.call(add("first")).await?;
+
+    let mut entries = Box::pin(client.read_stream("events", "0"));
+
+    let entry = entries.next().await.unwrap();
+    assert_eq!(entry.fields["value"], "first");
+
+    // entry added later is read by the waiting read
+    client.call(add("second")).await?;
+
+    let next = entries.next().await.unwrap();
+    assert_eq!(next.fields["value"], "second");
+    assert!(next.id > entry.id);
+
+    Ok(())
+}
@@ -1,15 +1,22 @@
 //! Client implementation for WebSocket connections.
 
-use super::messaging;
-use crate::err;
+use super::messaging::{self, QueryBuilder};
+use crate::{
+    embedded::messaging::{Message as PublishedMessage, Response},
+    err,
+};
 use futures::{
-    stream::{SplitSink, SplitStream},
-    SinkExt, StreamExt, TryStreamExt,
+    stream::{self, SplitSink, SplitStream},
+    SinkExt, Stream, StreamExt, TryStreamExt,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
 };
-use std::{collections::HashMap, sync::Arc, time::Duration};
 use tokio::{
     net::TcpStream,
-    sync::{oneshot, Mutex},
+    sync::{mpsc, oneshot, Mutex},
     time,
 };
 use tokio_tungstenite::{
@@ -18,12 +25,73 @@ use tokio_tungstenite::{
 
 type WebSocketConnection = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
+#[derive(Clone, PartialEq)]
+enum Target {
+    Channel(String),
+    Pattern(String),
+}
+
+impl Target {
+    fn matches(&self, message: &PublishedMessage) -> bool {
+        match self {
+            Target::Channel(channel) => message.pattern.is_none() && &message.channel == channel,
+            Target::Pattern(pattern) => message.pattern.as_ref() == Some(pattern),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Target::Channel(name) | Target::Pattern(name) => name,
+        }
+    }
+}
+
+struct Subscriber {
+    target: Target,
+    sender: mpsc::UnboundedSender<PublishedMessage>,
+}
+
+/// Entry read from a stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamEntry {
+    pub id: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+impl StreamEntry {
+    fn from_response(response: &Response) -> Option<Self> {
+        let Response::Map(entry) = response else {
+            return None;
+        };
+
+        let (Some(Response::Text(id)), Some(Response::Map(fields))) =
+            (entry.get("id"), entry.get("fields"))
+        else {
+            return None;
+        };
+
+        let fields = fields
+            .iter()
+            .filter_map(|(field, value)| match value {
+                Response::Text(value) => Some((field.clone(), value.clone())),
+                _ => None,
+            })
+            .collect();
+
+        Some(Self {
+            id: id.clone(),
+            fields,
+        })
+    }
+}
+
 /// WebSocket client struct.
 #[derive(Clone)]
 pub struct Client {
     sender: Arc<Mutex<SplitSink<WebSocketConnection, Message>>>,
     receiver: Arc<Mutex<SplitStream<WebSocketConnection>>>,
     pool: Arc<Mutex<HashMap<String, oneshot::Sender<messaging::Response>>>>,
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
 }
 
 impl Client {
@@ -47,6 +115,7 @@ impl Client {
             sender: Arc::new(Mutex::new(sender)),
             receiver: Arc::new(Mutex::new(receiver)),
             pool: Arc::new(Mutex::new(HashMap::new())),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
         })
     }
 
@@ -59,10 +128,20 @@ impl Client {
         });
     }
 
-    /// Message broker for WebSocket connection. Distributes responses through channels based on request ID. This function blocks the flow.
+    /// Message broker for WebSocket connection. Distributes responses through channels based on request ID and published messages to subscribers. This function blocks the flow.
     pub async fn listen(&self) {
         while let Ok(message) = self.receiver.lock().await.try_next().await {
             if let Some(Message::Text(message)) = message {
+                // published messages are kept in order, so they are not spawned
+                if let Ok(published) = serde_json::from_str::<PublishedMessage>(&message) {
+                    self.subscribers.lock().await.retain(|subscriber| {
+                        !subscriber.target.matches(&published)
+                            || subscriber.sender.send(published.clone()).is_ok()
+                    });
+
+                    continue;
+                }
+
                 let pool = Arc::clone(&self.pool);
 
                 tokio::spawn(async move {
@@ -78,6 +157,146 @@ impl Client {
         }
     }
 
+    /// Subscribe to a channel and return the stream of messages published to it. Listener must be running.
+    ///
+    /// ```no_run
+    /// # async fn howdy4() {
+    /// use eight::client::websocket::Client;
+    /// use futures::StreamExt;
+    ///
+    /// let client = Client::connect("ws://localhost:3000").await.unwrap();
+    /// client.start().await;
+    ///
+    /// let mut messages = Box::pin(client.subscribe("news").await.unwrap());
+    ///
+    /// while let Some(message) = messages.next().await {
+    ///   println!("{}: {}", message.channel, message.message);
+    /// }
+    /// # }
+    /// ```
+    pub async fn subscribe(
+        &self,
+        channel: &str,
+    ) -> super::Result<impl Stream<Item = PublishedMessage>> {
+        self.subscribe_to(Target::Channel(channel.to_string()))
+            .await
+    }
+
+    /// Subscribe to channels matching a pattern and return the stream of messages published to them.
+    /// `*` matches any text and `?` matches a single character.
+    pub async fn pattern_subscribe(
+        &self,
+        pattern: &str,
+    ) -> super::Result<impl Stream<Item = PublishedMessage>> {
+        self.subscribe_to(Target::Pattern(pattern.to_string()))
+            .await
+    }
+
+    /// Unsubscribe from a channel or pattern. Streams returned for it are ended.
+    pub async fn unsubscribe(&self, name: &str) -> super::Result<()> {
+        self.subscribers
+            .lock()
+            .await
+            .retain(|subscriber| subscriber.target.name() != name);
+
+        let request = QueryBuilder::new()
+            .add_query("unsubscribe $name;")
+            .bind("name", name)
+            .set_random_id()
+            .collect();
+
+        self.call(request).await.map(|_| ())
+    }
+
+    async fn subscribe_to(
+        &self,
+        target: Target,
+    ) -> super::Result<impl Stream<Item = PublishedMessage>> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        // registered before subscribing, so no message is missed
+        self.subscribers.lock().await.push(Subscriber {
+            target: target.clone(),
+            sender,
+        });
+
+        let command = match target {
+            Target::Channel(_) => "subscribe",
+            Target::Pattern(_) => "psubscribe",
+        };
+
+        let request = QueryBuilder::new()
+            .add_query(&format!("{command} $name;"))
+            .bind("name", target.name())
+            .set_random_id()
+            .collect();
+
+        let response = self.call(request).await?;
+
+        if response.results != [Response::Ok] {
+            return Err(err!(client, SubscribeFail));
+        }
+
+        Ok(stream::unfold(receiver, |mut receiver| async move {
+            let message = receiver.recv().await?;
+            Some((message, receiver))
+        }))
+    }
+
+    /// Read entries added to a stream after given ID, `$` for new entries only. Stream ends if a read fails. Listener must be running.
+    ///
+    /// Every read waits on server until an entry is added, so the last read is left waiting after stream is dropped.
+    ///
+    /// ```no_run
+    /// # async fn howdy5() {
+    /// use eight::client::websocket::Client;
+    /// use futures::StreamExt;
+    ///
+    /// let client = Client::connect("ws://localhost:3000").await.unwrap();
+    /// client.start().await;
+    ///
+    /// let mut entries = Box::pin(client.read_stream("events", "0"));
+    ///
+    /// while let Some(entry) = entries.next().await {
+    ///   println!("{}: {:?}", entry.id, entry.fields);
+    /// }
+    /// # }
+    /// ```
+    pub fn read_stream(&self, stream: &str, after: &str) -> impl Stream<Item = StreamEntry> {
+        let state = (self.clone(), stream.to_string(), after.to_string());
+
+        stream::unfold(Some(state), |state| async move {
+            let (client, stream, after) = state?;
+
+            let request = QueryBuilder::new()
+                .add_query("xread $stream $after 0 BLOCK 0;")
+                .bind("stream", &stream)
+                .bind("after", &after)
+                .set_random_id()
+                .collect();
+
+            let response = client.call(request).await.ok()?;
+
+            let Some(Response::List(entries)) = response.results.first() else {
+                return None;
+            };
+
+            let entries: Vec<StreamEntry> = entries
+                .iter()
+                .map(StreamEntry::from_response)
+                .collect::<Option<_>>()?;
+
+            // next read continues after the last entry
+            let after = entries
+                .last()
+                .map(|entry| entry.id.clone())
+                .unwrap_or(after);
+
+            Some((stream::iter(entries), Some((client, stream, after))))
+        })
+        .flatten()
+    }
+
     /// Execute query without waiting for [`messaging::Response`]. Returns a oneshot receiver so you can manually receive it later.
     ///
     /// ```no_run
@@ -110,7 +329,7 @@ impl Client {
             .sender
             .lock()
             .await
-            .send(Message::Text(raw_request.into()))
+            .send(Message::Text(raw_request))
             .await;
 
         if result.is_err() {
@@ -0,0 +1,174 @@
+use super::{
+    utils::{self, Record},
+    Layout,
+};
+use crate::err;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// What [`fsck`] should do with the problems it finds.
+///
+/// [`fsck`]: crate::embedded::storage::filesystem::Storage::fsck
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Repair {
+    /// Only report problems, don't touch anything.
+    #[default]
+    None,
+    /// Delete corrupt values, stray files and orphaned directories. Legacy values get a checksum.
+    Fix,
+    /// Same with [`Repair::Fix`], but corrupt values and stray files are moved into given directory instead of being deleted.
+    Quarantine(PathBuf),
+}
+
+/// Result of a storage check.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    /// Number of values with a valid checksum.
+    pub values: usize,
+    /// Value files with a checksum mismatch or invalid content.
+    pub corrupt: Vec<PathBuf>,
+    /// Value files written before checksums, they can't be verified.
+    pub unverified: Vec<PathBuf>,
+    /// Files that don't belong to any key.
+    pub stray: Vec<PathBuf>,
+    /// Directories without any value inside.
+    pub orphaned: Vec<PathBuf>,
+}
+
+impl Report {
+    /// Returns true if no problem is found.
+    pub fn is_clean(&self) -> bool {
+        self.corrupt.is_empty()
+            && self.unverified.is_empty()
+            && self.stray.is_empty()
+            && self.orphaned.is_empty()
+    }
+}
+
+pub(crate) fn fsck(
+    root: &Path,
+    layout: &Layout,
+    repair: &Repair,
+) -> crate::embedded::Result<Report> {
+    let mut report = Report::default();
+
+    if !root.exists() {
+        return Ok(report);
+    }
+
+    let (_, orphaned) = check_dir(root, layout, root, repair, &mut report)?;
+    remove_orphaned(orphaned, repair, &mut report)?;
+
+    Ok(report)
+}
+
+// returns whether directory has any value and its orphaned sub directories
+fn check_dir(
+    root: &Path,
+    layout: &Layout,
+    dir: &Path,
+    repair: &Repair,
+    report: &mut Report,
+) -> crate::embedded::Result<(bool, Vec<PathBuf>)> {
+    let entries = fs::read_dir(dir).map_err(|_| err!(embedded, FsckFail))?;
+
+    let mut has_values = false;
+    let mut orphaned = Vec::new();
+
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            let (child_has_values, child_orphaned) =
+                check_dir(root, layout, &path, repair, report)?;
+
+            if child_has_values {
+                has_values = true;
+                remove_orphaned(child_orphaned, repair, report)?;
+            } else {
+                orphaned.push(path);
+            }
+        } else if !path.ends_with(utils::VALUE_FILE) || !is_placed(root, layout, &path) {
+            discard(root, &path, repair)?;
+            report.stray.push(path);
+        } else {
+            let record = fs::read_to_string(&path)
+                .map_err(|_| err!(embedded, CorruptedValue))
+                .and_then(utils::unseal);
+
+            match record {
+                Ok(Record::Checked(_)) => {
+                    report.values += 1;
+                    has_values = true;
+                }
+                Ok(Record::Legacy(value)) => {
+                    if repair != &Repair::None {
+                        fs::write(&path, utils::seal(&value))
+                            .map_err(|_| err!(embedded, FsckFail))?;
+                    }
+
+                    report.unverified.push(path);
+                    has_values = true;
+                }
+                Err(_) => {
+                    discard(root, &path, repair)?;
+                    report.corrupt.push(path);
+                }
+            }
+        }
+    }
+
+    if has_values {
+        remove_orphaned(orphaned, repair, report)?;
+        Ok((true, Vec::new()))
+    } else {
+        Ok((false, orphaned))
+    }
+}
+
+fn remove_orphaned(
+    orphaned: Vec<PathBuf>,
+    repair: &Repair,
+    report: &mut Report,
+) -> crate::embedded::Result<()> {
+    for path in orphaned {
+        // orphaned directories has nothing to keep, quarantine removes them too
+        if repair != &Repair::None {
+            fs::remove_dir_all(&path).map_err(|_| err!(embedded, FsckFail))?;
+        }
+
+        report.orphaned.push(path);
+    }
+
+    Ok(())
+}
+
+fn discard(root: &Path, path: &Path, repair: &Repair) -> crate::embedded::Result<()> {
+    match repair {
+        Repair::None => Ok(()),
+        Repair::Fix => fs::remove_file(path).map_err(|_| err!(embedded, FsckFail)),
+        Repair::Quarantine(target) => {
+            let target = target.join(path.strip_prefix(root).unwrap_or(path));
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|_| err!(embedded, FsckFail))?;
+            }
+
+            // rename fails across devices, fallback to copy
+            if fs::rename(path, &target).is_err() {
+                fs::copy(path, &target).map_err(|_| err!(embedded, FsckFail))?;
+                fs::remove_file(path).map_err(|_| err!(embedded, FsckFail))?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+fn is_placed(root: &Path, layout: &Layout, path: &Path) -> bool {
+    let Some(key) = utils::path_to_key(root, layout, path) else {
+        return false;
+    };
+
+    matches!(super::create_path(root, layout, &key), Ok(expected) if expected == path)
+}
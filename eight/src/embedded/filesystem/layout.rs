@@ -0,0 +1,128 @@
+use super::utils;
+
+// keeps directory names far below common filename limits (255 bytes)
+const MAXIMUM_PART_LENGTH: usize = 200;
+
+const BUCKET_PREFIX: char = '#';
+
+/// Directory layout of filesystem storage.
+///
+/// Every layout keeps the encoded key in directory names, so the same directory can be converted between layouts.
+/// Directory names never get longer than 200 bytes, longer parts are split into more directories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Split key into parts of `width` characters and nest them.
+    ///
+    /// With a `depth` limit, the last directory keeps the rest of the key.
+    Nested { width: usize, depth: Option<usize> },
+    /// Place every key directly into one of `buckets` directories, picked by key hash.
+    ///
+    /// Searching has to visit every bucket.
+    Hashed { buckets: u32 },
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::Nested {
+            width: 2,
+            depth: None,
+        }
+    }
+}
+
+impl Layout {
+    /// Directory names for an encoded key.
+    pub(super) fn parts(&self, encoded: &str) -> Vec<String> {
+        let parts = match *self {
+            Layout::Nested { width, depth } => {
+                let mut parts = chunks(encoded, width.max(1));
+
+                if let Some(depth) = depth.map(|depth| depth.max(1)) {
+                    if parts.len() > depth {
+                        let rest = parts.split_off(depth - 1).concat();
+                        parts.push(rest);
+                    }
+                }
+
+                parts
+            }
+            Layout::Hashed { buckets } => {
+                let bucket = hash(encoded) % u64::from(buckets.max(1));
+                vec![format!("{BUCKET_PREFIX}{bucket:x}"), encoded.to_string()]
+            }
+        };
+
+        parts.iter().flat_map(|part| limit_length(part)).collect()
+    }
+
+    /// Directory names that every key starting with encoded prefix shares.
+    pub(super) fn prefix_parts(&self, encoded: &str) -> Vec<String> {
+        match *self {
+            Layout::Nested { width, depth } => {
+                let width = width.max(1);
+                let depth = depth.map_or(usize::MAX, |depth| depth.max(1) - 1);
+
+                chunks(encoded, width)
+                    .into_iter()
+                    .filter(|part| part.chars().count() == width)
+                    .take(depth)
+                    .flat_map(|part| limit_length(&part))
+                    .collect()
+            }
+            Layout::Hashed { .. } => Vec::new(),
+        }
+    }
+
+    /// Turns directory names back into the key.
+    pub(super) fn key(&self, parts: &[&str]) -> Option<String> {
+        let parts = match self {
+            Layout::Nested { .. } => parts,
+            Layout::Hashed { .. } => {
+                let (bucket, rest) = parts.split_first()?;
+
+                if !bucket.starts_with(BUCKET_PREFIX) {
+                    return None;
+                }
+
+                rest
+            }
+        };
+
+        utils::decode_key(&parts.concat())
+    }
+}
+
+fn chunks(value: &str, width: usize) -> Vec<String> {
+    value
+        .chars()
+        .collect::<Vec<_>>()
+        .chunks(width)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+fn limit_length(part: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+
+    for character in part.chars() {
+        if current.len() + character.len_utf8() > MAXIMUM_PART_LENGTH {
+            parts.push(std::mem::take(&mut current));
+        }
+
+        current.push(character);
+    }
+
+    if !current.is_empty() {
+        parts.push(current);
+    }
+
+    parts
+}
+
+// FNV-1a, bucket of a key must never change between versions
+fn hash(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
@@ -1,26 +1,77 @@
 use crate::err;
-use futures::{stream, StreamExt};
-use std::path::{Path, PathBuf};
-use tokio::fs;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+use tokio::{
+    fs,
+    io::AsyncReadExt,
+    sync::{Mutex, MutexGuard},
+};
 
+mod fsck;
+mod layout;
 mod utils;
 
+pub(crate) use fsck::fsck;
+pub use fsck::{Repair, Report};
+pub use layout::Layout;
+
 const MAXIMUM_PARALLEL_SEARCH: usize = 512;
+const MAXIMUM_WRITE_ATTEMPTS: usize = 3;
+
+const LOCK_STRIPES: usize = 32;
+
+static TEMPORARY_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Write locks for keys. Keys share a fixed number of locks, so memory doesn't grow with the keyspace.
+#[derive(Debug, Default)]
+pub(crate) struct Locks([Mutex<()>; LOCK_STRIPES]);
+
+impl Locks {
+    pub async fn lock(&self, key: &str) -> MutexGuard<'_, ()> {
+        self.0[stripe(key)].lock().await
+    }
+
+    /// Lock two keys, always in the same order so two requests can't wait for each other.
+    pub async fn lock_pair(&self, first: &str, second: &str) -> Vec<MutexGuard<'_, ()>> {
+        let (first, second) = (stripe(first), stripe(second));
+        let mut guards = vec![self.0[first.min(second)].lock().await];
 
-pub(crate) fn create_path(path: &Path, key: &str) -> super::Result<PathBuf> {
-    if key.len() < 2 {
-        return Err(err!(embedded, KeyTooShort));
-    } else if !utils::validate_key(key) {
-        return Err(err!(embedded, KeyWrongFormat));
+        if first != second {
+            guards.push(self.0[first.max(second)].lock().await);
+        }
+
+        guards
     }
+}
+
+fn stripe(key: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
 
+    (hasher.finish() % LOCK_STRIPES as u64) as usize
+}
+
+pub(crate) fn create_path(path: &Path, layout: &Layout, key: &str) -> super::Result<PathBuf> {
     let mut new_path = path.to_path_buf();
 
-    for list in key.chars().collect::<Vec<char>>().chunks(2) {
-        new_path.push(list.iter().collect::<String>());
+    for part in layout.parts(&utils::encode_key(key)) {
+        new_path.push(part);
     }
 
-    new_path.push("$");
+    new_path.push(utils::VALUE_FILE);
 
     Ok(new_path)
 }
@@ -28,89 +79,297 @@ pub(crate) fn create_path(path: &Path, key: &str) -> super::Result<PathBuf> {
 pub(crate) async fn write(path: &mut PathBuf, content: String) -> super::Result<()> {
     let file = path.file_name().unwrap().to_str().unwrap().to_string();
 
+    let content = utils::seal(&content);
+
     path.pop();
 
-    if !exists(path).await? && fs::create_dir_all(&path).await.is_err() {
-        return Err(err!(embedded, CreateDirFail));
+    // write into a temporary file first, so a crash can't leave a truncated value behind
+    let counter = TEMPORARY_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temporary = path.join(format!("{}{counter}", utils::TEMPORARY_FILE));
+
+    for attempt in 1.. {
+        if !exists(path).await? && fs::create_dir_all(&path).await.is_err() {
+            return Err(err!(embedded, CreateDirFail));
+        }
+
+        match fs::write(&temporary, &content).await {
+            Ok(_) => break,
+            // directory can be pruned by a delete in the meantime
+            Err(error)
+                if error.kind() == ErrorKind::NotFound && attempt < MAXIMUM_WRITE_ATTEMPTS => {}
+            Err(_) => return Err(err!(embedded, SetKeyFail)),
+        }
     }
 
     path.push(file);
 
-    fs::write(&path, content)
+    fs::rename(temporary, &path)
         .await
         .map_err(|_| err!(embedded, SetKeyFail))
 }
 
-pub(crate) async fn read(path: &PathBuf) -> super::Result<String> {
-    fs::read_to_string(path)
+/// Read value, values without checksum are only accepted if legacy is true.
+pub(crate) async fn read(path: &PathBuf, legacy: bool) -> super::Result<String> {
+    let raw = fs::read_to_string(path)
+        .await
+        .map_err(|_| err!(embedded, GetKeyFail))?;
+
+    match utils::unseal(raw)? {
+        utils::Record::Checked(value) => Ok(value),
+        utils::Record::Legacy(value) if legacy => Ok(value),
+        utils::Record::Legacy(_) => Err(err!(embedded, CorruptedValue)),
+    }
+}
+
+/// Size of value and last time it's written.
+pub(crate) async fn metadata(path: &Path) -> super::Result<(u64, SystemTime)> {
+    let mut file = fs::File::open(path)
+        .await
+        .map_err(|_| err!(embedded, GetKeyFail))?;
+
+    let metadata = file
+        .metadata()
         .await
-        .map_err(|_| err!(embedded, GetKeyFail))
+        .map_err(|_| err!(embedded, GetKeyFail))?;
+
+    let mut head = Vec::with_capacity(utils::CHECKSUM_HEADER.len());
+    (&mut file)
+        .take(utils::CHECKSUM_HEADER.len() as u64)
+        .read_to_end(&mut head)
+        .await
+        .map_err(|_| err!(embedded, GetKeyFail))?;
+
+    let modified = metadata
+        .modified()
+        .map_err(|_| err!(embedded, GetKeyFail))?;
+
+    Ok((utils::content_length(&head, metadata.len()), modified))
 }
 
-pub(crate) async fn delete(path: &PathBuf) -> super::Result<()> {
+pub(crate) async fn delete(root: &Path, path: &Path) -> super::Result<()> {
     fs::remove_file(path)
         .await
-        .map_err(|_| err!(embedded, DeleteKeyFail))
+        .map_err(|_| err!(embedded, DeleteKeyFail))?;
+
+    prune(root, path).await;
+    Ok(())
 }
 
-pub(crate) async fn exists(path: &PathBuf) -> super::Result<bool> {
+pub(crate) async fn exists(path: &Path) -> super::Result<bool> {
     fs::try_exists(path)
         .await
         .map_err(|_| err!(embedded, CheckExistsFail))
 }
 
 pub(crate) async fn flush(path: &PathBuf) -> super::Result<()> {
-    fs::remove_dir_all(path)
-        .await
-        .map_err(|_| err!(embedded, DirRemoveFail))
+    match fs::remove_dir_all(path).await {
+        // nothing is written yet, storage is already empty
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(()),
+        result => result.map_err(|_| err!(embedded, DirRemoveFail)),
+    }
 }
 
-pub(crate) async fn search(root: &Path, key: &str) -> super::Result<Vec<String>> {
-    let key_length = key.len();
-    let deep = key_length / 2;
-
-    let search_path = if key_length == 0 {
-        root.to_path_buf()
-    } else {
-        let mut path = create_path(root, key)?;
-        path.pop();
-
-        if key_length % 2 == 1 {
-            path.pop();
-        }
+pub(crate) async fn search(root: &Path, layout: &Layout, key: &str) -> super::Result<Vec<String>> {
+    // every match is placed under the directory of the shared parts of the key
+    let mut search_path = root.to_path_buf();
 
-        path
-    };
+    for part in layout.prefix_parts(&utils::encode_key(key)) {
+        search_path.push(part);
+    }
 
     let Ok(paths) = search_path.read_dir() else {
         return Ok(Vec::new());
     };
 
-    let tasks = stream::iter(paths)
-        .filter_map(|path| async {
-            if let Ok(entry) = path {
-                let path = entry.path();
+    let root = Arc::new(root.to_path_buf());
+    let layout = *layout;
 
-                if path.is_dir() {
-                    return Some(entry.path());
-                }
-            }
-
-            None
+    let tasks = stream::iter(paths)
+        .filter_map(|path| async { path.ok().map(|entry| entry.path()) })
+        .map(|path| {
+            let root = Arc::clone(&root);
+            tokio::spawn(async move { utils::search_recursive(&root, &layout, path) })
         })
-        .map(|path| tokio::spawn(async move { utils::search_recursive(path, deep) }))
         .buffer_unordered(MAXIMUM_PARALLEL_SEARCH);
 
     let results = tasks
-        .filter_map(|value| async {
-            match value {
-                Ok(result) => Some(result),
-                _ => None,
-            }
-        })
+        .filter_map(|value| async { value.ok() })
         .collect::<Vec<_>>()
         .await
-        .concat();
+        .concat()
+        .into_iter()
+        .filter(|found| found.starts_with(key))
+        .collect();
 
     Ok(results)
 }
+
+pub(crate) fn walk(
+    root: PathBuf,
+    layout: Layout,
+    key: String,
+) -> BoxStream<'static, super::Result<String>> {
+    let mut search_path = root.clone();
+
+    for part in layout.prefix_parts(&utils::encode_key(&key)) {
+        search_path.push(part);
+    }
+
+    // directories are listed one at a time, only pending entries are kept in memory
+    stream::unfold(vec![search_path], move |mut pending| {
+        let (root, key) = (root.clone(), key.clone());
+
+        async move {
+            while let Some(path) = pending.pop() {
+                if path.ends_with(utils::VALUE_FILE) {
+                    match utils::path_to_key(&root, &layout, &path) {
+                        Some(found) if found.starts_with(&key) => {
+                            return Some((Ok(found), pending))
+                        }
+                        _ => continue,
+                    }
+                }
+
+                let Ok(mut entries) = fs::read_dir(&path).await else {
+                    continue;
+                };
+
+                while let Ok(Some(entry)) = entries.next_entry().await {
+                    pending.push(entry.path());
+                }
+            }
+
+            None
+        }
+    })
+    .boxed()
+}
+
+pub(crate) async fn rename(root: &Path, source: &Path, target: &Path) -> super::Result<()> {
+    if !exists(source).await? {
+        return Err(err!(embedded, GetKeyFail));
+    }
+
+    if source == target {
+        return Ok(());
+    }
+
+    for attempt in 1.. {
+        create_parent(target).await?;
+
+        match fs::rename(source, target).await {
+            Ok(_) => break,
+            // directory can be pruned by a delete in the meantime
+            Err(error)
+                if error.kind() == ErrorKind::NotFound && attempt < MAXIMUM_WRITE_ATTEMPTS => {}
+            Err(_) => return Err(err!(embedded, SetKeyFail)),
+        }
+    }
+
+    prune(root, source).await;
+    Ok(())
+}
+
+pub(crate) async fn rename_nx(root: &Path, source: &Path, target: &Path) -> super::Result<bool> {
+    if !exists(source).await? {
+        return Err(err!(embedded, GetKeyFail));
+    }
+
+    if source == target || !link(root, source, target).await? {
+        return Ok(false);
+    }
+
+    delete(root, source).await?;
+    Ok(true)
+}
+
+pub(crate) async fn copy(root: &Path, source: &Path, target: &Path) -> super::Result<bool> {
+    if !exists(source).await? {
+        return Err(err!(embedded, GetKeyFail));
+    }
+
+    if source == target {
+        return Ok(false);
+    }
+
+    let counter = TEMPORARY_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temporary = target.with_file_name(format!("{}{counter}", utils::TEMPORARY_FILE));
+
+    for attempt in 1.. {
+        create_parent(target).await?;
+
+        match fs::copy(source, &temporary).await {
+            Ok(_) => break,
+            Err(error)
+                if error.kind() == ErrorKind::NotFound && attempt < MAXIMUM_WRITE_ATTEMPTS => {}
+            Err(_) => return Err(err!(embedded, SetKeyFail)),
+        }
+    }
+
+    let copied = link(root, &temporary, target).await;
+    fs::remove_file(&temporary).await.ok();
+
+    copied
+}
+
+// hard links never replace an existing file, so checking target and creating it is a single step
+async fn link(root: &Path, source: &Path, target: &Path) -> super::Result<bool> {
+    for attempt in 1.. {
+        create_parent(target).await?;
+
+        match fs::hard_link(source, target).await {
+            Ok(_) => return Ok(true),
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => return Ok(false),
+            Err(error)
+                if error.kind() == ErrorKind::NotFound && attempt < MAXIMUM_WRITE_ATTEMPTS => {}
+            Err(_) => break,
+        }
+    }
+
+    prune(root, target).await;
+    Err(err!(embedded, SetKeyFail))
+}
+
+async fn create_parent(path: &Path) -> super::Result<()> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent)
+            .await
+            .map_err(|_| err!(embedded, CreateDirFail)),
+        None => Ok(()),
+    }
+}
+
+pub(crate) async fn migrate(root: &Path, from: &Layout, to: &Layout) -> super::Result<usize> {
+    let mut moved = 0;
+
+    for key in search(root, from, "").await? {
+        let source = create_path(root, from, &key)?;
+        let target = create_path(root, to, &key)?;
+
+        if source == target {
+            continue;
+        }
+
+        rename(root, &source, &target).await?;
+        moved += 1;
+    }
+
+    Ok(moved)
+}
+
+// removes directories left empty, stops at the first directory that still has entries
+async fn prune(root: &Path, path: &Path) {
+    let mut current = path.parent();
+
+    while let Some(directory) = current {
+        if directory == root || !directory.starts_with(root) {
+            break;
+        }
+
+        if fs::remove_dir(directory).await.is_err() {
+            break;
+        }
+
+        current = directory.parent();
+    }
+}
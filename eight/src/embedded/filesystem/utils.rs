@@ -1,44 +1,129 @@
-use std::{mem, path::PathBuf};
+use super::Layout;
+use crate::err;
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+pub(super) const CHECKSUM_HEADER: &str = "eight:crc32:";
+
+pub(super) const VALUE_FILE: &str = "$";
+pub(super) const TEMPORARY_FILE: &str = "$~";
+
+/// Value read from disk.
+pub(super) enum Record {
+    /// Value with a valid checksum.
+    Checked(String),
+    /// Value written before checksums, there is nothing to verify.
+    Legacy(String),
+}
+
+// characters allowed before key encoding are kept as is, so older stores stay readable without migration.
+fn is_plain(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+pub(super) fn encode_key(key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
 
-pub(super) fn validate_key(key: &str) -> bool {
     for character in key.chars() {
-        if !character.is_alphanumeric() && character != '_' {
-            return false;
+        if is_plain(character) {
+            encoded.push(character);
+        } else {
+            let mut buffer = [0; 4];
+
+            for byte in character.encode_utf8(&mut buffer).bytes() {
+                write!(encoded, "%{byte:02X}").ok();
+            }
         }
     }
 
-    true
+    encoded
 }
 
-pub(super) fn search_recursive(path: PathBuf, deep: usize) -> Vec<String> {
-    let mut result = Vec::new();
+pub(super) fn decode_key(encoded: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut chars = encoded.chars();
 
-    let Ok(entries) = path.read_dir() else {
-        return result;
-    };
+    while let Some(character) = chars.next() {
+        if character == '%' {
+            let hex = chars.by_ref().take(2).collect::<String>();
 
-    let paths = entries.flatten().map(|entry| entry.path());
-    for path in paths {
-        if path.is_dir() {
-            result.extend(search_recursive(path, deep + 1))
-        } else if path.is_file() {
-            result.push(get_file_name(path, deep));
+            if hex.len() != 2 || !hex.chars().all(|x| x.is_ascii_hexdigit()) {
+                return None;
+            }
+
+            bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+        } else if is_plain(character) {
+            let mut buffer = [0; 4];
+            bytes.extend(character.encode_utf8(&mut buffer).bytes());
+        } else {
+            return None;
         }
     }
 
-    result
+    String::from_utf8(bytes).ok()
+}
+
+pub(super) fn path_to_key(root: &Path, layout: &Layout, path: &Path) -> Option<String> {
+    let relative = path.parent()?.strip_prefix(root).ok()?;
+
+    let parts = relative
+        .iter()
+        .map(|part| part.to_str())
+        .collect::<Option<Vec<_>>>()?;
+
+    layout.key(&parts)
+}
+
+pub(super) fn search_recursive(root: &Path, layout: &Layout, path: PathBuf) -> Vec<String> {
+    if path.is_file() {
+        return if path.ends_with(VALUE_FILE) {
+            path_to_key(root, layout, &path).into_iter().collect()
+        } else {
+            Vec::new()
+        };
+    }
+
+    let Ok(entries) = path.read_dir() else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .flat_map(|entry| search_recursive(root, layout, entry.path()))
+        .collect()
+}
+
+pub(super) fn seal(content: &str) -> String {
+    let checksum = crc32fast::hash(content.as_bytes());
+    format!("{CHECKSUM_HEADER}{checksum:08x}\n{content}")
 }
 
-fn get_file_name(path: PathBuf, deep: usize) -> String {
-    let mut iter = path.iter();
-    iter.next_back();
+// length of content in a file of given length, head is the beginning of file
+pub(super) fn content_length(head: &[u8], length: u64) -> u64 {
+    if head.starts_with(CHECKSUM_HEADER.as_bytes()) {
+        // header, eight (8) hex digits and a newline
+        length.saturating_sub(CHECKSUM_HEADER.len() as u64 + 9)
+    } else {
+        length
+    }
+}
+
+pub(super) fn unseal(raw: String) -> crate::embedded::Result<Record> {
+    let Some(sealed) = raw.strip_prefix(CHECKSUM_HEADER) else {
+        return Ok(Record::Legacy(raw));
+    };
+
+    let (checksum, content) = sealed
+        .split_once('\n')
+        .ok_or(err!(embedded, CorruptedValue))?;
+
+    let checksum = u32::from_str_radix(checksum, 16).map_err(|_| err!(embedded, CorruptedValue))?;
+
+    if crc32fast::hash(content.as_bytes()) != checksum {
+        return Err(err!(embedded, CorruptedValue));
+    }
 
-    (0..deep + 1)
-        .map(|_| iter.next_back().unwrap().to_str().unwrap().to_string())
-        .collect::<Vec<_>>()
-        .iter_mut()
-        .rev()
-        .map(mem::take)
-        .collect::<Vec<_>>()
-        .join("")
+    Ok(Record::Checked(content.to_string()))
 }
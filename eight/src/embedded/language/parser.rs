@@ -1,9 +1,15 @@
 use super::token::Token;
 use crate::{
-    embedded::{messaging::Request, Error, Result},
+    embedded::{
+        messaging::{Aggregation, RateLimiter, Request},
+        Error, Result,
+    },
     err,
 };
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{Duration, UNIX_EPOCH},
+};
 
 pub(super) struct Parser {
     env: HashMap<String, String>,
@@ -44,7 +50,56 @@ impl Parser {
             "decr" | "DECR" => self.parse_decrement(tokens),
             "search" | "SEARCH" => self.parse_search(tokens),
             "flush" | "FLUSH" => self.parse_flush(tokens),
+            "backup" | "BACKUP" => self.parse_backup(tokens),
             "downgrade" | "DOWNGRADE" => self.parse_downgrade(tokens),
+            "publish" | "PUBLISH" => self.parse_publish(tokens),
+            "subscribe" | "SUBSCRIBE" => self.parse_subscribe(tokens),
+            "psubscribe" | "PSUBSCRIBE" => self.parse_pattern_subscribe(tokens),
+            "unsubscribe" | "UNSUBSCRIBE" => self.parse_unsubscribe(tokens),
+            "lpush" | "LPUSH" => self.parse_left_push(tokens),
+            "rpush" | "RPUSH" => self.parse_right_push(tokens),
+            "lpop" | "LPOP" => self.parse_left_pop(tokens),
+            "blpop" | "BLPOP" => self.parse_blocking_left_pop(tokens),
+            "wait" | "WAIT" => self.parse_wait(tokens),
+            "rename" | "RENAME" => self.parse_rename(tokens),
+            "renamenx" | "RENAMENX" => self.parse_rename_nx(tokens),
+            "copy" | "COPY" => self.parse_copy(tokens),
+            "setnx" | "SETNX" => self.parse_set_nx(tokens),
+            "getset" | "GETSET" => self.parse_get_set(tokens),
+            "getdel" | "GETDEL" => self.parse_get_delete(tokens),
+            "append" | "APPEND" => self.parse_append(tokens),
+            "strlen" | "STRLEN" => self.parse_length(tokens),
+            "getrange" | "GETRANGE" => self.parse_get_range(tokens),
+            "meta" | "META" | "object" | "OBJECT" => self.parse_metadata(tokens),
+            "history" | "HISTORY" => self.parse_history(tokens),
+            "getat" | "GETAT" => self.parse_get_at(tokens),
+            "create" | "CREATE" => self.parse_create_index(tokens),
+            "drop" | "DROP" => self.parse_drop_index(tokens),
+            "find" | "FIND" => self.parse_find(tokens),
+            "ftindex" | "FTINDEX" => self.parse_full_text_index(tokens),
+            "ftdrop" | "FTDROP" => self.parse_full_text_drop(tokens),
+            "ftsearch" | "FTSEARCH" => self.parse_full_text_search(tokens),
+            "ratelimit" | "RATELIMIT" => self.parse_rate_limit(tokens),
+            "lock" | "LOCK" => self.parse_lock(tokens, "Lock"),
+            "renew" | "RENEW" => self.parse_lock(tokens, "Renew"),
+            "unlock" | "UNLOCK" => self.parse_unlock(tokens),
+            "xadd" | "XADD" => self.parse_stream_add(tokens),
+            "xtrim" | "XTRIM" => self.parse_stream_trim(tokens),
+            "xrange" | "XRANGE" => self.parse_stream_range(tokens),
+            "xread" | "XREAD" => self.parse_stream_read(tokens),
+            "xgroup" | "XGROUP" => self.parse_stream_group(tokens),
+            "xreadgroup" | "XREADGROUP" => self.parse_stream_read_group(tokens),
+            "xack" | "XACK" => self.parse_stream_ack(tokens),
+            "pfadd" | "PFADD" => self.parse_hyper_log_log_add(tokens),
+            "pfcount" | "PFCOUNT" => self.parse_hyper_log_log_count(tokens),
+            "pfmerge" | "PFMERGE" => self.parse_hyper_log_log_merge(tokens),
+            "bfreserve" | "BFRESERVE" => self.parse_bloom_reserve(tokens),
+            "bfadd" | "BFADD" => self.parse_bloom_add(tokens),
+            "bfexists" | "BFEXISTS" => self.parse_bloom_exists(tokens),
+            "tscreate" | "TSCREATE" => self.parse_time_series_create(tokens),
+            "tsadd" | "TSADD" => self.parse_time_series_add(tokens),
+            "tsrange" | "TSRANGE" => self.parse_time_series_range(tokens),
+            "tsrule" | "TSRULE" => self.parse_time_series_rule(tokens),
             _ => Err(err!("Command not found", command)),
         }?;
 
@@ -71,13 +126,19 @@ impl Parser {
     }
 
     fn parse_set(&mut self, tokens: Vec<Token>) -> Result<Request> {
-        if tokens.len() != 3 {
-            Err(err!("Set command requires two (2) argument", tokens[0]))
-        } else {
-            let (key, value) = (&tokens[1], &tokens[2]);
-            let (key, value) = (self.fetch_env(&key.value), self.fetch_env(&value.value));
+        if tokens.len() != 3 && tokens.len() != 4 {
+            return Err(err!("Set command requires two (2) argument", tokens[0]));
+        }
+
+        let (key, value) = (&tokens[1], &tokens[2]);
+        let (key, value) = (self.fetch_env(&key.value), self.fetch_env(&value.value));
 
-            Ok(Request::Set(key, value))
+        // optional condition, only literal
+        match tokens.get(3).map(|token| token.value.as_str()) {
+            None => Ok(Request::Set(key, value)),
+            Some("nx" | "NX") => Ok(Request::SetNx(key, value)),
+            Some("xx" | "XX") => Ok(Request::SetXx(key, value)),
+            Some(_) => Err(err!("Set condition must be either NX or XX", tokens[3])),
         }
     }
 
@@ -173,6 +234,15 @@ impl Parser {
         }
     }
 
+    fn parse_backup(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 2 {
+            Err(err!("Backup command requires one (1) argument", tokens[0]))
+        } else {
+            let path = self.fetch_env(&tokens[1].value);
+            Ok(Request::Backup(path))
+        }
+    }
+
     fn parse_downgrade(&mut self, tokens: Vec<Token>) -> Result<Request> {
         if tokens.len() != 1 {
             Err(err!(
@@ -183,4 +253,931 @@ impl Parser {
             Ok(Request::DowngradePermission)
         }
     }
+
+    fn parse_publish(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!("Publish command requires two (2) argument", tokens[0]))
+        } else {
+            let (channel, message) = (&tokens[1], &tokens[2]);
+            let (channel, message) = (
+                self.fetch_env(&channel.value),
+                self.fetch_env(&message.value),
+            );
+
+            Ok(Request::Publish(channel, message))
+        }
+    }
+
+    fn parse_subscribe(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 2 {
+            Err(err!(
+                "Subscribe command requires one (1) argument",
+                tokens[0]
+            ))
+        } else {
+            let channel = self.fetch_env(&tokens[1].value);
+            Ok(Request::Subscribe(channel))
+        }
+    }
+
+    fn parse_pattern_subscribe(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 2 {
+            Err(err!(
+                "Pattern subscribe command requires one (1) argument",
+                tokens[0]
+            ))
+        } else {
+            let pattern = self.fetch_env(&tokens[1].value);
+            Ok(Request::PatternSubscribe(pattern))
+        }
+    }
+
+    fn parse_unsubscribe(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 2 {
+            Err(err!(
+                "Unsubscribe command requires one (1) argument",
+                tokens[0]
+            ))
+        } else {
+            let name = self.fetch_env(&tokens[1].value);
+            Ok(Request::Unsubscribe(name))
+        }
+    }
+
+    fn parse_left_push(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!(
+                "Left push command requires two (2) argument",
+                tokens[0]
+            ))
+        } else {
+            let (key, value) = (&tokens[1], &tokens[2]);
+            let (key, value) = (self.fetch_env(&key.value), self.fetch_env(&value.value));
+
+            Ok(Request::LeftPush(key, value))
+        }
+    }
+
+    fn parse_right_push(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!(
+                "Right push command requires two (2) argument",
+                tokens[0]
+            ))
+        } else {
+            let (key, value) = (&tokens[1], &tokens[2]);
+            let (key, value) = (self.fetch_env(&key.value), self.fetch_env(&value.value));
+
+            Ok(Request::RightPush(key, value))
+        }
+    }
+
+    fn parse_left_pop(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 2 {
+            Err(err!(
+                "Left pop command requires one (1) argument",
+                tokens[0]
+            ))
+        } else {
+            let key = self.fetch_env(&tokens[1].value);
+            Ok(Request::LeftPop(key))
+        }
+    }
+
+    fn parse_blocking_left_pop(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            return Err(err!(
+                "Blocking left pop command requires two (2) argument",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let timeout = self.parse_timeout(&tokens[2], "blocking left pop")?;
+
+        Ok(Request::BlockingLeftPop(key, timeout))
+    }
+
+    fn parse_wait(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            return Err(err!("Wait command requires two (2) argument", tokens[0]));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let timeout = self.parse_timeout(&tokens[2], "wait")?;
+
+        Ok(Request::Wait(key, timeout))
+    }
+
+    fn parse_rename(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!("Rename command requires two (2) argument", tokens[0]))
+        } else {
+            let (from, to) = (&tokens[1], &tokens[2]);
+            let (from, to) = (self.fetch_env(&from.value), self.fetch_env(&to.value));
+
+            Ok(Request::Rename(from, to))
+        }
+    }
+
+    fn parse_rename_nx(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!(
+                "Rename if not exists command requires two (2) argument",
+                tokens[0]
+            ))
+        } else {
+            let (from, to) = (&tokens[1], &tokens[2]);
+            let (from, to) = (self.fetch_env(&from.value), self.fetch_env(&to.value));
+
+            Ok(Request::RenameNx(from, to))
+        }
+    }
+
+    fn parse_copy(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!("Copy command requires two (2) argument", tokens[0]))
+        } else {
+            let (from, to) = (&tokens[1], &tokens[2]);
+            let (from, to) = (self.fetch_env(&from.value), self.fetch_env(&to.value));
+
+            Ok(Request::Copy(from, to))
+        }
+    }
+
+    fn parse_set_nx(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!(
+                "Set if not exists command requires two (2) argument",
+                tokens[0]
+            ))
+        } else {
+            let (key, value) = (&tokens[1], &tokens[2]);
+            let (key, value) = (self.fetch_env(&key.value), self.fetch_env(&value.value));
+
+            Ok(Request::SetNx(key, value))
+        }
+    }
+
+    fn parse_get_set(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!("Get set command requires two (2) argument", tokens[0]))
+        } else {
+            let (key, value) = (&tokens[1], &tokens[2]);
+            let (key, value) = (self.fetch_env(&key.value), self.fetch_env(&value.value));
+
+            Ok(Request::GetSet(key, value))
+        }
+    }
+
+    fn parse_get_delete(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 2 {
+            Err(err!(
+                "Get delete command requires one (1) argument",
+                tokens[0]
+            ))
+        } else {
+            let key = self.fetch_env(&tokens[1].value);
+            Ok(Request::GetDelete(key))
+        }
+    }
+
+    fn parse_append(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!("Append command requires two (2) argument", tokens[0]))
+        } else {
+            let (key, value) = (&tokens[1], &tokens[2]);
+            let (key, value) = (self.fetch_env(&key.value), self.fetch_env(&value.value));
+
+            Ok(Request::Append(key, value))
+        }
+    }
+
+    fn parse_length(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 2 {
+            Err(err!("Length command requires one (1) argument", tokens[0]))
+        } else {
+            let key = self.fetch_env(&tokens[1].value);
+            Ok(Request::Length(key))
+        }
+    }
+
+    fn parse_get_range(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 4 {
+            return Err(err!(
+                "Get range command requires three (3) argument",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let start = self.parse_position(&tokens[2])?;
+        let end = self.parse_position(&tokens[3])?;
+
+        Ok(Request::GetRange(key, start, end))
+    }
+
+    fn parse_metadata(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 2 {
+            Err(err!(
+                "Metadata command requires one (1) argument",
+                tokens[0]
+            ))
+        } else {
+            let key = self.fetch_env(&tokens[1].value);
+            Ok(Request::Metadata(key))
+        }
+    }
+
+    fn parse_history(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 2 {
+            Err(err!("History command requires one (1) argument", tokens[0]))
+        } else {
+            let key = self.fetch_env(&tokens[1].value);
+            Ok(Request::History(key))
+        }
+    }
+
+    // time is a unix timestamp in milliseconds
+    fn parse_get_at(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            return Err(err!("Get at command requires two (2) argument", tokens[0]));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let millis = self
+            .fetch_env(&tokens[2].value)
+            .parse::<u64>()
+            .map_err(|_| {
+                err!(
+                    "Second argument for get at command must be a unix timestamp in milliseconds",
+                    tokens[2]
+                )
+            })?;
+
+        Ok(Request::GetAt(
+            key,
+            UNIX_EPOCH + Duration::from_millis(millis),
+        ))
+    }
+
+    // create index [name] on [prefix] field [path]
+    fn parse_create_index(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        let keywords = [(1, "index"), (3, "on"), (5, "field")];
+
+        if tokens.len() != 7
+            || keywords
+                .iter()
+                .any(|&(position, keyword)| !tokens[position].value.eq_ignore_ascii_case(keyword))
+        {
+            return Err(err!(
+                "Create index command must be written as CREATE INDEX [name] ON [prefix] FIELD [path]",
+                tokens[0]
+            ));
+        }
+
+        let (name, prefix) = (
+            self.fetch_env(&tokens[2].value),
+            self.fetch_env(&tokens[4].value),
+        );
+
+        // `$` starts the path, it's not a variable
+        let field = tokens[6].value.clone();
+
+        Ok(Request::CreateIndex(name, prefix, field))
+    }
+
+    fn parse_drop_index(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 || !tokens[1].value.eq_ignore_ascii_case("index") {
+            Err(err!(
+                "Drop index command must be written as DROP INDEX [name]",
+                tokens[0]
+            ))
+        } else {
+            let name = self.fetch_env(&tokens[2].value);
+            Ok(Request::DropIndex(name))
+        }
+    }
+
+    fn parse_find(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!("Find command requires two (2) argument", tokens[0]))
+        } else {
+            let (name, value) = (&tokens[1], &tokens[2]);
+            let (name, value) = (self.fetch_env(&name.value), self.fetch_env(&value.value));
+
+            Ok(Request::Find(name, value))
+        }
+    }
+
+    fn parse_full_text_index(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 2 {
+            Err(err!(
+                "Full-text index command requires one (1) argument",
+                tokens[0]
+            ))
+        } else {
+            let prefix = self.fetch_env(&tokens[1].value);
+            Ok(Request::FullTextIndex(prefix))
+        }
+    }
+
+    fn parse_full_text_drop(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 2 {
+            Err(err!(
+                "Full-text drop command requires one (1) argument",
+                tokens[0]
+            ))
+        } else {
+            let prefix = self.fetch_env(&tokens[1].value);
+            Ok(Request::FullTextDrop(prefix))
+        }
+    }
+
+    // words can be quoted or written as separate arguments
+    fn parse_full_text_search(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() < 3 {
+            return Err(err!(
+                "Full-text search command requires at least two (2) argument",
+                tokens[0]
+            ));
+        }
+
+        let prefix = self.fetch_env(&tokens[1].value);
+        let query = tokens[2..]
+            .iter()
+            .map(|token| self.fetch_env(&token.value))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Ok(Request::FullTextSearch(prefix, query))
+    }
+
+    // ratelimit [key] [limit] [window] [fixed|sliding|bucket]
+    fn parse_rate_limit(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 4 && tokens.len() != 5 {
+            return Err(err!(
+                "Rate limit command requires three (3) or four (4) argument",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let limit = self
+            .fetch_env(&tokens[2].value)
+            .parse::<usize>()
+            .map_err(|_| {
+                err!(
+                    "Limit for rate limit command must be a valid unsigned integer",
+                    tokens[2]
+                )
+            })?;
+        let window = self
+            .fetch_env(&tokens[3].value)
+            .parse::<u64>()
+            .map(Duration::from_millis)
+            .map_err(|_| {
+                err!(
+                    "Window for rate limit command must be a duration in milliseconds",
+                    tokens[3]
+                )
+            })?;
+
+        let algorithm = match tokens.get(4).map(|token| token.value.to_lowercase()) {
+            None => RateLimiter::default(),
+            Some(algorithm) => match algorithm.as_str() {
+                "fixed" => RateLimiter::FixedWindow,
+                "sliding" => RateLimiter::SlidingWindow,
+                "bucket" => RateLimiter::TokenBucket,
+                _ => {
+                    return Err(err!(
+                        "Rate limit algorithm must be either FIXED, SLIDING or BUCKET",
+                        tokens[4]
+                    ))
+                }
+            },
+        };
+
+        Ok(Request::RateLimit(key, limit, window, algorithm))
+    }
+
+    // lock/renew [name] [owner] [ttl]
+    fn parse_lock(&mut self, tokens: Vec<Token>, command: &str) -> Result<Request> {
+        if tokens.len() != 4 {
+            return Err(err!(
+                format!("{command} command requires three (3) argument"),
+                tokens[0]
+            ));
+        }
+
+        let name = self.fetch_env(&tokens[1].value);
+        let owner = self.fetch_env(&tokens[2].value);
+        let ttl = self
+            .fetch_env(&tokens[3].value)
+            .parse::<u64>()
+            .map(Duration::from_millis)
+            .map_err(|_| {
+                err!(
+                    format!("Third argument for {command} command must be a lease in milliseconds"),
+                    tokens[3]
+                )
+            })?;
+
+        if command == "Lock" {
+            Ok(Request::Lock(name, owner, ttl))
+        } else {
+            Ok(Request::Renew(name, owner, ttl))
+        }
+    }
+
+    // unlock [name] [owner]
+    fn parse_unlock(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            return Err(err!("Unlock command requires two (2) argument", tokens[0]));
+        }
+
+        Ok(Request::Unlock(
+            self.fetch_env(&tokens[1].value),
+            self.fetch_env(&tokens[2].value),
+        ))
+    }
+
+    // xadd [stream] (maxlen [count]) [field] [value] [field] [value]...
+    fn parse_stream_add(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        let (max, start) = match tokens.get(2).map(|token| token.value.as_str()) {
+            Some("maxlen" | "MAXLEN") if tokens.len() > 3 => {
+                let max = self.fetch_env(&tokens[3].value).parse().map_err(|_| {
+                    err!(
+                        "Maximum length for stream add command must be a valid unsigned integer",
+                        tokens[3]
+                    )
+                })?;
+
+                (Some(max), 4)
+            }
+            _ => (None, 2),
+        };
+
+        if tokens.len() < start + 2 || (tokens.len() - start) % 2 != 0 {
+            return Err(err!(
+                "Stream add command requires a stream and field value pairs",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let fields = tokens[start..]
+            .chunks(2)
+            .map(|pair| {
+                (
+                    self.fetch_env(&pair[0].value),
+                    self.fetch_env(&pair[1].value),
+                )
+            })
+            .collect();
+
+        Ok(Request::StreamAdd(key, fields, max))
+    }
+
+    // xtrim [stream] [count]
+    fn parse_stream_trim(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            return Err(err!(
+                "Stream trim command requires two (2) argument",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let max = self.fetch_env(&tokens[2].value).parse().map_err(|_| {
+            err!(
+                "Second argument for stream trim command must be a valid unsigned integer",
+                tokens[2]
+            )
+        })?;
+
+        Ok(Request::StreamTrim(key, max))
+    }
+
+    // xrange [stream] [start] [end] [count]
+    fn parse_stream_range(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 4 && tokens.len() != 5 {
+            return Err(err!(
+                "Stream range command requires three (3) or four (4) argument",
+                tokens[0]
+            ));
+        }
+
+        let (key, start, end) = (
+            self.fetch_env(&tokens[1].value),
+            self.fetch_env(&tokens[2].value),
+            self.fetch_env(&tokens[3].value),
+        );
+        let count = self.parse_count(tokens.get(4))?;
+
+        Ok(Request::StreamRange(key, start, end, count))
+    }
+
+    // xread [stream] [id] [count] [BLOCK timeout]
+    fn parse_stream_read(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        let block = tokens
+            .iter()
+            .position(|token| token.value.eq_ignore_ascii_case("block"));
+        let arguments = block.unwrap_or(tokens.len());
+
+        if !(3..=4).contains(&arguments) || block.is_some_and(|block| block + 2 != tokens.len()) {
+            return Err(err!(
+                "Stream read command must be written as XREAD [stream] [id] [count] BLOCK [timeout]",
+                tokens[0]
+            ));
+        }
+
+        let (key, after) = (
+            self.fetch_env(&tokens[1].value),
+            self.fetch_env(&tokens[2].value),
+        );
+        let count = self.parse_count(tokens.get(3).filter(|_| arguments == 4))?;
+
+        match block {
+            Some(block) => {
+                let timeout = self.parse_timeout(&tokens[block + 1], "stream read")?;
+                Ok(Request::BlockingStreamRead(key, after, count, timeout))
+            }
+            None => Ok(Request::StreamRead(key, after, count)),
+        }
+    }
+
+    // xgroup create [stream] [group] [timeout] [id] or xgroup destroy [stream] [group]
+    fn parse_stream_group(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        let action = tokens.get(1).map(|token| token.value.to_lowercase());
+
+        match (action.as_deref(), tokens.len()) {
+            (Some("create"), 5 | 6) => {
+                let (key, group) = (
+                    self.fetch_env(&tokens[2].value),
+                    self.fetch_env(&tokens[3].value),
+                );
+                let timeout = self
+                    .fetch_env(&tokens[4].value)
+                    .parse::<u64>()
+                    .map(Duration::from_millis)
+                    .map_err(|_| {
+                        err!(
+                            "Redelivery timeout for stream group command must be in milliseconds",
+                            tokens[4]
+                        )
+                    })?;
+                let start = tokens
+                    .get(5)
+                    .map(|token| self.fetch_env(&token.value))
+                    .unwrap_or_else(|| "0".to_string());
+
+                Ok(Request::StreamGroupCreate(key, group, start, timeout))
+            }
+            (Some("destroy"), 4) => Ok(Request::StreamGroupDestroy(
+                self.fetch_env(&tokens[2].value),
+                self.fetch_env(&tokens[3].value),
+            )),
+            _ => Err(err!(
+                "Stream group command must be written as XGROUP CREATE [stream] [group] [timeout] [id] or XGROUP DESTROY [stream] [group]",
+                tokens[0]
+            )),
+        }
+    }
+
+    // xreadgroup [stream] [group] [consumer] [count]
+    fn parse_stream_read_group(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 4 && tokens.len() != 5 {
+            return Err(err!(
+                "Stream read group command requires three (3) or four (4) argument",
+                tokens[0]
+            ));
+        }
+
+        let (key, group, consumer) = (
+            self.fetch_env(&tokens[1].value),
+            self.fetch_env(&tokens[2].value),
+            self.fetch_env(&tokens[3].value),
+        );
+        let count = self.parse_count(tokens.get(4))?;
+
+        Ok(Request::StreamReadGroup(key, group, consumer, count))
+    }
+
+    // xack [stream] [group] [id]...
+    fn parse_stream_ack(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() < 4 {
+            return Err(err!(
+                "Stream acknowledge command requires at least three (3) argument",
+                tokens[0]
+            ));
+        }
+
+        let (key, group) = (
+            self.fetch_env(&tokens[1].value),
+            self.fetch_env(&tokens[2].value),
+        );
+        let ids = self.fetch_all(&tokens[3..]);
+
+        Ok(Request::StreamAck(key, group, ids))
+    }
+
+    // pfadd [key] [item]...
+    fn parse_hyper_log_log_add(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() < 3 {
+            return Err(err!(
+                "HyperLogLog add command requires at least two (2) argument",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let items = self.fetch_all(&tokens[2..]);
+
+        Ok(Request::HyperLogLogAdd(key, items))
+    }
+
+    // pfcount [key]...
+    fn parse_hyper_log_log_count(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() < 2 {
+            return Err(err!(
+                "HyperLogLog count command requires at least one (1) argument",
+                tokens[0]
+            ));
+        }
+
+        Ok(Request::HyperLogLogCount(self.fetch_all(&tokens[1..])))
+    }
+
+    // pfmerge [destination] [source]...
+    fn parse_hyper_log_log_merge(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() < 3 {
+            return Err(err!(
+                "HyperLogLog merge command requires at least two (2) argument",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let sources = self.fetch_all(&tokens[2..]);
+
+        Ok(Request::HyperLogLogMerge(key, sources))
+    }
+
+    // bfreserve [key] [error rate] [capacity]
+    fn parse_bloom_reserve(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 4 {
+            return Err(err!(
+                "Bloom reserve command requires three (3) argument",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let error_rate = self
+            .fetch_env(&tokens[2].value)
+            .parse::<f64>()
+            .ok()
+            .filter(|rate| *rate > 0.0 && *rate < 1.0)
+            .ok_or_else(|| {
+                err!(
+                    "Error rate for bloom reserve command must be between zero (0) and one (1)",
+                    tokens[2]
+                )
+            })?;
+        let capacity = self
+            .fetch_env(&tokens[3].value)
+            .parse::<usize>()
+            .ok()
+            .filter(|capacity| *capacity > 0)
+            .ok_or_else(|| {
+                err!(
+                    "Capacity for bloom reserve command must be a positive integer",
+                    tokens[3]
+                )
+            })?;
+
+        Ok(Request::BloomReserve(key, error_rate, capacity))
+    }
+
+    fn parse_bloom_add(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!(
+                "Bloom add command requires two (2) argument",
+                tokens[0]
+            ))
+        } else {
+            let (key, item) = (&tokens[1], &tokens[2]);
+            let (key, item) = (self.fetch_env(&key.value), self.fetch_env(&item.value));
+
+            Ok(Request::BloomAdd(key, item))
+        }
+    }
+
+    fn parse_bloom_exists(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            Err(err!(
+                "Bloom exists command requires two (2) argument",
+                tokens[0]
+            ))
+        } else {
+            let (key, item) = (&tokens[1], &tokens[2]);
+            let (key, item) = (self.fetch_env(&key.value), self.fetch_env(&item.value));
+
+            Ok(Request::BloomExists(key, item))
+        }
+    }
+
+    fn parse_time_series_create(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 3 {
+            return Err(err!(
+                "Time series create command requires two (2) argument",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let retention = self.parse_millis(
+            &tokens[2],
+            "Retention for time series create command must be a duration in milliseconds",
+        )?;
+
+        Ok(Request::TimeSeriesCreate(
+            key,
+            Duration::from_millis(retention),
+        ))
+    }
+
+    // tsadd [key] [timestamp or *] [value]
+    fn parse_time_series_add(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 4 {
+            return Err(err!(
+                "Time series add command requires three (3) argument",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let time = match self.fetch_env(&tokens[2].value).as_str() {
+            "*" => None,
+            _ => Some(self.parse_millis(
+                &tokens[2],
+                "Timestamp for time series add command must be unix milliseconds or *",
+            )?),
+        };
+        let value = self
+            .fetch_env(&tokens[3].value)
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| {
+                err!(
+                    "Value for time series add command must be a number",
+                    tokens[3]
+                )
+            })?;
+
+        Ok(Request::TimeSeriesAdd(key, time, value))
+    }
+
+    // tsrange [key] [from or -] [to or +] [aggregation] [bucket]
+    fn parse_time_series_range(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 4 && tokens.len() != 6 {
+            return Err(err!(
+                "Time series range command requires three (3) or five (5) argument",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let from = match self.fetch_env(&tokens[2].value).as_str() {
+            "-" => 0,
+            _ => self.parse_millis(
+                &tokens[2],
+                "Start for time series range command must be unix milliseconds or -",
+            )?,
+        };
+        let to = match self.fetch_env(&tokens[3].value).as_str() {
+            "+" => u64::MAX,
+            _ => self.parse_millis(
+                &tokens[3],
+                "End for time series range command must be unix milliseconds or +",
+            )?,
+        };
+
+        let aggregation = match tokens.get(4..6) {
+            Some([aggregation, bucket]) => Some((
+                self.parse_aggregation(aggregation)?,
+                Duration::from_millis(self.parse_bucket(bucket, "range")?),
+            )),
+            _ => None,
+        };
+
+        Ok(Request::TimeSeriesRange(key, from, to, aggregation))
+    }
+
+    // tsrule [key] [aggregation] [bucket] [retention]
+    fn parse_time_series_rule(&mut self, tokens: Vec<Token>) -> Result<Request> {
+        if tokens.len() != 5 {
+            return Err(err!(
+                "Time series rule command requires four (4) argument",
+                tokens[0]
+            ));
+        }
+
+        let key = self.fetch_env(&tokens[1].value);
+        let aggregation = self.parse_aggregation(&tokens[2])?;
+        let bucket = self.parse_bucket(&tokens[3], "rule")?;
+        let retention = self.parse_millis(
+            &tokens[4],
+            "Retention for time series rule command must be a duration in milliseconds",
+        )?;
+
+        Ok(Request::TimeSeriesRule(
+            key,
+            aggregation,
+            Duration::from_millis(bucket),
+            Duration::from_millis(retention),
+        ))
+    }
+
+    fn parse_aggregation(&self, token: &Token) -> Result<Aggregation> {
+        match self.fetch_env(&token.value).to_lowercase().as_str() {
+            "avg" => Ok(Aggregation::Avg),
+            "min" => Ok(Aggregation::Min),
+            "max" => Ok(Aggregation::Max),
+            "sum" => Ok(Aggregation::Sum),
+            _ => Err(err!(
+                "Aggregation must be either AVG, MIN, MAX or SUM",
+                token
+            )),
+        }
+    }
+
+    fn parse_bucket(&self, token: &Token, command: &str) -> Result<u64> {
+        self.fetch_env(&token.value)
+            .parse::<u64>()
+            .ok()
+            .filter(|bucket| *bucket > 0)
+            .ok_or_else(|| {
+                err!(
+                    format!(
+                        "Bucket for time series {command} command must be a positive duration in milliseconds"
+                    ),
+                    token
+                )
+            })
+    }
+
+    fn parse_millis(&self, token: &Token, message: &str) -> Result<u64> {
+        self.fetch_env(&token.value)
+            .parse::<u64>()
+            .map_err(|_| err!(message.to_string(), token))
+    }
+
+    fn fetch_all(&self, tokens: &[Token]) -> Vec<String> {
+        tokens
+            .iter()
+            .map(|token| self.fetch_env(&token.value))
+            .collect()
+    }
+
+    // missing count means every entry
+    fn parse_count(&self, token: Option<&Token>) -> Result<usize> {
+        let Some(token) = token else {
+            return Ok(0);
+        };
+
+        self.fetch_env(&token.value).parse::<usize>().map_err(|_| {
+            err!(
+                "Count for stream commands must be a valid unsigned integer",
+                token
+            )
+        })
+    }
+
+    // positions may be negative to count from the end
+    fn parse_position(&self, token: &Token) -> Result<isize> {
+        self.fetch_env(&token.value).parse::<isize>().map_err(|_| {
+            err!(
+                "Positions for get range command must be valid integers",
+                token
+            )
+        })
+    }
+
+    // timeouts are written in milliseconds
+    fn parse_timeout(&self, token: &Token, command: &str) -> Result<Duration> {
+        self.fetch_env(&token.value)
+            .parse::<u64>()
+            .map(Duration::from_millis)
+            .map_err(|_| {
+                err!(
+                    format!(
+                        "Second argument for {command} command must be a timeout in milliseconds"
+                    ),
+                    token
+                )
+            })
+    }
 }
@@ -2,7 +2,11 @@ use super::{
     lexer::lex,
     parser::{CallType, Parser},
 };
-use crate::embedded::{messaging::Response, server::Server, Result};
+use crate::embedded::{
+    messaging::{Request, Response},
+    server::{Server, Subscription},
+    Result,
+};
 use std::{collections::HashMap, mem};
 
 pub(crate) struct QueryExecutor {
@@ -15,7 +19,11 @@ impl QueryExecutor {
         Self { source, env }
     }
 
-    pub async fn execute(&mut self, server: &Server) -> Result<Vec<Response>> {
+    pub async fn execute(
+        &mut self,
+        server: &Server,
+        subscription: Option<&Subscription>,
+    ) -> Result<Vec<Response>> {
         let collection = lex(mem::take(&mut self.source));
         let mut parser = Parser::new(mem::take(&mut self.env));
         let mut results = Vec::new();
@@ -23,16 +31,36 @@ impl QueryExecutor {
         for tokens in collection {
             let command = parser.execute(tokens)?;
 
+            // subscriptions belong to the caller rather than server, so they are changed right away
             match command {
                 CallType::Await(request) => {
-                    results.push(server.call(request).await?);
-                }
-                CallType::Spawn(request) => {
-                    server.cast(request).await?;
+                    let response = match subscription {
+                        Some(subscription) if changes_subscription(&request) => {
+                            server.subscription_request(request, subscription).await
+                        }
+                        _ => server.call(request).await?,
+                    };
+
+                    results.push(response);
                 }
+                CallType::Spawn(request) => match subscription {
+                    Some(subscription) if changes_subscription(&request) => {
+                        server.subscription_request(request, subscription).await;
+                    }
+                    _ => {
+                        server.cast(request).await?;
+                    }
+                },
             }
         }
 
         Ok(results)
     }
 }
+
+fn changes_subscription(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::Subscribe(_) | Request::PatternSubscribe(_) | Request::Unsubscribe(_)
+    )
+}
@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{Duration, UNIX_EPOCH},
+};
 
 use crate::embedded::{
     language::{lexer::Lexer, parser::CallType, parser::Parser, token::Token},
-    messaging::Request,
+    messaging::{Aggregation, RateLimiter, Request},
 };
 
 #[test]
@@ -99,11 +102,458 @@ fn test_execute_parser() {
         CallType::Await(Request::Flush)
     );
 
+    assert_eq!(
+        parser.execute(tokenize("backup $varA")).unwrap(),
+        CallType::Await(Request::Backup(a.clone()))
+    );
+
     assert_eq!(
         parser.execute(tokenize("downgrade")).unwrap(),
         CallType::Await(Request::DowngradePermission)
     );
 
+    assert_eq!(
+        parser.execute(tokenize("publish $varA $varB")).unwrap(),
+        CallType::Await(Request::Publish(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("subscribe $varA")).unwrap(),
+        CallType::Await(Request::Subscribe(a.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("psubscribe $varA")).unwrap(),
+        CallType::Await(Request::PatternSubscribe(a.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("unsubscribe $varA")).unwrap(),
+        CallType::Await(Request::Unsubscribe(a.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("lpush $varA $varB")).unwrap(),
+        CallType::Await(Request::LeftPush(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("rpush $varA $varB")).unwrap(),
+        CallType::Await(Request::RightPush(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("lpop $varA")).unwrap(),
+        CallType::Await(Request::LeftPop(a.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("blpop $varA $varC")).unwrap(),
+        CallType::Await(Request::BlockingLeftPop(
+            a.clone(),
+            Duration::from_millis(c as u64)
+        ))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("wait $varA 0")).unwrap(),
+        CallType::Await(Request::Wait(a.clone(), Duration::ZERO))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("rename $varA $varB")).unwrap(),
+        CallType::Await(Request::Rename(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("renamenx $varA $varB")).unwrap(),
+        CallType::Await(Request::RenameNx(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("copy $varA $varB")).unwrap(),
+        CallType::Await(Request::Copy(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("set $varA $varB NX")).unwrap(),
+        CallType::Await(Request::SetNx(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("set $varA $varB xx")).unwrap(),
+        CallType::Await(Request::SetXx(a.clone(), b.clone()))
+    );
+
+    assert!(parser.execute(tokenize("set $varA $varB ZZ")).is_err());
+
+    assert_eq!(
+        parser.execute(tokenize("setnx $varA $varB")).unwrap(),
+        CallType::Await(Request::SetNx(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("getset $varA $varB")).unwrap(),
+        CallType::Await(Request::GetSet(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("getdel $varA")).unwrap(),
+        CallType::Await(Request::GetDelete(a.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("append $varA $varB")).unwrap(),
+        CallType::Await(Request::Append(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("strlen $varA")).unwrap(),
+        CallType::Await(Request::Length(a.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("getrange $varA $varC -1")).unwrap(),
+        CallType::Await(Request::GetRange(a.clone(), c as isize, -1))
+    );
+
+    assert!(parser.execute(tokenize("getrange $varA $varB 1")).is_err());
+
+    assert_eq!(
+        parser.execute(tokenize("meta $varA")).unwrap(),
+        CallType::Await(Request::Metadata(a.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("OBJECT $varA")).unwrap(),
+        CallType::Await(Request::Metadata(a.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("history $varA")).unwrap(),
+        CallType::Await(Request::History(a.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("getat $varA 1500")).unwrap(),
+        CallType::Await(Request::GetAt(
+            a.clone(),
+            UNIX_EPOCH + Duration::from_millis(1500)
+        ))
+    );
+
+    assert!(parser.execute(tokenize("getat $varA $varB")).is_err());
+
+    assert_eq!(
+        parser
+            .execute(tokenize("CREATE INDEX by_email ON $varA FIELD $.email"))
+            .unwrap(),
+        CallType::Await(Request::CreateIndex(
+            "by_email".into(),
+            a.clone(),
+            "$.email".into()
+        ))
+    );
+
+    assert!(parser
+        .execute(tokenize("create index by_email in user: field $.email"))
+        .is_err());
+
+    assert_eq!(
+        parser.execute(tokenize("drop index by_email")).unwrap(),
+        CallType::Await(Request::DropIndex("by_email".into()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("find by_email $varB")).unwrap(),
+        CallType::Await(Request::Find("by_email".into(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("ftindex $varA")).unwrap(),
+        CallType::Await(Request::FullTextIndex(a.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("ftdrop $varA")).unwrap(),
+        CallType::Await(Request::FullTextDrop(a.clone()))
+    );
+
+    assert_eq!(
+        parser
+            .execute(tokenize("FTSEARCH $varA printer $varB"))
+            .unwrap(),
+        CallType::Await(Request::FullTextSearch(a.clone(), format!("printer {b}")))
+    );
+
+    assert!(parser.execute(tokenize("ftsearch $varA")).is_err());
+
+    assert_eq!(
+        parser.execute(tokenize("ratelimit $varA 10 1000")).unwrap(),
+        CallType::Await(Request::RateLimit(
+            a.clone(),
+            10,
+            Duration::from_secs(1),
+            RateLimiter::FixedWindow
+        ))
+    );
+
+    assert_eq!(
+        parser
+            .execute(tokenize("RATELIMIT $varA 5 60000 BUCKET"))
+            .unwrap(),
+        CallType::Await(Request::RateLimit(
+            a.clone(),
+            5,
+            Duration::from_secs(60),
+            RateLimiter::TokenBucket
+        ))
+    );
+
+    assert!(parser
+        .execute(tokenize("ratelimit $varA 5 1000 leaky"))
+        .is_err());
+    assert!(parser.execute(tokenize("ratelimit $varA -5 1000")).is_err());
+
+    assert_eq!(
+        parser.execute(tokenize("lock $varA $varB 5000")).unwrap(),
+        CallType::Await(Request::Lock(a.clone(), b.clone(), Duration::from_secs(5)))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("RENEW $varA $varB 5000")).unwrap(),
+        CallType::Await(Request::Renew(a.clone(), b.clone(), Duration::from_secs(5)))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("unlock $varA $varB")).unwrap(),
+        CallType::Await(Request::Unlock(a.clone(), b.clone()))
+    );
+
+    assert!(parser.execute(tokenize("lock $varA $varB")).is_err());
+    assert!(parser.execute(tokenize("renew $varA $varB soon")).is_err());
+
+    assert_eq!(
+        parser
+            .execute(tokenize("xadd $varA user $varB action login"))
+            .unwrap(),
+        CallType::Await(Request::StreamAdd(
+            a.clone(),
+            vec![
+                ("user".into(), b.clone()),
+                ("action".into(), "login".into())
+            ],
+            None
+        ))
+    );
+
+    assert_eq!(
+        parser
+            .execute(tokenize("xadd $varA MAXLEN 100 user $varB"))
+            .unwrap(),
+        CallType::Await(Request::StreamAdd(
+            a.clone(),
+            vec![("user".into(), b.clone())],
+            Some(100)
+        ))
+    );
+
+    assert!(parser.execute(tokenize("xadd $varA user")).is_err());
+    assert!(parser.execute(tokenize("xadd $varA maxlen 100")).is_err());
+    assert!(parser
+        .execute(tokenize("xadd $varA maxlen many user bob"))
+        .is_err());
+
+    assert_eq!(
+        parser.execute(tokenize("xtrim $varA 10")).unwrap(),
+        CallType::Await(Request::StreamTrim(a.clone(), 10))
+    );
+
+    assert!(parser.execute(tokenize("xtrim $varA")).is_err());
+
+    assert_eq!(
+        parser.execute(tokenize("XRANGE $varA - + 10")).unwrap(),
+        CallType::Await(Request::StreamRange(a.clone(), "-".into(), "+".into(), 10))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("xread $varA 0")).unwrap(),
+        CallType::Await(Request::StreamRead(a.clone(), "0".into(), 0))
+    );
+
+    assert_eq!(
+        parser
+            .execute(tokenize("xread $varA $ 5 BLOCK 1000"))
+            .unwrap(),
+        CallType::Await(Request::BlockingStreamRead(
+            a.clone(),
+            "$".into(),
+            5,
+            Duration::from_secs(1)
+        ))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("xread $varA 0 block 0")).unwrap(),
+        CallType::Await(Request::BlockingStreamRead(
+            a.clone(),
+            "0".into(),
+            0,
+            Duration::ZERO
+        ))
+    );
+
+    assert!(parser.execute(tokenize("xread $varA 0 block")).is_err());
+
+    assert_eq!(
+        parser
+            .execute(tokenize("xgroup create $varA workers 30000"))
+            .unwrap(),
+        CallType::Await(Request::StreamGroupCreate(
+            a.clone(),
+            "workers".into(),
+            "0".into(),
+            Duration::from_secs(30)
+        ))
+    );
+
+    assert_eq!(
+        parser
+            .execute(tokenize("XGROUP DESTROY $varA workers"))
+            .unwrap(),
+        CallType::Await(Request::StreamGroupDestroy(a.clone(), "workers".into()))
+    );
+
+    assert!(parser.execute(tokenize("xgroup $varA workers")).is_err());
+
+    assert_eq!(
+        parser
+            .execute(tokenize("xreadgroup $varA workers $varB 10"))
+            .unwrap(),
+        CallType::Await(Request::StreamReadGroup(
+            a.clone(),
+            "workers".into(),
+            b.clone(),
+            10
+        ))
+    );
+
+    assert_eq!(
+        parser
+            .execute(tokenize("xack $varA workers 1-0 2-0"))
+            .unwrap(),
+        CallType::Await(Request::StreamAck(
+            a.clone(),
+            "workers".into(),
+            vec!["1-0".into(), "2-0".into()]
+        ))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("pfadd $varA alice $varB")).unwrap(),
+        CallType::Await(Request::HyperLogLogAdd(
+            a.clone(),
+            vec!["alice".into(), b.clone()]
+        ))
+    );
+
+    assert!(parser.execute(tokenize("pfadd $varA")).is_err());
+
+    assert_eq!(
+        parser.execute(tokenize("PFCOUNT $varA $varB")).unwrap(),
+        CallType::Await(Request::HyperLogLogCount(vec![a.clone(), b.clone()]))
+    );
+
+    assert_eq!(
+        parser
+            .execute(tokenize("pfmerge week $varA $varB"))
+            .unwrap(),
+        CallType::Await(Request::HyperLogLogMerge(
+            "week".into(),
+            vec![a.clone(), b.clone()]
+        ))
+    );
+
+    assert_eq!(
+        parser
+            .execute(tokenize("bfreserve $varA 0.01 5000"))
+            .unwrap(),
+        CallType::Await(Request::BloomReserve(a.clone(), 0.01, 5000))
+    );
+
+    assert!(parser
+        .execute(tokenize("bfreserve $varA 1.5 5000"))
+        .is_err());
+    assert!(parser.execute(tokenize("bfreserve $varA 0.01 0")).is_err());
+
+    assert_eq!(
+        parser.execute(tokenize("BFADD $varA $varB")).unwrap(),
+        CallType::Await(Request::BloomAdd(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("bfexists $varA $varB")).unwrap(),
+        CallType::Await(Request::BloomExists(a.clone(), b.clone()))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("tscreate $varA 60000")).unwrap(),
+        CallType::Await(Request::TimeSeriesCreate(
+            a.clone(),
+            Duration::from_secs(60)
+        ))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("tsadd $varA 1000 1.5")).unwrap(),
+        CallType::Await(Request::TimeSeriesAdd(a.clone(), Some(1000), 1.5))
+    );
+
+    assert_eq!(
+        parser.execute(tokenize("TSADD $varA * -2")).unwrap(),
+        CallType::Await(Request::TimeSeriesAdd(a.clone(), None, -2.0))
+    );
+
+    assert!(parser.execute(tokenize("tsadd $varA now 1")).is_err());
+    assert!(parser.execute(tokenize("tsadd $varA 1000 NaN")).is_err());
+
+    assert_eq!(
+        parser.execute(tokenize("tsrange $varA - +")).unwrap(),
+        CallType::Await(Request::TimeSeriesRange(a.clone(), 0, u64::MAX, None))
+    );
+
+    assert_eq!(
+        parser
+            .execute(tokenize("tsrange $varA 10 20 avg 5"))
+            .unwrap(),
+        CallType::Await(Request::TimeSeriesRange(
+            a.clone(),
+            10,
+            20,
+            Some((Aggregation::Avg, Duration::from_millis(5)))
+        ))
+    );
+
+    assert!(parser.execute(tokenize("tsrange $varA 10 20 avg")).is_err());
+    assert!(parser
+        .execute(tokenize("tsrange $varA 10 20 median 5"))
+        .is_err());
+    assert!(parser
+        .execute(tokenize("tsrange $varA 10 20 avg 0"))
+        .is_err());
+
+    assert_eq!(
+        parser
+            .execute(tokenize("tsrule $varA SUM 60000 0"))
+            .unwrap(),
+        CallType::Await(Request::TimeSeriesRule(
+            a.clone(),
+            Aggregation::Sum,
+            Duration::from_secs(60),
+            Duration::ZERO
+        ))
+    );
+
     assert_eq!(
         parser.execute(tokenize("set? $varA $varB")).unwrap(),
         CallType::Spawn(Request::Set(a.clone(), b.clone()))
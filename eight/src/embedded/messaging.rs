@@ -1,5 +1,10 @@
 //! Types for messaging between server.
 
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+
 /// Allows you to send request to server.
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -22,8 +27,192 @@ pub enum Request {
     Search(String),
     /// Flush request. Returns [`Response::Ok`] on success.
     Flush,
+    /// Backup request with name. Creates a consistent snapshot in a new directory with the name, inside the directory set
+    /// with [`Server::set_snapshot_directory`]. Returns [`Response::Number`] of copied keys on success.
+    ///
+    /// [`Server::set_snapshot_directory`]: crate::embedded::server::Server::set_snapshot_directory
+    Backup(String),
     /// Downgrade permission. Returns [`Response::Ok`] on success.
     DowngradePermission,
+    /// Publish request with channel and message. Returns [`Response::Number`] of receivers on success.
+    Publish(String, String),
+    /// Subscribe to a channel. Requires a [`Subscription`], returns [`Response::Ok`] on success.
+    ///
+    /// [`Subscription`]: crate::embedded::server::Subscription
+    Subscribe(String),
+    /// Subscribe to channels matching a pattern, `*` matches any text and `?` matches a single character. Requires a [`Subscription`], returns [`Response::Ok`] on success.
+    ///
+    /// [`Subscription`]: crate::embedded::server::Subscription
+    PatternSubscribe(String),
+    /// Unsubscribe from a channel or pattern. Requires a [`Subscription`], returns [`Response::Boolean`] of whether it was subscribed on success.
+    ///
+    /// [`Subscription`]: crate::embedded::server::Subscription
+    Unsubscribe(String),
+    /// Push value to the front of list. Missing key is treated as an empty list. Returns [`Response::Number`] of list length on success.
+    LeftPush(String, String),
+    /// Push value to the back of list. Missing key is treated as an empty list. Returns [`Response::Number`] of list length on success.
+    RightPush(String, String),
+    /// Remove and return the first value of list, key is deleted when list becomes empty. Returns [`Response::Text`] on success.
+    LeftPop(String),
+    /// Same with [`Request::LeftPop`], but waits until list has a value or timeout is reached. Zero timeout waits forever.
+    /// Returns [`Response::Text`] on success.
+    BlockingLeftPop(String, Duration),
+    /// Wait until key exists, or timeout is reached. Returns right away if key already exists, zero timeout waits forever.
+    /// Returns [`Response::Ok`] on success.
+    Wait(String, Duration),
+    /// Rename a key, replacing target key if it exists. Returns [`Response::Ok`] on success.
+    Rename(String, String),
+    /// Rename a key if target key doesn't exist. Returns [`Response::Boolean`] of whether key is renamed on success.
+    RenameNx(String, String),
+    /// Copy a key if target key doesn't exist. Returns [`Response::Boolean`] of whether key is copied on success.
+    Copy(String, String),
+    /// Set request with key and value, only if key doesn't exist. Returns [`Response::Boolean`] of whether value is set on success.
+    SetNx(String, String),
+    /// Set request with key and value, only if key exists. Returns [`Response::Boolean`] of whether value is set on success.
+    SetXx(String, String),
+    /// Set request that returns previous value. Returns [`Response::Text`] on success, [`Response::Ok`] if key didn't exist.
+    GetSet(String, String),
+    /// Delete request that returns deleted value. Returns [`Response::Text`] on success.
+    GetDelete(String),
+    /// Append request with key and value, missing key is created. Returns [`Response::Number`] of new length in characters on success.
+    Append(String, String),
+    /// Length request with key. Returns [`Response::Number`] of characters on success, zero (0) if key doesn't exist.
+    Length(String),
+    /// Get request with key, start and end characters. Both ends are included, negative positions count from the end.
+    /// Returns [`Response::Text`] on success.
+    GetRange(String, isize, isize),
+    /// Metadata request with key. Returns [`Response::Map`] on success with:
+    ///
+    /// - `size`: size of value in bytes.
+    /// - `type`: `list`, `stream`, `timeseries`, `hyperloglog` or `bloom` for typed values, `string` for anything else.
+    /// - `created` and `updated`: unix timestamps in milliseconds, only if storage keeps them.
+    /// - `ttl`: remaining lifetime in milliseconds, only if key expires.
+    Metadata(String),
+    /// History request with key. Returns [`Response::List`] of versions on success, oldest first.
+    /// Every version is a [`Response::Map`] with `time` as unix timestamp in milliseconds and `value`, `value` is missing for deletions.
+    History(String),
+    /// Get request with key and time, reads value from history. Returns [`Response::Text`] on success.
+    GetAt(String, SystemTime),
+    /// Create index request with name, key prefix and field path like `$.email`. Fills index from existing keys.
+    /// Returns [`Response::Number`] of indexed keys on success.
+    CreateIndex(String, String, String),
+    /// Drop index request with name. Returns [`Response::Ok`] on success.
+    DropIndex(String),
+    /// Find request with index name and field value. Returns [`Response::TextList`] of keys on success.
+    Find(String, String),
+    /// Full-text index request with key prefix. Indexes words of every value under prefix.
+    /// Returns [`Response::Number`] of indexed keys on success.
+    FullTextIndex(String),
+    /// Full-text drop request with key prefix. Returns [`Response::Ok`] on success.
+    FullTextDrop(String),
+    /// Full-text search request with indexed key prefix and words. Returns [`Response::TextList`] of keys matching any word on success,
+    /// best match first.
+    FullTextSearch(String, String),
+    /// Rate limit request with key, limit, window and algorithm. Counts one request atomically, state is kept as the value of key.
+    /// Returns [`Response::Map`] on success with:
+    ///
+    /// - `allowed`: whether request is allowed, as [`Response::Boolean`].
+    /// - `remaining`: requests left in quota.
+    /// - `retry_after`: milliseconds until a request is allowed again, zero (0) if this one is allowed.
+    RateLimit(String, usize, Duration, RateLimiter),
+    /// Lock request with name, owner and lease duration. Lock expires after lease unless renewed, state is kept as the value of name.
+    /// Returns fencing token as [`Response::Number`] on success, which grows every time lock changes hands.
+    /// Deleting name or flushing starts tokens from one (1) again.
+    Lock(String, String, Duration),
+    /// Unlock request with name and owner. Returns [`Response::Ok`] on success.
+    Unlock(String, String),
+    /// Renew request with name, owner and lease duration. Lease starts over from now.
+    /// Returns fencing token as [`Response::Number`] on success.
+    Renew(String, String, Duration),
+    /// Stream add request with stream key, field value pairs and maximum entry count, [`None`] for no limit.
+    /// Appends an entry with a generated ID, IDs always grow. Oldest entries over maximum are removed.
+    /// Returns ID as [`Response::Text`] on success.
+    ///
+    /// Stream is kept as a single value, every change reads and writes all of its entries and pending deliveries.
+    StreamAdd(String, Vec<(String, String)>, Option<usize>),
+    /// Stream trim request with stream key and maximum entry count. Oldest entries over maximum are removed,
+    /// with their pending deliveries in consumer groups. Returns removed entry count as [`Response::Number`] on success.
+    StreamTrim(String, usize),
+    /// Stream range request with stream key, start and end IDs, both inclusive, and entry count, zero (0) for all.
+    /// `-` and `+` are the first and last IDs. Returns [`Response::List`] of entries on success.
+    ///
+    /// Every entry is a [`Response::Map`] with `id` and `fields` as a [`Response::Map`] of field values.
+    StreamRange(String, String, String, usize),
+    /// Stream read request with stream key, ID to read after and entry count, zero (0) for all. `$` is the last ID.
+    /// Returns [`Response::List`] of entries on success, like [`Request::StreamRange`].
+    StreamRead(String, String, usize),
+    /// Same as [`Request::StreamRead`], but waits for new entries until timeout if there is none. Zero (0) timeout waits forever.
+    BlockingStreamRead(String, String, usize, Duration),
+    /// Consumer group create request with stream key, group name, ID to start after and redelivery timeout.
+    /// Entries not acknowledged within timeout are delivered again. Creates stream if it doesn't exist.
+    /// Returns [`Response::Ok`] on success.
+    StreamGroupCreate(String, String, String, Duration),
+    /// Consumer group destroy request with stream key and group name. Returns [`Response::Ok`] on success.
+    StreamGroupDestroy(String, String),
+    /// Consumer group read request with stream key, group name, consumer name and entry count, zero (0) for all.
+    /// Delivers timed out entries first, then new entries. Returns [`Response::List`] of entries on success, like [`Request::StreamRange`].
+    StreamReadGroup(String, String, String, usize),
+    /// Acknowledge request with stream key, group name and entry IDs. Returns [`Response::Number`] of acknowledged entries on success.
+    StreamAck(String, String, Vec<String>),
+    /// HyperLogLog add request with key and items. Returns [`Response::Boolean`] on success, true if estimated count may have changed.
+    HyperLogLogAdd(String, Vec<String>),
+    /// HyperLogLog count request with keys. Returns estimated count of unique items in union of keys as [`Response::Number`] on success.
+    HyperLogLogCount(Vec<String>),
+    /// HyperLogLog merge request with destination key and source keys. Destination is merged with sources.
+    /// Returns [`Response::Ok`] on success.
+    HyperLogLogMerge(String, Vec<String>),
+    /// Bloom filter reserve request with key, false positive rate and capacity. Returns [`Response::Ok`] on success.
+    BloomReserve(String, f64, usize),
+    /// Bloom filter add request with key and item. Filter is created with 1% error rate and a capacity of 1000 if it doesn't exist.
+    /// Returns [`Response::Boolean`] on success, false if item may have been added before.
+    BloomAdd(String, String),
+    /// Bloom filter exists request with key and item. Returns [`Response::Boolean`] on success, false if item is definitely not added.
+    BloomExists(String, String),
+    /// Time series create request with key and retention, it must be positive. Retention is counted back from the latest sample.
+    /// Returns [`Response::Ok`] on success.
+    ///
+    /// Series is kept as a single value, every sample rewrites all samples kept for retention.
+    TimeSeriesCreate(String, Duration),
+    /// Time series add request with key, unix timestamp in milliseconds, [`None`] for now, and value. A sample at the same time is replaced.
+    /// Series is created with a day of retention if it doesn't exist. Returns timestamp as [`Response::Number`] on success.
+    TimeSeriesAdd(String, Option<u64>, f64),
+    /// Time series range request with key, start and end timestamps, both inclusive, and optional aggregation with bucket size.
+    /// Returns [`Response::List`] of samples on success, every sample is a [`Response::Map`] with `time` and `value` as [`Response::Text`].
+    ///
+    /// Aggregated samples are timed at the start of their bucket, downsampled samples are used if there is a rule with same aggregation and bucket size.
+    TimeSeriesRange(String, u64, u64, Option<(Aggregation, Duration)>),
+    /// Time series rule request with key, aggregation, bucket size and a positive retention. New samples are downsampled into buckets as they are added,
+    /// and downsampled samples are kept for retention even after raw samples are gone. Returns [`Response::Ok`] on success.
+    TimeSeriesRule(String, Aggregation, Duration, Duration),
+}
+
+/// Algorithm of [`Request::RateLimit`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum RateLimiter {
+    /// Allow limit requests in every window, window starts with the first request.
+    #[default]
+    FixedWindow,
+    /// Like fixed window, but requests of previous window count by how much it overlaps with the last window duration. Avoids bursts at window edges.
+    SlidingWindow,
+    /// Bucket holds up to limit tokens and refills limit tokens every window, every request takes a token.
+    TokenBucket,
+}
+
+/// Aggregation of time series samples in a bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Aggregation {
+    /// Average of samples.
+    Avg,
+    /// Smallest sample.
+    Min,
+    /// Largest sample.
+    Max,
+    /// Sum of samples.
+    Sum,
 }
 
 /// Allows you to get response from server.
@@ -42,6 +231,123 @@ pub enum Response {
     Boolean(bool),
     /// Success, with text list returned from server.
     TextList(Vec<String>),
+    /// Success, with named values returned from server.
+    Map(BTreeMap<String, Response>),
+    /// Success, with list of values returned from server.
+    List(Vec<Response>),
     /// Error, with error value returned from server.
     Error(crate::embedded::Error),
 }
+
+/// Change of a key, sent to watchers after a mutating [`Request`] succeeds. See [`Server::watch`].
+///
+/// [`Server::watch`]: crate::embedded::server::Server::watch
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum KeyEvent {
+    /// Key is created or updated, including increments, decrements and list changes.
+    Set(String),
+    /// Key is deleted.
+    Delete(String),
+    /// Key is expired. Reserved for expiring keys, server doesn't send it yet.
+    Expire(String),
+    /// Every key is deleted. Sent to every watcher, whatever their prefix is.
+    Flush,
+    /// Watcher was too slow and given number of events are dropped since the previous event.
+    Lagged(u64),
+}
+
+impl KeyEvent {
+    /// Events request causes if it succeeds.
+    pub(crate) fn of(request: &Request) -> Vec<Self> {
+        match request {
+            Request::Set(key, _)
+            | Request::Increment(key, _)
+            | Request::Decrement(key, _)
+            | Request::LeftPush(key, _)
+            | Request::RightPush(key, _)
+            | Request::LeftPop(key)
+            | Request::BlockingLeftPop(key, _)
+            | Request::Copy(_, key)
+            | Request::SetNx(key, _)
+            | Request::SetXx(key, _)
+            | Request::GetSet(key, _)
+            | Request::Append(key, _)
+            | Request::RateLimit(key, _, _, _)
+            | Request::Lock(key, _, _)
+            | Request::Unlock(key, _)
+            | Request::Renew(key, _, _)
+            | Request::StreamAdd(key, _, _)
+            | Request::StreamTrim(key, _)
+            | Request::StreamGroupCreate(key, _, _, _)
+            | Request::StreamGroupDestroy(key, _)
+            | Request::StreamReadGroup(key, _, _, _)
+            | Request::StreamAck(key, _, _)
+            | Request::HyperLogLogAdd(key, _)
+            | Request::HyperLogLogMerge(key, _)
+            | Request::BloomReserve(key, _, _)
+            | Request::BloomAdd(key, _)
+            | Request::TimeSeriesCreate(key, _)
+            | Request::TimeSeriesAdd(key, _, _)
+            | Request::TimeSeriesRule(key, _, _, _) => vec![KeyEvent::Set(key.clone())],
+            Request::Delete(key) | Request::GetDelete(key) => vec![KeyEvent::Delete(key.clone())],
+            Request::Rename(from, to) | Request::RenameNx(from, to) if from != to => {
+                vec![KeyEvent::Delete(from.clone()), KeyEvent::Set(to.clone())]
+            }
+            Request::Flush => vec![KeyEvent::Flush],
+            Request::Get(_)
+            | Request::Exists(_)
+            | Request::Search(_)
+            | Request::Backup(_)
+            | Request::DowngradePermission
+            | Request::Publish(_, _)
+            | Request::Subscribe(_)
+            | Request::PatternSubscribe(_)
+            | Request::Unsubscribe(_)
+            | Request::Wait(_, _)
+            | Request::Rename(_, _)
+            | Request::RenameNx(_, _)
+            | Request::Length(_)
+            | Request::GetRange(_, _, _)
+            | Request::Metadata(_)
+            | Request::History(_)
+            | Request::GetAt(_, _)
+            | Request::CreateIndex(_, _, _)
+            | Request::DropIndex(_)
+            | Request::Find(_, _)
+            | Request::FullTextIndex(_)
+            | Request::FullTextDrop(_)
+            | Request::FullTextSearch(_, _)
+            | Request::StreamRange(_, _, _, _)
+            | Request::StreamRead(_, _, _)
+            | Request::BlockingStreamRead(_, _, _, _)
+            | Request::HyperLogLogCount(_)
+            | Request::BloomExists(_, _)
+            | Request::TimeSeriesRange(_, _, _, _) => vec![],
+        }
+    }
+
+    /// Key event is about. [`None`] for events that are not about a single key.
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            KeyEvent::Set(key) | KeyEvent::Delete(key) | KeyEvent::Expire(key) => Some(key),
+            KeyEvent::Flush | KeyEvent::Lagged(_) => None,
+        }
+    }
+}
+
+/// Message published to a channel, received through a [`Subscription`].
+///
+/// [`Subscription`]: crate::embedded::server::Subscription
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Message {
+    /// Channel message is published to.
+    pub channel: String,
+    /// Pattern matched the channel, [`None`] if subscribed to the channel itself.
+    pub pattern: Option<String>,
+    /// Message content.
+    pub message: String,
+}
@@ -26,12 +26,88 @@ pub enum Error {
     DeleteKeyFail,
     #[error("Removing a directory failed due to filesystem error")]
     DirRemoveFail,
+    #[error("Value is corrupted, checksum does not match")]
+    CorruptedValue,
+    #[error("Checking storage failed due to filesystem error")]
+    FsckFail,
+    #[error("Writing dump failed")]
+    DumpWriteFail,
+    #[error("Reading dump failed")]
+    DumpReadFail,
+    #[error("Dump is malformed, truncated or has an unsupported version")]
+    InvalidDump,
+    #[error("Creating snapshot failed")]
+    SnapshotFail,
+    #[error("Snapshot path already exists")]
+    SnapshotPathExists,
+    #[error("Backup requires a snapshot directory")]
+    SnapshotDirectoryMissing,
+    #[error("Backup name must be a single directory name")]
+    InvalidSnapshotName,
+    #[error("Opening database failed")]
+    DatabaseOpenFail,
+    #[error("Database query failed")]
+    DatabaseQueryFail,
+    #[error("Subscribing requires a connection")]
+    SubscriptionRequired,
+    #[error("Value is not a list")]
+    ListParseFail,
+    #[error("List is empty")]
+    ListEmpty,
+    #[error("Waiting timed out")]
+    WaitTimeout,
+    #[error("History is not kept for key")]
+    HistoryDisabled,
+    #[error("No version of key is kept for given time")]
+    VersionMissing,
+    #[error("Index name must be alphanumeric and field must be a path like $.field")]
+    InvalidIndex,
+    #[error("Index already exists")]
+    IndexExists,
+    #[error("Index is not found")]
+    IndexNotFound,
+    #[error("Value is not a rate limit state")]
+    RateLimitParseFail,
+    #[error("Value is not a lock")]
+    LockParseFail,
+    #[error("Lock is held by another owner")]
+    LockHeld,
+    #[error("Lock is not held by owner")]
+    LockNotHeld,
+    #[error("Value is not a stream")]
+    StreamParseFail,
+    #[error("Stream ID is not valid")]
+    InvalidStreamId,
+    #[error("Consumer group already exists")]
+    GroupExists,
+    #[error("Consumer group is not found")]
+    GroupNotFound,
+    #[error("Value is not a HyperLogLog")]
+    HyperLogLogParseFail,
+    #[error("Value is not a Bloom filter")]
+    BloomParseFail,
+    #[error("Bloom filter already exists")]
+    FilterExists,
+    #[error("Bloom filter error rate must be between 0 and 1, capacity must be positive and filter must fit in 8 MiB")]
+    InvalidFilter,
+    #[error("Value is not a time series")]
+    SeriesParseFail,
+    #[error("Time series already exists")]
+    SeriesExists,
+    #[error("Downsampling rule already exists")]
+    RuleExists,
+    #[error("Time series retention must be positive")]
+    InvalidRetention,
     #[error("Value must be a valid unsigned integer")]
     UIntParseFail,
     #[error("Sending message failed")]
     SendFail,
     #[error("Receive message failed")]
     RecvFail,
+    #[error("Key is reserved")]
+    ReservedKey,
+    #[error("Server is overloaded, request queue or blocking request limit is full")]
+    Overloaded,
     #[error("Receive message timeout")]
     RecvTimeout,
     #[error("Nothing to execute")]
@@ -40,6 +116,10 @@ pub enum Error {
     CommandError(String, usize, usize),
     #[error("You don't have a permission to perform this operation")]
     PermissionFailure,
+    #[error("Value authentication failed, it is either tampered or encrypted with another key")]
+    DecryptionFail,
+    #[error("Encryption key for value is not found in keyring")]
+    EncryptionKeyNotFound,
     #[error("{0}")]
     Custom(String),
 }
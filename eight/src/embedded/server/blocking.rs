@@ -0,0 +1,93 @@
+//! Wake-ups for requests waiting on a key. Waiting requests hold nothing but a [`Notify`].
+
+use crate::embedded::messaging::KeyEvent;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use tokio::{
+    sync::Notify,
+    time::{self, Instant},
+};
+
+#[derive(Debug, Default)]
+pub(super) struct Waiters {
+    keys: Mutex<HashMap<String, Arc<Notify>>>,
+}
+
+impl Waiters {
+    pub fn register(self: &Arc<Self>, key: &str) -> Waiter {
+        let notify = Arc::clone(
+            self.keys
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(key.to_string())
+                .or_default(),
+        );
+
+        Waiter {
+            waiters: Arc::clone(self),
+            key: key.to_string(),
+            notify,
+        }
+    }
+
+    pub fn wake(&self, event: &KeyEvent) {
+        let keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match event.key() {
+            Some(key) => {
+                if let Some(notify) = keys.get(key) {
+                    notify.notify_waiters();
+                }
+            }
+            None => keys.values().for_each(|notify| notify.notify_waiters()),
+        }
+    }
+}
+
+pub(super) struct Waiter {
+    waiters: Arc<Waiters>,
+    key: String,
+    notify: Arc<Notify>,
+}
+
+impl Waiter {
+    /// Future completes on the next change of key, changes are caught from the moment future is created.
+    pub fn changed(&self) -> impl Future<Output = ()> + '_ {
+        self.notify.notified()
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        let mut keys = self
+            .waiters
+            .keys
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        // one reference is kept by map, other one is this waiter
+        if Arc::strong_count(&self.notify) == 2 {
+            keys.remove(&self.key);
+        }
+    }
+}
+
+/// Deadline for a timeout, zero timeout means no deadline.
+pub(super) fn deadline(timeout: Duration) -> Option<Instant> {
+    (!timeout.is_zero()).then(|| Instant::now() + timeout)
+}
+
+/// Wait for future until deadline. Returns false if deadline is reached first.
+pub(super) async fn until(future: impl Future<Output = ()>, deadline: Option<Instant>) -> bool {
+    match deadline {
+        Some(deadline) => time::timeout_at(deadline, future).await.is_ok(),
+        None => {
+            future.await;
+            true
+        }
+    }
+}
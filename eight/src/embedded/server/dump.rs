@@ -0,0 +1,172 @@
+//! Dump format:
+//!
+//! ```text
+//! header: "EIGHT" version:u8
+//! entry:  0x01 key_length:u32 key value_length:u32 value
+//! end:    0x00 entry_count:u64
+//! ```
+//!
+//! Numbers are big endian, keys and values are UTF-8. End record lets restore notice a truncated dump.
+
+use crate::{
+    embedded::{storage::Storage, Result},
+    err,
+};
+use futures::StreamExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const MAGIC: &[u8; 5] = b"EIGHT";
+const VERSION: u8 = 1;
+
+const ENTRY: u8 = 1;
+const END: u8 = 0;
+
+pub(super) async fn write<W>(storage: &dyn Storage, writer: &mut W) -> Result<u64>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut count: u64 = 0;
+
+    let mut header = MAGIC.to_vec();
+    header.push(VERSION);
+    write_all(writer, &header).await?;
+
+    let mut keys = storage.keys(String::new()).await?;
+
+    while let Some(key) = keys.next().await {
+        let key = key?;
+
+        let value = match storage.get(key.clone()).await {
+            Ok(value) => value,
+            // key can be deleted after listing, any other failure would leave it out of dump silently
+            Err(error) => match storage.exists(key).await {
+                Ok(false) => continue,
+                Ok(true) => return Err(error),
+                Err(error) => return Err(error),
+            },
+        };
+
+        let mut record = vec![ENTRY];
+        push_bytes(&mut record, key.as_bytes())?;
+        push_bytes(&mut record, value.as_bytes())?;
+
+        write_all(writer, &record).await?;
+        count += 1;
+    }
+
+    let mut end = vec![END];
+    end.extend(count.to_be_bytes());
+    write_all(writer, &end).await?;
+
+    writer
+        .flush()
+        .await
+        .map_err(|_| err!(embedded, DumpWriteFail))?;
+
+    Ok(count)
+}
+
+/// Reads entries of a dump one by one.
+pub(super) struct Reader<R> {
+    reader: R,
+    count: u64,
+}
+
+impl<R> Reader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    pub async fn open(mut reader: R) -> Result<Self> {
+        let mut header = [0; MAGIC.len() + 1];
+        read_exact(&mut reader, &mut header).await?;
+
+        if &header[..MAGIC.len()] != MAGIC || header[MAGIC.len()] != VERSION {
+            return Err(err!(embedded, InvalidDump));
+        }
+
+        Ok(Self { reader, count: 0 })
+    }
+
+    /// Next key and value, [`None`] once end record is read and entry count matches.
+    pub async fn next(&mut self) -> Result<Option<(String, String)>> {
+        let mut tag = [0; 1];
+        read_exact(&mut self.reader, &mut tag).await?;
+
+        match tag[0] {
+            ENTRY => {
+                let key = read_string(&mut self.reader).await?;
+                let value = read_string(&mut self.reader).await?;
+
+                self.count += 1;
+                Ok(Some((key, value)))
+            }
+            END => {
+                let mut expected = [0; 8];
+                read_exact(&mut self.reader, &mut expected).await?;
+
+                if u64::from_be_bytes(expected) == self.count {
+                    Ok(None)
+                } else {
+                    Err(err!(embedded, InvalidDump))
+                }
+            }
+            _ => Err(err!(embedded, InvalidDump)),
+        }
+    }
+}
+
+fn push_bytes(record: &mut Vec<u8>, bytes: &[u8]) -> Result<()> {
+    let length = u32::try_from(bytes.len()).map_err(|_| err!(embedded, DumpWriteFail))?;
+
+    record.extend(length.to_be_bytes());
+    record.extend(bytes);
+
+    Ok(())
+}
+
+async fn write_all<W>(writer: &mut W, bytes: &[u8]) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    writer
+        .write_all(bytes)
+        .await
+        .map_err(|_| err!(embedded, DumpWriteFail))
+}
+
+async fn read_string<R>(reader: &mut R) -> Result<String>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut length = [0; 4];
+    read_exact(reader, &mut length).await?;
+
+    let length = u64::from(u32::from_be_bytes(length));
+
+    // buffer grows while reading, a broken length can't allocate gigabytes upfront
+    let mut bytes = Vec::new();
+    (&mut *reader)
+        .take(length)
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(|_| err!(embedded, DumpReadFail))?;
+
+    if bytes.len() as u64 != length {
+        return Err(err!(embedded, InvalidDump));
+    }
+
+    String::from_utf8(bytes).map_err(|_| err!(embedded, InvalidDump))
+}
+
+async fn read_exact<R>(reader: &mut R, buffer: &mut [u8]) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    match reader.read_exact(buffer).await {
+        Ok(_) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => {
+            Err(err!(embedded, InvalidDump))
+        }
+        Err(_) => Err(err!(embedded, DumpReadFail)),
+    }
+}
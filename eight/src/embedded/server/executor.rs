@@ -1,4 +1,22 @@
-use crate::embedded::{messaging::Response, storage::Storage};
+use super::{
+    is_reserved, list, lock, ratelimit,
+    sketch::{Bloom, HyperLogLog},
+    stream::{self, Fields, Id},
+    timeseries::Series,
+    typed::{self, Type},
+};
+use crate::{
+    embedded::{
+        messaging::{Aggregation, RateLimiter, Response},
+        storage::Storage,
+        Error, Result,
+    },
+    err,
+};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 pub(super) struct Executor {
     storage: Box<dyn Storage>,
@@ -11,6 +29,10 @@ impl Executor {
         }
     }
 
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
     pub async fn set(&self, key: String, value: String) -> Response {
         match self.storage.set(key, value).await {
             Ok(_) => Response::Ok,
@@ -55,7 +77,10 @@ impl Executor {
 
     pub async fn search(&self, key: String) -> Response {
         match self.storage.search(key).await {
-            Ok(value) => Response::TextList(value),
+            Ok(mut value) => {
+                value.retain(|key| !is_reserved(key));
+                Response::TextList(value)
+            }
             Err(error) => error.as_response(),
         }
     }
@@ -66,4 +91,725 @@ impl Executor {
             Err(error) => error.as_response(),
         }
     }
+
+    pub async fn rename(&self, from: String, to: String) -> Response {
+        match self.storage.rename(from, to).await {
+            Ok(_) => Response::Ok,
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn rename_nx(&self, from: String, to: String) -> Response {
+        match self.storage.rename_nx(from, to).await {
+            Ok(value) => Response::Boolean(value),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn copy(&self, from: String, to: String) -> Response {
+        match self.storage.copy(from, to).await {
+            Ok(value) => Response::Boolean(value),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn set_nx(&self, key: String, value: String) -> Response {
+        match self.storage.set_nx(key, value).await {
+            Ok(value) => Response::Boolean(value),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn set_xx(&self, key: String, value: String) -> Response {
+        match self.storage.set_xx(key, value).await {
+            Ok(value) => Response::Boolean(value),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn get_set(&self, key: String, value: String) -> Response {
+        match self.storage.get_set(key, value).await {
+            Ok(Some(old)) => Response::Text(old),
+            Ok(None) => Response::Ok,
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn get_delete(&self, key: String) -> Response {
+        match self.storage.get_delete(key).await {
+            Ok(value) => Response::Text(value),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn append(&self, key: String, value: String) -> Response {
+        match self.storage.append(key, value).await {
+            Ok(length) => Response::Number(length),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn length(&self, key: String) -> Response {
+        match self.storage.length(key).await {
+            Ok(length) => Response::Number(length),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn get_range(&self, key: String, start: isize, end: isize) -> Response {
+        match self.storage.get_range(key, start, end).await {
+            Ok(value) => Response::Text(value),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn metadata(&self, key: String) -> Response {
+        let metadata = match self.storage.metadata(key.clone()).await {
+            Ok(metadata) => metadata,
+            Err(error) => return error.as_response(),
+        };
+
+        // tag is enough to tell type, value is not read as a whole
+        let head = match self
+            .storage
+            .get_range(key, 0, typed::MAX_TAG_LENGTH as isize - 1)
+            .await
+        {
+            Ok(head) => head,
+            Err(error) => return error.as_response(),
+        };
+
+        let mut map = BTreeMap::from([
+            ("size".to_string(), Response::Number(metadata.size)),
+            ("type".to_string(), Response::Text(value_type(&head).into())),
+        ]);
+
+        for (name, time) in [("created", metadata.created), ("updated", metadata.updated)] {
+            if let Some(time) = time {
+                map.insert(name.to_string(), Response::Number(timestamp(time)));
+            }
+        }
+
+        if let Some(ttl) = metadata.ttl {
+            map.insert(
+                "ttl".to_string(),
+                Response::Number(ttl.as_millis() as usize),
+            );
+        }
+
+        Response::Map(map)
+    }
+
+    pub async fn history(&self, key: String) -> Response {
+        let versions = match self.storage.history(key).await {
+            Ok(versions) => versions,
+            Err(error) => return error.as_response(),
+        };
+
+        let versions = versions
+            .into_iter()
+            .map(|version| {
+                let mut map = BTreeMap::from([(
+                    "time".to_string(),
+                    Response::Number(timestamp(version.time)),
+                )]);
+
+                if let Some(value) = version.value {
+                    map.insert("value".to_string(), Response::Text(value));
+                }
+
+                Response::Map(map)
+            })
+            .collect();
+
+        Response::List(versions)
+    }
+
+    pub async fn get_at(&self, key: String, time: SystemTime) -> Response {
+        match self.storage.get_at(key, time).await {
+            Ok(value) => Response::Text(value),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn rate_limit(
+        &self,
+        key: String,
+        limit: usize,
+        window: Duration,
+        algorithm: RateLimiter,
+    ) -> Response {
+        let result = self
+            .swap(key, |state, now| {
+                ratelimit::decide(algorithm, state, limit, window, now)
+            })
+            .await;
+
+        match result {
+            Ok(decision) => Response::Map(BTreeMap::from([
+                ("allowed".to_string(), Response::Boolean(decision.allowed)),
+                (
+                    "remaining".to_string(),
+                    Response::Number(decision.remaining),
+                ),
+                (
+                    "retry_after".to_string(),
+                    Response::Number(decision.retry_after.as_millis() as usize),
+                ),
+            ])),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn lock(&self, name: String, owner: String, ttl: Duration) -> Response {
+        self.lease(name, |state, now| lock::acquire(state, &owner, ttl, now))
+            .await
+    }
+
+    pub async fn renew(&self, name: String, owner: String, ttl: Duration) -> Response {
+        self.lease(name, |state, now| lock::renew(state, &owner, ttl, now))
+            .await
+    }
+
+    pub async fn unlock(&self, name: String, owner: String) -> Response {
+        match self
+            .swap(name, |state, now| lock::release(state, &owner, now))
+            .await
+        {
+            Ok(_) => Response::Ok,
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn hyper_log_log_add(&self, key: String, items: Vec<String>) -> Response {
+        let result = self
+            .swap(key, |state, _| {
+                let mut sketch = match state {
+                    Some(state) => HyperLogLog::decode(state)?,
+                    None => HyperLogLog::default(),
+                };
+
+                let mut changed = false;
+
+                // every item is added, even after one changes the sketch
+                for item in &items {
+                    changed |= sketch.add(item);
+                }
+
+                Ok((changed, sketch.encode()))
+            })
+            .await;
+
+        match result {
+            Ok(changed) => Response::Boolean(changed),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn hyper_log_log_count(&self, keys: Vec<String>) -> Response {
+        let mut union = HyperLogLog::default();
+
+        for key in keys {
+            match self.hyper_log_log(key).await {
+                Ok(sketch) => union.merge(&sketch),
+                Err(error) => return error.as_response(),
+            }
+        }
+
+        Response::Number(union.count())
+    }
+
+    pub async fn hyper_log_log_merge(&self, key: String, sources: Vec<String>) -> Response {
+        let mut union = HyperLogLog::default();
+
+        for source in sources {
+            match self.hyper_log_log(source).await {
+                Ok(sketch) => union.merge(&sketch),
+                Err(error) => return error.as_response(),
+            }
+        }
+
+        let result = self
+            .swap(key, |state, _| {
+                let mut sketch = match state {
+                    Some(state) => HyperLogLog::decode(state)?,
+                    None => HyperLogLog::default(),
+                };
+
+                sketch.merge(&union);
+
+                Ok(((), sketch.encode()))
+            })
+            .await;
+
+        match result {
+            Ok(_) => Response::Ok,
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn bloom_reserve(&self, key: String, error_rate: f64, capacity: usize) -> Response {
+        let result = self
+            .swap(key, |state, _| match state {
+                Some(_) => Err(err!(embedded, FilterExists)),
+                None => Ok(((), Bloom::new(error_rate, capacity)?.encode())),
+            })
+            .await;
+
+        match result {
+            Ok(_) => Response::Ok,
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn bloom_add(&self, key: String, item: String) -> Response {
+        let result = self
+            .swap(key, |state, _| {
+                let mut filter = match state {
+                    Some(state) => Bloom::decode(state)?,
+                    None => Bloom::default(),
+                };
+
+                let added = filter.add(&item);
+
+                Ok((added, filter.encode()))
+            })
+            .await;
+
+        match result {
+            Ok(added) => Response::Boolean(added),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn bloom_exists(&self, key: String, item: String) -> Response {
+        let result = match self.storage.exists(key.clone()).await {
+            Ok(true) => match self.storage.get(key).await {
+                Ok(value) => Bloom::decode(&value).map(|filter| filter.contains(&item)),
+                Err(error) => Err(error),
+            },
+            Ok(false) => Ok(false),
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Ok(exists) => Response::Boolean(exists),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn time_series_create(&self, key: String, retention: Duration) -> Response {
+        let result = self
+            .swap(key, |state, _| match state {
+                Some(_) => Err(err!(embedded, SeriesExists)),
+                None => Ok(((), Series::new(retention.as_millis() as u64)?.encode())),
+            })
+            .await;
+
+        match result {
+            Ok(_) => Response::Ok,
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn time_series_add(&self, key: String, time: Option<u64>, value: f64) -> Response {
+        let result = self
+            .swap(key, |state, now| {
+                let mut series = state.map(Series::decode).transpose()?.unwrap_or_default();
+                let time = time.unwrap_or(now);
+
+                series.add(time, value);
+
+                Ok((time, series.encode()))
+            })
+            .await;
+
+        match result {
+            Ok(time) => Response::Number(time as usize),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn time_series_range(
+        &self,
+        key: String,
+        from: u64,
+        to: u64,
+        aggregation: Option<(Aggregation, Duration)>,
+    ) -> Response {
+        let aggregation =
+            aggregation.map(|(aggregation, bucket)| (aggregation, bucket.as_millis() as u64));
+
+        match self.series(key).await {
+            Ok(series) => Response::List(
+                series
+                    .range(from, to, aggregation)
+                    .into_iter()
+                    .map(|(time, value)| {
+                        Response::Map(BTreeMap::from([
+                            ("time".to_string(), Response::Number(time as usize)),
+                            ("value".to_string(), Response::Text(value.to_string())),
+                        ]))
+                    })
+                    .collect(),
+            ),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn time_series_rule(
+        &self,
+        key: String,
+        aggregation: Aggregation,
+        bucket: Duration,
+        retention: Duration,
+    ) -> Response {
+        let result = self
+            .swap(key, |state, _| {
+                let mut series = state.map(Series::decode).transpose()?.unwrap_or_default();
+
+                series.add_rule(
+                    aggregation,
+                    bucket.as_millis() as u64,
+                    retention.as_millis() as u64,
+                )?;
+
+                Ok(((), series.encode()))
+            })
+            .await;
+
+        match result {
+            Ok(_) => Response::Ok,
+            Err(error) => error.as_response(),
+        }
+    }
+
+    async fn series(&self, key: String) -> Result<Series> {
+        if !self.storage.exists(key.clone()).await? {
+            return Ok(Series::default());
+        }
+
+        Series::decode(&self.storage.get(key).await?)
+    }
+
+    async fn hyper_log_log(&self, key: String) -> Result<HyperLogLog> {
+        if !self.storage.exists(key.clone()).await? {
+            return Ok(HyperLogLog::default());
+        }
+
+        HyperLogLog::decode(&self.storage.get(key).await?)
+    }
+
+    async fn lease(
+        &self,
+        name: String,
+        change: impl Fn(Option<&str>, u64) -> Result<(u64, String)>,
+    ) -> Response {
+        match self.swap(name, change).await {
+            Ok(token) => Response::Number(token as usize),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    /// Replace state kept in key with current time, retrying until state is swapped without a concurrent change.
+    async fn swap<T>(
+        &self,
+        key: String,
+        change: impl Fn(Option<&str>, u64) -> Result<(T, String)>,
+    ) -> Result<T> {
+        loop {
+            let current = self.current(&key).await?;
+
+            let now = timestamp(SystemTime::now()) as u64;
+            let (result, state) = change(current.as_deref(), now)?;
+
+            if self
+                .storage
+                .compare_and_swap(key.clone(), current, state)
+                .await?
+            {
+                return Ok(result);
+            }
+        }
+    }
+
+    async fn current(&self, key: &str) -> Result<Option<String>> {
+        if self.storage.exists(key.to_string()).await? {
+            Ok(Some(self.storage.get(key.to_string()).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn left_push(&self, key: String, value: String) -> Response {
+        self.push(key, value, VecDeque::push_front).await
+    }
+
+    pub async fn right_push(&self, key: String, value: String) -> Response {
+        self.push(key, value, VecDeque::push_back).await
+    }
+
+    /// Pops first value of list, second value is true if list is deleted because it became empty.
+    pub async fn left_pop(&self, key: String) -> (Response, bool) {
+        let popped = self
+            .change_list(key, |list| {
+                let value = list.pop_front().ok_or(err!(embedded, ListEmpty))?;
+                Ok((value, list.is_empty()))
+            })
+            .await;
+
+        match popped {
+            Ok((value, deleted)) => (Response::Text(value), deleted),
+            Err(error) => (error.as_response(), false),
+        }
+    }
+
+    async fn push(
+        &self,
+        key: String,
+        value: String,
+        push: fn(&mut VecDeque<String>, String),
+    ) -> Response {
+        let pushed = self
+            .change_list(key, |list| {
+                push(list, value.clone());
+                Ok(list.len())
+            })
+            .await;
+
+        match pushed {
+            Ok(length) => Response::Number(length),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    /// Change list kept in key like [`Executor::swap`], key is deleted when list becomes empty.
+    async fn change_list<T>(
+        &self,
+        key: String,
+        change: impl Fn(&mut VecDeque<String>) -> Result<T>,
+    ) -> Result<T> {
+        loop {
+            let current = self.current(&key).await?;
+
+            let mut list = match &current {
+                Some(raw) => decode_list(raw)?,
+                None => VecDeque::new(),
+            };
+
+            let result = change(&mut list)?;
+
+            let swapped = match current {
+                Some(current) if list.is_empty() => {
+                    self.storage
+                        .compare_and_delete(key.clone(), current)
+                        .await?
+                }
+                current => {
+                    let list = typed::tag(Type::List, &list::encode(&list));
+                    self.storage
+                        .compare_and_swap(key.clone(), current, list)
+                        .await?
+                }
+            };
+
+            if swapped {
+                return Ok(result);
+            }
+        }
+    }
+
+    pub async fn stream_add(&self, key: String, fields: Fields, max: Option<usize>) -> Response {
+        let now = timestamp(SystemTime::now()) as u64;
+
+        let added = self
+            .change_stream(key, |stream| {
+                let id = stream.add(fields.clone(), now);
+
+                if let Some(max) = max {
+                    stream.trim(max);
+                }
+
+                Ok(id)
+            })
+            .await;
+
+        match added {
+            Ok(id) => Response::Text(id.to_string()),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn stream_trim(&self, key: String, max: usize) -> Response {
+        let trimmed = self
+            .swap(key, |current, _| {
+                // a missing stream is not created
+                let mut stream =
+                    stream::Stream::decode(current.ok_or(err!(embedded, GetKeyFail))?)?;
+                Ok((stream.trim(max), stream.encode()))
+            })
+            .await;
+
+        match trimmed {
+            Ok(count) => Response::Number(count),
+            Err(Error::GetKeyFail) => Response::Number(0),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn stream_range(
+        &self,
+        key: String,
+        start: String,
+        end: String,
+        count: usize,
+    ) -> Response {
+        let result = match self.stream(&key).await {
+            Ok(stream) => stream
+                .range(&start, &end, count)
+                .map(|entries| entries_response(entries)),
+            Err(error) => Err(error),
+        };
+
+        result.unwrap_or_else(|error| error.as_response())
+    }
+
+    pub async fn stream_read(&self, key: String, after: String, count: usize) -> Response {
+        let result = match self.stream(&key).await {
+            Ok(stream) => stream
+                .read(&after, count)
+                .map(|entries| entries_response(entries)),
+            Err(error) => Err(error),
+        };
+
+        result.unwrap_or_else(|error| error.as_response())
+    }
+
+    /// ID of the last entry added to stream.
+    pub async fn stream_last(&self, key: &str) -> Result<String> {
+        Ok(self.stream(key).await?.last().to_string())
+    }
+
+    pub async fn stream_group_create(
+        &self,
+        key: String,
+        group: String,
+        start: String,
+        timeout: Duration,
+    ) -> Response {
+        match self
+            .change_stream(key, |stream| {
+                stream.create_group(group.clone(), &start, timeout)
+            })
+            .await
+        {
+            Ok(_) => Response::Ok,
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn stream_group_destroy(&self, key: String, group: String) -> Response {
+        match self
+            .change_stream(key, |stream| stream.destroy_group(&group))
+            .await
+        {
+            Ok(_) => Response::Ok,
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn stream_read_group(
+        &self,
+        key: String,
+        group: String,
+        consumer: String,
+        count: usize,
+    ) -> Response {
+        let now = timestamp(SystemTime::now()) as u64;
+
+        match self
+            .change_stream(key, |stream| {
+                stream.read_group(&group, &consumer, count, now)
+            })
+            .await
+        {
+            Ok(entries) => {
+                entries_response(entries.iter().map(|(id, fields)| (*id, fields)).collect())
+            }
+            Err(error) => error.as_response(),
+        }
+    }
+
+    pub async fn stream_ack(&self, key: String, group: String, ids: Vec<String>) -> Response {
+        match self
+            .change_stream(key, |stream| stream.ack(&group, &ids))
+            .await
+        {
+            Ok(count) => Response::Number(count),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    /// Change stream kept in key like [`Executor::swap`].
+    async fn change_stream<T>(
+        &self,
+        key: String,
+        change: impl Fn(&mut stream::Stream) -> Result<T>,
+    ) -> Result<T> {
+        self.swap(key, |current, _| {
+            let mut stream = match current {
+                Some(raw) => stream::Stream::decode(raw)?,
+                None => stream::Stream::default(),
+            };
+
+            let result = change(&mut stream)?;
+            Ok((result, stream.encode()))
+        })
+        .await
+    }
+
+    async fn stream(&self, key: &str) -> Result<stream::Stream> {
+        if !self.storage.exists(key.to_string()).await? {
+            return Ok(stream::Stream::default());
+        }
+
+        stream::Stream::decode(&self.storage.get(key.to_string()).await?)
+    }
+}
+
+fn entries_response(entries: Vec<(Id, &Fields)>) -> Response {
+    Response::List(
+        entries
+            .into_iter()
+            .map(|(id, fields)| {
+                let fields = fields
+                    .iter()
+                    .map(|(field, value)| (field.clone(), Response::Text(value.clone())))
+                    .collect();
+
+                Response::Map(BTreeMap::from([
+                    ("id".to_string(), Response::Text(id.to_string())),
+                    ("fields".to_string(), Response::Map(fields)),
+                ]))
+            })
+            .collect(),
+    )
+}
+
+// only tagged values have a type, anything else is a string even if it looks like a number or an encoding
+fn value_type(head: &str) -> &'static str {
+    typed::of(head).map(Type::name).unwrap_or("string")
+}
+
+// only values tagged as list are lists, plain strings are never taken as one
+fn decode_list(raw: &str) -> Result<VecDeque<String>> {
+    typed::untag(Type::List, raw)
+        .ok_or(err!(embedded, ListParseFail))
+        .and_then(list::decode)
+}
+
+fn timestamp(time: SystemTime) -> usize {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as usize)
+        .unwrap_or_default()
 }
@@ -0,0 +1,374 @@
+//! Full-text search over values, ranked with BM25.
+//!
+//! Registered prefixes are kept in the `__fulltext__` key, and terms of every document in
+//! `__fulltext__:<prefix byte length>:<key>` keys, so search survives a restart on persistent storages.
+//! Documents are loaded with the first request.
+
+use super::{is_reserved, list};
+use crate::{
+    embedded::{messaging::KeyEvent, storage::Storage, Result},
+    err,
+};
+use futures::TryStreamExt;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tokio::sync::{Mutex, OnceCell};
+
+/// Key holding registered prefixes, document terms are kept in keys starting with it and a colon.
+pub(super) const FULLTEXT_PREFIX: &str = "__fulltext__";
+
+// usual BM25 parameters, how fast term frequency saturates and how much length matters
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+type Terms = BTreeMap<String, usize>;
+
+#[derive(Default)]
+struct Corpus {
+    documents: HashMap<String, Terms>,
+    postings: HashMap<String, HashSet<String>>,
+    // sum of document lengths in terms
+    length: usize,
+}
+
+impl Corpus {
+    fn insert(&mut self, key: String, terms: Terms) {
+        self.remove(&key);
+
+        for term in terms.keys() {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(key.clone());
+        }
+
+        self.length += terms.values().sum::<usize>();
+        self.documents.insert(key, terms);
+    }
+
+    fn remove(&mut self, key: &str) {
+        let Some(terms) = self.documents.remove(key) else {
+            return;
+        };
+
+        for term in terms.keys() {
+            if let Some(keys) = self.postings.get_mut(term) {
+                keys.remove(key);
+
+                if keys.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+
+        self.length -= terms.values().sum::<usize>();
+    }
+
+    fn search(&self, query: &str) -> Vec<String> {
+        let count = self.documents.len() as f64;
+        let average = self.length as f64 / count.max(1.0);
+
+        let mut scores: HashMap<&str, f64> = HashMap::new();
+
+        for term in tokenize(query).keys() {
+            let Some(keys) = self.postings.get(term) else {
+                continue;
+            };
+
+            let frequency = keys.len() as f64;
+            let idf = ((count - frequency + 0.5) / (frequency + 0.5) + 1.0).ln();
+
+            for key in keys {
+                let terms = &self.documents[key];
+                let occurrences = terms[term] as f64;
+                let length = terms.values().sum::<usize>() as f64;
+
+                *scores.entry(key).or_default() += idf * occurrences * (K1 + 1.0)
+                    / (occurrences + K1 * (1.0 - B + B * length / average));
+            }
+        }
+
+        let mut ranked: Vec<_> = scores.into_iter().collect();
+        ranked.sort_by(|(a, a_score), (b, b_score)| b_score.total_cmp(a_score).then(a.cmp(b)));
+
+        ranked.into_iter().map(|(key, _)| key.to_string()).collect()
+    }
+}
+
+#[derive(Default)]
+pub(super) struct FullText {
+    corpora: Mutex<HashMap<String, Corpus>>,
+    loaded: OnceCell<()>,
+    // false only while there is surely no registered prefix, so updates skip the lock
+    any: AtomicBool,
+}
+
+impl FullText {
+    /// Read registered prefixes and documents from storage, only once.
+    pub async fn load(&self, storage: &dyn Storage) -> Result<()> {
+        self.loaded
+            .get_or_try_init(|| async {
+                let mut corpora = self.corpora.lock().await;
+
+                for prefix in registered(storage).await? {
+                    corpora.insert(prefix, Corpus::default());
+                }
+
+                for entry in storage.search(format!("{FULLTEXT_PREFIX}:")).await? {
+                    let (length, key) = entry[FULLTEXT_PREFIX.len() + 1..]
+                        .split_once(':')
+                        .ok_or(err!(embedded, CorruptedValue))?;
+                    let length = length
+                        .parse::<usize>()
+                        .map_err(|_| err!(embedded, CorruptedValue))?;
+
+                    let Some(prefix) = key.get(..length) else {
+                        continue;
+                    };
+
+                    // documents of dropped prefixes are left behind if dropping fails halfway
+                    if let Some(corpus) = corpora.get_mut(prefix) {
+                        let terms = decode(&storage.get(entry.clone()).await?)?;
+                        corpus.insert(key.to_string(), terms);
+                    }
+                }
+
+                self.any.store(!corpora.is_empty(), Ordering::SeqCst);
+                Ok(())
+            })
+            .await
+            .map(|_| ())
+    }
+
+    /// Register prefix and index its keys. Returns indexed key count.
+    pub async fn create(&self, storage: &dyn Storage, prefix: String) -> Result<usize> {
+        let mut corpora = self.corpora.lock().await;
+
+        if corpora.contains_key(&prefix) {
+            return Err(err!(embedded, IndexExists));
+        }
+
+        // set before filling, so a write missed by the scan updates the index once the lock is released
+        self.any.store(true, Ordering::SeqCst);
+
+        let mut prefixes = registered(storage).await?;
+        prefixes.push_back(prefix.clone());
+        storage
+            .set(FULLTEXT_PREFIX.to_string(), list::encode(&prefixes))
+            .await?;
+
+        let corpus = fill(storage, &prefix).await?;
+
+        let count = corpus.documents.len();
+        corpora.insert(prefix, corpus);
+
+        Ok(count)
+    }
+
+    /// Read registered prefixes from storage again and index them from scratch, like after a restore.
+    pub async fn rebuild(&self, storage: &dyn Storage) -> Result<()> {
+        let mut corpora = self.corpora.lock().await;
+        corpora.clear();
+        self.any.store(true, Ordering::SeqCst);
+
+        for entry in storage.search(format!("{FULLTEXT_PREFIX}:")).await? {
+            storage.delete(entry).await?;
+        }
+
+        for prefix in registered(storage).await? {
+            let corpus = fill(storage, &prefix).await?;
+            corpora.insert(prefix, corpus);
+        }
+
+        self.any.store(!corpora.is_empty(), Ordering::SeqCst);
+
+        // nothing left to load
+        self.loaded.set(()).ok();
+
+        Ok(())
+    }
+
+    pub async fn remove(&self, storage: &dyn Storage, prefix: String) -> Result<()> {
+        let mut corpora = self.corpora.lock().await;
+        let corpus = corpora
+            .remove(&prefix)
+            .ok_or(err!(embedded, IndexNotFound))?;
+        self.any.store(!corpora.is_empty(), Ordering::SeqCst);
+
+        let mut prefixes = registered(storage).await?;
+        prefixes.retain(|registered| registered != &prefix);
+
+        if prefixes.is_empty() {
+            storage.delete(FULLTEXT_PREFIX.to_string()).await?;
+        } else {
+            storage
+                .set(FULLTEXT_PREFIX.to_string(), list::encode(&prefixes))
+                .await?;
+        }
+
+        for key in corpus.documents.keys() {
+            storage.delete(entry(&prefix, key)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Keys under registered prefix matching any of the terms, best match first.
+    pub async fn search(&self, prefix: &str, query: &str) -> Result<Vec<String>> {
+        let corpora = self.corpora.lock().await;
+        let corpus = corpora.get(prefix).ok_or(err!(embedded, IndexNotFound))?;
+
+        Ok(corpus.search(query))
+    }
+
+    /// Document keys of key in every corpus covering it, they may be written after key changes.
+    pub async fn entries(&self, key: &str) -> Vec<String> {
+        if !self.any.load(Ordering::SeqCst) || is_reserved(key) {
+            return Vec::new();
+        }
+
+        let corpora = self.corpora.lock().await;
+
+        corpora
+            .keys()
+            .filter(|prefix| key.starts_with(prefix.as_str()))
+            .map(|prefix| entry(prefix, key))
+            .collect()
+    }
+
+    /// Bring documents up to date after a change.
+    pub async fn update(&self, storage: &dyn Storage, event: &KeyEvent) -> Result<()> {
+        if !self.any.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let mut corpora = self.corpora.lock().await;
+
+        let (key, deleted) = match event {
+            KeyEvent::Set(key) => (key, false),
+            KeyEvent::Delete(key) | KeyEvent::Expire(key) => (key, true),
+            // registered prefixes are flushed too
+            KeyEvent::Flush => {
+                corpora.clear();
+                self.any.store(false, Ordering::SeqCst);
+                return Ok(());
+            }
+            KeyEvent::Lagged(_) => return Ok(()),
+        };
+
+        if is_reserved(key) || !corpora.keys().any(|prefix| key.starts_with(prefix)) {
+            return Ok(());
+        }
+
+        // value is read while holding the lock, so the last update always sees the last write
+        let terms = if deleted {
+            Terms::new()
+        } else {
+            match storage.get(key.clone()).await {
+                Ok(value) => tokenize(&value),
+                Err(_) => Terms::new(),
+            }
+        };
+
+        for (prefix, corpus) in corpora
+            .iter_mut()
+            .filter(|(prefix, _)| key.starts_with(prefix.as_str()))
+        {
+            if corpus.documents.get(key) == Some(&terms)
+                || (terms.is_empty() && !corpus.documents.contains_key(key))
+            {
+                continue;
+            }
+
+            if terms.is_empty() {
+                storage.delete(entry(prefix, key)).await?;
+                corpus.remove(key);
+            } else {
+                storage.set(entry(prefix, key), encode(&terms)).await?;
+                corpus.insert(key.clone(), terms.clone());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn registered(storage: &dyn Storage) -> Result<VecDeque<String>> {
+    if !storage.exists(FULLTEXT_PREFIX.to_string()).await? {
+        return Ok(VecDeque::new());
+    }
+
+    list::decode(&storage.get(FULLTEXT_PREFIX.to_string()).await?)
+}
+
+// index every key under prefix
+async fn fill(storage: &dyn Storage, prefix: &str) -> Result<Corpus> {
+    let mut corpus = Corpus::default();
+    let keys: Vec<String> = storage
+        .keys(prefix.to_string())
+        .await?
+        .try_collect()
+        .await?;
+
+    for key in keys.into_iter().filter(|key| !is_reserved(key)) {
+        // key can be deleted while scanning
+        let Ok(value) = storage.get(key.clone()).await else {
+            continue;
+        };
+
+        let terms = tokenize(&value);
+
+        if !terms.is_empty() {
+            storage.set(entry(prefix, &key), encode(&terms)).await?;
+            corpus.insert(key, terms);
+        }
+    }
+
+    Ok(corpus)
+}
+
+// prefix length tells prefix apart from the rest of key
+fn entry(prefix: &str, key: &str) -> String {
+    format!("{FULLTEXT_PREFIX}:{}:{key}", prefix.len())
+}
+
+// lowercase words and how many times they appear, anything other than alphanumerics separates words
+fn tokenize(text: &str) -> Terms {
+    let mut terms = Terms::new();
+
+    for word in text
+        .split(|character: char| !character.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        *terms.entry(word.to_lowercase()).or_default() += 1;
+    }
+
+    terms
+}
+
+// terms are alphanumeric, written as `term:count` separated by spaces
+fn encode(terms: &Terms) -> String {
+    terms
+        .iter()
+        .map(|(term, count)| format!("{term}:{count}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn decode(raw: &str) -> Result<Terms> {
+    raw.split(' ')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let (term, count) = part
+                .rsplit_once(':')
+                .ok_or(err!(embedded, CorruptedValue))?;
+            let count = count
+                .parse::<usize>()
+                .map_err(|_| err!(embedded, CorruptedValue))?;
+
+            Ok((term.to_string(), count))
+        })
+        .collect()
+}
@@ -0,0 +1,348 @@
+//! Secondary indexes on JSON value fields.
+//!
+//! Definitions are kept as `__index__:<name>` keys and entries as `__index__:<name>:<key>` keys holding the field value,
+//! so indexes survive a restart on persistent storages. Indexes are loaded with the first request.
+
+use super::is_reserved;
+use crate::{
+    embedded::{messaging::KeyEvent, storage::Storage, Result},
+    err,
+};
+use futures::TryStreamExt;
+use serde_json::Value;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tokio::sync::{Mutex, OnceCell};
+
+/// Keys starting with this prefix hold index definitions and entries.
+pub(super) const INDEX_PREFIX: &str = "__index__:";
+
+struct Index {
+    prefix: String,
+    field: String,
+    pointer: String,
+    // field value to keys, and key to field value
+    values: HashMap<String, BTreeSet<String>>,
+    keys: HashMap<String, String>,
+}
+
+impl Index {
+    fn new(prefix: String, field: String) -> Result<Self> {
+        let pointer = pointer(&field).ok_or(err!(embedded, InvalidIndex))?;
+
+        Ok(Self {
+            prefix,
+            field,
+            pointer,
+            values: HashMap::new(),
+            keys: HashMap::new(),
+        })
+    }
+
+    fn covers(&self, key: &str) -> bool {
+        key.starts_with(&self.prefix) && !is_reserved(key)
+    }
+
+    fn insert(&mut self, key: String, value: String) {
+        self.remove(&key);
+        self.values
+            .entry(value.clone())
+            .or_default()
+            .insert(key.clone());
+        self.keys.insert(key, value);
+    }
+
+    fn remove(&mut self, key: &str) {
+        let Some(value) = self.keys.remove(key) else {
+            return;
+        };
+
+        if let Some(keys) = self.values.get_mut(&value) {
+            keys.remove(key);
+
+            if keys.is_empty() {
+                self.values.remove(&value);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub(super) struct Indexes {
+    list: Mutex<HashMap<String, Index>>,
+    loaded: OnceCell<()>,
+    // false only while there is surely no index, so updates skip the lock
+    any: AtomicBool,
+}
+
+impl Indexes {
+    /// Read indexes from storage, only once.
+    pub async fn load(&self, storage: &dyn Storage) -> Result<()> {
+        self.loaded
+            .get_or_try_init(|| async {
+                let mut list = self.list.lock().await;
+                let mut entries = Vec::new();
+
+                for key in storage.search(INDEX_PREFIX.to_string()).await? {
+                    match key[INDEX_PREFIX.len()..].split_once(':') {
+                        Some((name, indexed)) => {
+                            entries.push((name.to_string(), indexed.to_string(), key))
+                        }
+                        None => {
+                            let name = key[INDEX_PREFIX.len()..].to_string();
+                            let raw = storage.get(key).await?;
+                            let (prefix, field) =
+                                raw.split_once('\n').ok_or(err!(embedded, CorruptedValue))?;
+
+                            list.insert(name, Index::new(prefix.into(), field.into())?);
+                        }
+                    }
+                }
+
+                // entries of dropped indexes are left behind if dropping fails halfway
+                for (name, indexed, key) in entries {
+                    if let Some(index) = list.get_mut(&name) {
+                        index.insert(indexed, storage.get(key).await?);
+                    }
+                }
+
+                self.any.store(!list.is_empty(), Ordering::SeqCst);
+                Ok(())
+            })
+            .await
+            .map(|_| ())
+    }
+
+    /// Create index and fill it from a scan of prefix. Returns indexed key count.
+    pub async fn create(
+        &self,
+        storage: &dyn Storage,
+        name: String,
+        prefix: String,
+        field: String,
+    ) -> Result<usize> {
+        if !valid_name(&name) {
+            return Err(err!(embedded, InvalidIndex));
+        }
+
+        let mut list = self.list.lock().await;
+
+        if list.contains_key(&name) {
+            return Err(err!(embedded, IndexExists));
+        }
+
+        let mut index = Index::new(prefix, field)?;
+
+        // set before filling, so a write missed by the scan updates the index once the lock is released
+        self.any.store(true, Ordering::SeqCst);
+
+        storage
+            .set(
+                format!("{INDEX_PREFIX}{name}"),
+                format!("{}\n{}", index.prefix, index.field),
+            )
+            .await?;
+
+        fill(storage, &name, &mut index).await?;
+
+        let count = index.keys.len();
+        list.insert(name, index);
+
+        Ok(count)
+    }
+
+    /// Read definitions from storage again and index their prefixes from scratch, like after a restore.
+    pub async fn rebuild(&self, storage: &dyn Storage) -> Result<()> {
+        let mut list = self.list.lock().await;
+        list.clear();
+        self.any.store(true, Ordering::SeqCst);
+
+        for key in storage.search(INDEX_PREFIX.to_string()).await? {
+            match key[INDEX_PREFIX.len()..].split_once(':') {
+                Some(_) => storage.delete(key).await?,
+                None => {
+                    let name = key[INDEX_PREFIX.len()..].to_string();
+                    let raw = storage.get(key).await?;
+                    let (prefix, field) =
+                        raw.split_once('\n').ok_or(err!(embedded, CorruptedValue))?;
+
+                    list.insert(name, Index::new(prefix.into(), field.into())?);
+                }
+            }
+        }
+
+        for (name, index) in list.iter_mut() {
+            fill(storage, name, index).await?;
+        }
+
+        self.any.store(!list.is_empty(), Ordering::SeqCst);
+
+        // nothing left to load
+        self.loaded.set(()).ok();
+
+        Ok(())
+    }
+
+    pub async fn remove(&self, storage: &dyn Storage, name: String) -> Result<()> {
+        let mut list = self.list.lock().await;
+        let index = list.remove(&name).ok_or(err!(embedded, IndexNotFound))?;
+        self.any.store(!list.is_empty(), Ordering::SeqCst);
+
+        storage.delete(format!("{INDEX_PREFIX}{name}")).await?;
+
+        for key in index.keys.keys() {
+            storage.delete(entry(&name, key)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Entry keys of key in every index covering it, they may be written after key changes.
+    pub async fn entries(&self, key: &str) -> Vec<String> {
+        if !self.any.load(Ordering::SeqCst) {
+            return Vec::new();
+        }
+
+        let list = self.list.lock().await;
+
+        list.iter()
+            .filter(|(_, index)| index.covers(key))
+            .map(|(name, _)| entry(name, key))
+            .collect()
+    }
+
+    /// Keys with given field value, sorted.
+    pub async fn find(&self, name: &str, value: &str) -> Result<Vec<String>> {
+        let list = self.list.lock().await;
+        let index = list.get(name).ok_or(err!(embedded, IndexNotFound))?;
+
+        Ok(index
+            .values
+            .get(value)
+            .map(|keys| keys.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// Bring indexes up to date after a change.
+    pub async fn update(&self, storage: &dyn Storage, event: &KeyEvent) -> Result<()> {
+        if !self.any.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let mut list = self.list.lock().await;
+
+        let (key, deleted) = match event {
+            KeyEvent::Set(key) => (key, false),
+            KeyEvent::Delete(key) | KeyEvent::Expire(key) => (key, true),
+            // definitions are flushed too
+            KeyEvent::Flush => {
+                list.clear();
+                self.any.store(false, Ordering::SeqCst);
+                return Ok(());
+            }
+            KeyEvent::Lagged(_) => return Ok(()),
+        };
+
+        if !list.values().any(|index| index.covers(key)) {
+            return Ok(());
+        }
+
+        // value is read while holding the lock, so the last update always sees the last write
+        let value = if deleted {
+            None
+        } else {
+            storage.get(key.clone()).await.ok()
+        };
+
+        for (name, index) in list.iter_mut().filter(|(_, index)| index.covers(key)) {
+            let field = value
+                .as_deref()
+                .and_then(|value| extract(value, &index.pointer));
+
+            if index.keys.get(key) == field.as_ref() {
+                continue;
+            }
+
+            match field {
+                Some(field) => {
+                    storage.set(entry(name, key), field.clone()).await?;
+                    index.insert(key.clone(), field);
+                }
+                None => {
+                    storage.delete(entry(name, key)).await?;
+                    index.remove(key);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// index every covered key under prefix
+async fn fill(storage: &dyn Storage, name: &str, index: &mut Index) -> Result<()> {
+    let keys: Vec<String> = storage
+        .keys(index.prefix.clone())
+        .await?
+        .try_collect()
+        .await?;
+
+    for key in keys {
+        if !index.covers(&key) {
+            continue;
+        }
+
+        // key can be deleted while scanning
+        let Ok(value) = storage.get(key.clone()).await else {
+            continue;
+        };
+
+        if let Some(value) = extract(&value, &index.pointer) {
+            storage.set(entry(name, &key), value.clone()).await?;
+            index.insert(key, value);
+        }
+    }
+
+    Ok(())
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|character| character.is_alphanumeric() || character == '_' || character == '-')
+}
+
+fn entry(name: &str, key: &str) -> String {
+    format!("{INDEX_PREFIX}{name}:{key}")
+}
+
+// `$.address.city` into JSON pointer `/address/city`, `$` is the whole value
+fn pointer(field: &str) -> Option<String> {
+    let path = field.strip_prefix('$')?;
+
+    if path.is_empty() {
+        return Some(String::new());
+    }
+
+    path.strip_prefix('.')?
+        .split('.')
+        .map(|part| {
+            (!part.is_empty()).then(|| format!("/{}", part.replace('~', "~0").replace('/', "~1")))
+        })
+        .collect()
+}
+
+// only scalars are indexed, strings without quotes
+fn extract(value: &str, pointer: &str) -> Option<String> {
+    let value = serde_json::from_str::<Value>(value).ok()?;
+
+    match value.pointer(pointer)? {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+    }
+}
@@ -0,0 +1,53 @@
+const DEFAULT_QUEUE: usize = 1024;
+
+/// Request queue limits of server, see [`Server::with_limits`].
+///
+/// Requests wait in queue until server picks them up. Server stops picking up requests while in-flight limit is reached,
+/// so a slow storage fills the queue and callers feel the backpressure instead of memory growing without bound.
+///
+/// [`Server::with_limits`]: super::Server::with_limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// How many requests can wait in queue, at least one (1). Default is 1024.
+    pub queue: usize,
+    /// How many requests can run at the same time, [`None`] for no limit. Default is [`None`].
+    ///
+    /// Blocking requests, like [`Request::BlockingLeftPop`], don't count since they may wait for other requests to finish.
+    ///
+    /// [`Request::BlockingLeftPop`]: crate::embedded::messaging::Request::BlockingLeftPop
+    pub in_flight: Option<usize>,
+    /// How many blocking requests can wait at the same time, [`None`] for no limit. Default is [`None`].
+    ///
+    /// Blocking requests over the limit fail right away with [`Error::Overloaded`], waiting for room would also hold back
+    /// the requests they wait for.
+    ///
+    /// [`Error::Overloaded`]: crate::embedded::Error::Overloaded
+    pub blocking: Option<usize>,
+    /// What [`Server::cast`] does when queue is full. Default is [`Overflow::Wait`].
+    ///
+    /// [`Server::cast`]: super::Server::cast
+    pub overflow: Overflow,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            queue: DEFAULT_QUEUE,
+            in_flight: None,
+            blocking: None,
+            overflow: Overflow::Wait,
+        }
+    }
+}
+
+/// Behavior of casting a request into a full queue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait until queue has room.
+    #[default]
+    Wait,
+    /// Fail right away with [`Error::Overloaded`].
+    ///
+    /// [`Error::Overloaded`]: crate::embedded::Error::Overloaded
+    Reject,
+}
@@ -0,0 +1,32 @@
+//! Lists are kept as a single value, every item is written as `<byte length>:<item>`.
+
+use crate::{embedded::Result, err};
+use std::collections::VecDeque;
+
+pub(super) fn encode(list: &VecDeque<String>) -> String {
+    list.iter()
+        .map(|item| format!("{}:{item}", item.len()))
+        .collect()
+}
+
+pub(super) fn decode(mut raw: &str) -> Result<VecDeque<String>> {
+    let mut list = VecDeque::new();
+
+    while !raw.is_empty() {
+        let (length, rest) = raw.split_once(':').ok_or(err!(embedded, ListParseFail))?;
+        let length = length
+            .parse::<usize>()
+            .map_err(|_| err!(embedded, ListParseFail))?;
+
+        if !rest.is_char_boundary(length) {
+            return Err(err!(embedded, ListParseFail));
+        }
+
+        let (item, rest) = rest.split_at(length);
+
+        list.push_back(item.to_string());
+        raw = rest;
+    }
+
+    Ok(list)
+}
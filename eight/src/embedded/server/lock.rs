@@ -0,0 +1,98 @@
+//! Lease locks. State is kept as the value of lock name, as `<token>:<expiry>:<owner>` with expiry in unix milliseconds.
+//!
+//! Fencing token grows every time lock changes hands, so released locks keep their state with a zero (0) expiry.
+//! State is an ordinary key, deleting it or flushing storage starts tokens from one (1) again.
+
+use crate::{embedded::Result, err};
+use std::time::Duration;
+
+struct Lease {
+    token: u64,
+    expires: u64,
+    owner: String,
+}
+
+impl Lease {
+    fn parse(state: Option<&str>) -> Result<Option<Self>> {
+        let Some(state) = state else {
+            return Ok(None);
+        };
+
+        let mut parts = state.splitn(3, ':');
+        let (Some(token), Some(expires), Some(owner)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(err!(embedded, LockParseFail));
+        };
+
+        Ok(Some(Self {
+            token: token.parse().map_err(|_| err!(embedded, LockParseFail))?,
+            expires: expires.parse().map_err(|_| err!(embedded, LockParseFail))?,
+            owner: owner.to_string(),
+        }))
+    }
+
+    fn held_by(&self, owner: &str, now: u64) -> bool {
+        self.owner == owner && now < self.expires
+    }
+
+    fn encode(&self) -> String {
+        format!("{}:{}:{}", self.token, self.expires, self.owner)
+    }
+}
+
+/// Acquire lock for owner, returns fencing token and new state. Owner already holding the lock extends it.
+pub(super) fn acquire(
+    state: Option<&str>,
+    owner: &str,
+    ttl: Duration,
+    now: u64,
+) -> Result<(u64, String)> {
+    let lease = Lease::parse(state)?;
+
+    let token = match lease {
+        Some(lease) if lease.held_by(owner, now) => lease.token,
+        Some(lease) if now < lease.expires => return Err(err!(embedded, LockHeld)),
+        Some(lease) => lease.token + 1,
+        None => 1,
+    };
+
+    let lease = Lease {
+        token,
+        expires: expiry(now, ttl),
+        owner: owner.to_string(),
+    };
+
+    Ok((token, lease.encode()))
+}
+
+/// Extend lock held by owner, returns fencing token and new state.
+pub(super) fn renew(
+    state: Option<&str>,
+    owner: &str,
+    ttl: Duration,
+    now: u64,
+) -> Result<(u64, String)> {
+    match Lease::parse(state)? {
+        Some(mut lease) if lease.held_by(owner, now) => {
+            lease.expires = expiry(now, ttl);
+            Ok((lease.token, lease.encode()))
+        }
+        _ => Err(err!(embedded, LockNotHeld)),
+    }
+}
+
+/// Release lock held by owner, returns new state.
+pub(super) fn release(state: Option<&str>, owner: &str, now: u64) -> Result<((), String)> {
+    match Lease::parse(state)? {
+        Some(mut lease) if lease.held_by(owner, now) => {
+            lease.expires = 0;
+            Ok(((), lease.encode()))
+        }
+        _ => Err(err!(embedded, LockNotHeld)),
+    }
+}
+
+// a lease too long to count ends with time itself
+fn expiry(now: u64, ttl: Duration) -> u64 {
+    now.saturating_add(u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX))
+}
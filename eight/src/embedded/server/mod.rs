@@ -1,28 +1,97 @@
 //! Server upgrades your storage into next level.
 
+mod blocking;
+mod dump;
 mod executor;
+mod fulltext;
+mod index;
+mod limits;
+mod list;
+mod lock;
+mod mutation;
 mod permission;
+mod pubsub;
+mod ratelimit;
+mod sketch;
+mod snapshot;
+mod stats;
+mod stream;
+mod timeseries;
+mod typed;
+mod watch;
 
+#[cfg(all(test, feature = "in-memory-storage"))]
+mod tests;
+
+pub use limits::{Limits, Overflow};
 pub use permission::*;
+pub use pubsub::Subscription;
+pub use stats::Stats;
 
 use crate::{
     embedded::{
         language::QueryExecutor,
-        messaging::{Request, Response},
-        storage::Storage,
+        messaging::{KeyEvent, Message, Request, Response},
+        storage::{metrics::Recorder, Storage},
+        Error,
     },
     err,
 };
+use blocking::Waiters;
 use executor::Executor;
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use fulltext::FullText;
+use futures::{
+    future::{self, Either},
+    Stream,
+};
+use index::Indexes;
+use pubsub::Hub;
+use snapshot::Snapshots;
+use std::{
+    collections::HashMap,
+    pin::pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{
-    sync::{mpsc, oneshot, Mutex, RwLock},
+    io::{AsyncRead, AsyncWrite},
+    sync::{
+        mpsc::{self, error::TrySendError},
+        oneshot, Mutex, RwLock, Semaphore,
+    },
     time,
 };
+use watch::Watchers;
+
+#[cfg(feature = "filesystem-storage")]
+use crate::embedded::storage::filesystem;
+#[cfg(feature = "filesystem-storage")]
+use std::{
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+#[cfg(feature = "filesystem-storage")]
+use tokio::fs;
+
+#[cfg(feature = "filesystem-storage")]
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+#[cfg(feature = "filesystem-storage")]
+const FAILED_SNAPSHOT_PREFIX: &str = "failed-snapshot-";
+
+const DEFAULT_WATCH_CAPACITY: usize = 1024;
+
+// keys holding index data, hidden from searches, never indexed and never written by clients
+fn is_reserved(key: &str) -> bool {
+    key.starts_with(index::INDEX_PREFIX)
+        || key
+            .strip_prefix(fulltext::FULLTEXT_PREFIX)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(':'))
+}
 
 struct ServerRequest {
     sender: oneshot::Sender<Response>,
     request: Request,
+    sent: Instant,
 }
 
 /// Server for Eight.
@@ -30,15 +99,27 @@ struct ServerRequest {
 /// Server is based on [`Storage`] but makes it production grade.
 ///
 /// Eight Server is focused on asynchronous execution. Every command spawns a new tokio task and messaging between command and requester done asynchronous.
-/// Casts are just spawns commands and returns receiver channel so you can get the result later.
+/// Casts are just spawns commands and returns receiver channel so you can get the result later. Queue and in-flight requests are bounded, see [`Limits`].
 /// Calls are also wait for response. You can also add timeout for calls.
 /// Server also has it is own redis-like query language.
 #[derive(Clone)]
 pub struct Server {
     executor: Arc<Executor>,
-    sender: mpsc::UnboundedSender<ServerRequest>,
-    receiver: Arc<Mutex<mpsc::UnboundedReceiver<ServerRequest>>>,
+    sender: mpsc::Sender<ServerRequest>,
+    receiver: Arc<Mutex<mpsc::Receiver<ServerRequest>>>,
+    limits: Limits,
+    in_flight: Arc<Semaphore>,
+    blocking: Arc<Semaphore>,
     permission: Arc<RwLock<Permission>>,
+    snapshots: Arc<Snapshots>,
+    #[cfg(feature = "filesystem-storage")]
+    snapshot_directory: Arc<RwLock<Option<PathBuf>>>,
+    requests: Arc<Recorder>,
+    watchers: Arc<Watchers>,
+    hub: Arc<Hub>,
+    waiters: Arc<Waiters>,
+    indexes: Arc<Indexes>,
+    fulltext: Arc<FullText>,
 }
 
 impl Server {
@@ -51,13 +132,63 @@ impl Server {
     /// let server = Server::new(storage);
     /// ```
     pub fn new(storage: impl Storage) -> Self {
-        let (sender, receiver) = mpsc::unbounded_channel();
+        Self::with_limits(storage, Limits::default())
+    }
+
+    /// Same with [`Server::new`], with request queue limits.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::{server::{Limits, Overflow, Server}, storage::memory, messaging::{Request, Response}};
+    ///
+    /// let limits = Limits {
+    ///   queue: 256,
+    ///   in_flight: Some(64),
+    ///   blocking: Some(1024),
+    ///   overflow: Overflow::Reject,
+    /// };
+    ///
+    /// let server = Server::with_limits(memory::Storage::new(), limits);
+    /// server.start().await;
+    ///
+    /// let response = server.call(Request::Exists("key".into())).await.unwrap();
+    /// assert_eq!(response, Response::Boolean(false));
+    /// # });
+    /// ```
+    pub fn with_limits(storage: impl Storage, limits: Limits) -> Self {
+        let limits = Limits {
+            queue: limits.queue.max(1),
+            in_flight: limits
+                .in_flight
+                .map(|in_flight| in_flight.clamp(1, Semaphore::MAX_PERMITS)),
+            blocking: limits
+                .blocking
+                .map(|blocking| blocking.clamp(1, Semaphore::MAX_PERMITS)),
+            ..limits
+        };
+        let (sender, receiver) = mpsc::channel(limits.queue);
 
         Self {
             executor: Arc::new(Executor::new(storage)),
             sender,
             receiver: Arc::new(Mutex::new(receiver)),
+            limits,
+            in_flight: Arc::new(Semaphore::new(
+                limits.in_flight.unwrap_or(Semaphore::MAX_PERMITS),
+            )),
+            blocking: Arc::new(Semaphore::new(
+                limits.blocking.unwrap_or(Semaphore::MAX_PERMITS),
+            )),
             permission: Default::default(),
+            snapshots: Default::default(),
+            #[cfg(feature = "filesystem-storage")]
+            snapshot_directory: Default::default(),
+            requests: Default::default(),
+            watchers: Default::default(),
+            hub: Default::default(),
+            waiters: Default::default(),
+            indexes: Default::default(),
+            fulltext: Default::default(),
         }
     }
 
@@ -92,6 +223,11 @@ impl Server {
         *self.permission.write().await = permission;
     }
 
+    /// Get current server permission.
+    pub async fn permission(&self) -> Permission {
+        *self.permission.read().await
+    }
+
     /// Run listener in another task so flow execution can continue.
     ///
     /// ```no_run
@@ -115,42 +251,574 @@ impl Server {
     /// Run listener. This function blocks the flow.
     pub async fn listen(&self) {
         while let Some(request) = self.receiver.lock().await.recv().await {
-            let ServerRequest { sender, request } = request;
+            let ServerRequest {
+                mut sender,
+                request,
+                sent,
+            } = request;
+            let server = self.clone();
 
-            let executor = Arc::clone(&self.executor);
-            let permission = Arc::clone(&self.permission);
+            // waiting here leaves requests in queue, so casts wait or get rejected instead
+            let permit = if is_blocking(&request) {
+                // requests behind would wait too, including the ones blocking requests wait for
+                match self.blocking.clone().try_acquire_owned() {
+                    Ok(permit) => Some(permit),
+                    Err(_) => {
+                        self.requests.record(sent.elapsed(), true);
+                        sender.send(err!(embedded, Overloaded).as_response()).ok();
+                        continue;
+                    }
+                }
+            } else {
+                self.in_flight.clone().acquire_owned().await.ok()
+            };
 
             tokio::spawn(async move {
-                let is_allowed = { permission.read().await.allowed(&request) };
+                let _permit = permit;
 
-                if let Err(error) = is_allowed {
-                    sender.send(error.as_response()).ok();
+                let requeue = match &request {
+                    Request::BlockingLeftPop(key, _) => Some(key.clone()),
+                    _ => None,
+                };
+
+                let response = if is_blocking(&request) {
+                    // caller may stop waiting, like call_in does after timeout
+                    let execute = pin!(server.execute(request));
+
+                    match future::select(execute, pin!(sender.closed())).await {
+                        Either::Left((response, _)) => response,
+                        Either::Right(_) => return,
+                    }
                 } else {
-                    let response = match request {
-                        Request::Set(key, value) => executor.set(key, value).await,
-                        Request::Get(key) => executor.get(key).await,
-                        Request::Delete(key) => executor.delete(key).await,
-                        Request::Exists(key) => executor.exists(key).await,
-                        Request::Increment(key, num) => executor.increment(key, num).await,
-                        Request::Decrement(key, num) => executor.decrement(key, num).await,
-                        Request::Search(key) => executor.search(key).await,
-                        Request::Flush => executor.flush().await,
-                        Request::DowngradePermission => {
-                            let mut permission = permission.write().await;
-                            *permission = permission.lower();
-
-                            Response::Ok
-                        }
-                    };
-
-                    sender.send(response).ok();
+                    server.execute(request).await
+                };
+
+                let failed = matches!(response, Response::Error(_));
+                server.requests.record(sent.elapsed(), failed);
+
+                // popped value lost its receiver in the meantime, it goes back to where it was
+                if let (Err(Response::Text(value)), Some(key)) = (sender.send(response), requeue) {
+                    server.run(Request::LeftPush(key, value)).await;
                 }
             });
         }
     }
 
+    async fn execute(&self, request: Request) -> Response {
+        let is_allowed = { self.permission.read().await.allowed(&request) };
+
+        if let Err(error) = is_allowed {
+            return error.as_response();
+        }
+
+        // index data is only changed by index commands
+        if KeyEvent::of(&request)
+            .iter()
+            .filter_map(KeyEvent::key)
+            .any(is_reserved)
+        {
+            return err!(embedded, ReservedKey).as_response();
+        }
+
+        self.run(request).await
+    }
+
+    async fn run(&self, request: Request) -> Response {
+        let executor = &self.executor;
+
+        for result in [
+            self.indexes.load(executor.storage()).await,
+            self.fulltext.load(executor.storage()).await,
+        ] {
+            if let Err(error) = result {
+                return error.as_response();
+            }
+        }
+
+        let _barrier = self
+            .snapshots
+            .before(&request, executor.storage(), &self.indexes, &self.fulltext)
+            .await;
+        let mut events = KeyEvent::of(&request);
+
+        let mut response = match request {
+            Request::Set(key, value) => executor.set(key, value).await,
+            Request::Get(key) => executor.get(key).await,
+            Request::Delete(key) => executor.delete(key).await,
+            Request::Exists(key) => executor.exists(key).await,
+            Request::Increment(key, num) => executor.increment(key, num).await,
+            Request::Decrement(key, num) => executor.decrement(key, num).await,
+            Request::Search(key) => executor.search(key).await,
+            Request::Flush => executor.flush().await,
+            Request::Backup(path) => self.backup(path).await,
+            Request::DowngradePermission => {
+                let mut permission = self.permission.write().await;
+                *permission = permission.lower();
+
+                Response::Ok
+            }
+            Request::Publish(channel, message) => {
+                Response::Number(self.hub.publish(&channel, &message))
+            }
+            Request::Subscribe(_) | Request::PatternSubscribe(_) | Request::Unsubscribe(_) => {
+                err!(embedded, SubscriptionRequired).as_response()
+            }
+            Request::LeftPush(key, value) => executor.left_push(key, value).await,
+            Request::RightPush(key, value) => executor.right_push(key, value).await,
+            Request::LeftPop(key) => {
+                let (response, deleted) = executor.left_pop(key.clone()).await;
+
+                if deleted {
+                    events = vec![KeyEvent::Delete(key)];
+                }
+
+                response
+            }
+            Request::BlockingLeftPop(key, timeout) => {
+                let (response, deleted) = self.blocking_left_pop(key.clone(), timeout).await;
+
+                if deleted {
+                    events = vec![KeyEvent::Delete(key)];
+                }
+
+                response
+            }
+            Request::Wait(key, timeout) => self.wait(key, timeout).await,
+            Request::Rename(from, to) => executor.rename(from, to).await,
+            Request::RenameNx(from, to) => executor.rename_nx(from, to).await,
+            Request::Copy(from, to) => executor.copy(from, to).await,
+            Request::SetNx(key, value) => executor.set_nx(key, value).await,
+            Request::SetXx(key, value) => executor.set_xx(key, value).await,
+            Request::GetSet(key, value) => executor.get_set(key, value).await,
+            Request::GetDelete(key) => executor.get_delete(key).await,
+            Request::Append(key, value) => executor.append(key, value).await,
+            Request::Length(key) => executor.length(key).await,
+            Request::GetRange(key, start, end) => executor.get_range(key, start, end).await,
+            Request::Metadata(key) => executor.metadata(key).await,
+            Request::History(key) => executor.history(key).await,
+            Request::GetAt(key, time) => executor.get_at(key, time).await,
+            Request::CreateIndex(name, prefix, field) => {
+                match self
+                    .indexes
+                    .create(executor.storage(), name, prefix, field)
+                    .await
+                {
+                    Ok(count) => Response::Number(count),
+                    Err(error) => error.as_response(),
+                }
+            }
+            Request::DropIndex(name) => match self.indexes.remove(executor.storage(), name).await {
+                Ok(_) => Response::Ok,
+                Err(error) => error.as_response(),
+            },
+            Request::Find(name, value) => match self.indexes.find(&name, &value).await {
+                Ok(keys) => Response::TextList(keys),
+                Err(error) => error.as_response(),
+            },
+            Request::FullTextIndex(prefix) => {
+                match self.fulltext.create(executor.storage(), prefix).await {
+                    Ok(count) => Response::Number(count),
+                    Err(error) => error.as_response(),
+                }
+            }
+            Request::FullTextDrop(prefix) => {
+                match self.fulltext.remove(executor.storage(), prefix).await {
+                    Ok(_) => Response::Ok,
+                    Err(error) => error.as_response(),
+                }
+            }
+            Request::RateLimit(key, limit, window, algorithm) => {
+                executor.rate_limit(key, limit, window, algorithm).await
+            }
+            Request::Lock(name, owner, ttl) => executor.lock(name, owner, ttl).await,
+            Request::Unlock(name, owner) => executor.unlock(name, owner).await,
+            Request::Renew(name, owner, ttl) => executor.renew(name, owner, ttl).await,
+            Request::StreamAdd(key, fields, max) => executor.stream_add(key, fields, max).await,
+            Request::StreamTrim(key, max) => executor.stream_trim(key, max).await,
+            Request::StreamRange(key, start, end, count) => {
+                executor.stream_range(key, start, end, count).await
+            }
+            Request::StreamRead(key, after, count) => executor.stream_read(key, after, count).await,
+            Request::BlockingStreamRead(key, after, count, timeout) => {
+                self.blocking_stream_read(key, after, count, timeout).await
+            }
+            Request::StreamGroupCreate(key, group, start, timeout) => {
+                executor
+                    .stream_group_create(key, group, start, timeout)
+                    .await
+            }
+            Request::StreamGroupDestroy(key, group) => {
+                executor.stream_group_destroy(key, group).await
+            }
+            Request::StreamReadGroup(key, group, consumer, count) => {
+                executor
+                    .stream_read_group(key, group, consumer, count)
+                    .await
+            }
+            Request::StreamAck(key, group, ids) => executor.stream_ack(key, group, ids).await,
+            Request::HyperLogLogAdd(key, items) => executor.hyper_log_log_add(key, items).await,
+            Request::HyperLogLogCount(keys) => executor.hyper_log_log_count(keys).await,
+            Request::HyperLogLogMerge(key, sources) => {
+                executor.hyper_log_log_merge(key, sources).await
+            }
+            Request::BloomReserve(key, error_rate, capacity) => {
+                executor.bloom_reserve(key, error_rate, capacity).await
+            }
+            Request::BloomAdd(key, item) => executor.bloom_add(key, item).await,
+            Request::BloomExists(key, item) => executor.bloom_exists(key, item).await,
+            Request::TimeSeriesCreate(key, retention) => {
+                executor.time_series_create(key, retention).await
+            }
+            Request::TimeSeriesAdd(key, time, value) => {
+                executor.time_series_add(key, time, value).await
+            }
+            Request::TimeSeriesRange(key, from, to, aggregation) => {
+                executor.time_series_range(key, from, to, aggregation).await
+            }
+            Request::TimeSeriesRule(key, aggregation, bucket, retention) => {
+                executor
+                    .time_series_rule(key, aggregation, bucket, retention)
+                    .await
+            }
+            Request::FullTextSearch(prefix, query) => {
+                match self.fulltext.search(&prefix, &query).await {
+                    Ok(keys) => Response::TextList(keys),
+                    Err(error) => error.as_response(),
+                }
+            }
+        };
+
+        // conditional requests answer false when they change nothing
+        if !matches!(response, Response::Error(_) | Response::Boolean(false)) {
+            for event in events {
+                // indexes are updated before answering, so a find right after a write sees it
+                for result in [
+                    self.indexes.update(executor.storage(), &event).await,
+                    self.fulltext.update(executor.storage(), &event).await,
+                ] {
+                    if let Err(error) = result {
+                        response = error.as_response();
+                    }
+                }
+
+                self.waiters.wake(&event);
+                self.watchers.publish(event);
+            }
+        }
+
+        response
+    }
+
+    async fn blocking_left_pop(&self, key: String, timeout: Duration) -> (Response, bool) {
+        let waiter = self.waiters.register(&key);
+        let deadline = blocking::deadline(timeout);
+
+        loop {
+            // listening starts before trying, so a push in between is not missed
+            let changed = waiter.changed();
+
+            let attempt = {
+                let request = Request::LeftPop(key.clone());
+                let _barrier = self
+                    .snapshots
+                    .before(
+                        &request,
+                        self.executor.storage(),
+                        &self.indexes,
+                        &self.fulltext,
+                    )
+                    .await;
+
+                self.executor.left_pop(key.clone()).await
+            };
+
+            if !matches!(attempt, (Response::Error(Error::ListEmpty), _)) {
+                return attempt;
+            }
+
+            if !blocking::until(changed, deadline).await {
+                return (err!(embedded, WaitTimeout).as_response(), false);
+            }
+        }
+    }
+
+    async fn blocking_stream_read(
+        &self,
+        key: String,
+        after: String,
+        count: usize,
+        timeout: Duration,
+    ) -> Response {
+        let waiter = self.waiters.register(&key);
+        let deadline = blocking::deadline(timeout);
+
+        // new entries are the ones after the last entry at the time of request
+        let after = match after.as_str() {
+            "$" => match self.executor.stream_last(&key).await {
+                Ok(last) => last,
+                Err(error) => return error.as_response(),
+            },
+            _ => after,
+        };
+
+        loop {
+            // listening starts before reading, so an entry added in between is not missed
+            let changed = waiter.changed();
+            let attempt = self
+                .executor
+                .stream_read(key.clone(), after.clone(), count)
+                .await;
+
+            if !matches!(&attempt, Response::List(entries) if entries.is_empty()) {
+                return attempt;
+            }
+
+            if !blocking::until(changed, deadline).await {
+                return err!(embedded, WaitTimeout).as_response();
+            }
+        }
+    }
+
+    async fn wait(&self, key: String, timeout: Duration) -> Response {
+        let waiter = self.waiters.register(&key);
+        let deadline = blocking::deadline(timeout);
+
+        loop {
+            // listening starts before checking, so a key created in between is not missed
+            let changed = waiter.changed();
+
+            match self.executor.storage().exists(key.clone()).await {
+                Ok(true) => return Response::Ok,
+                Ok(false) => {}
+                Err(error) => return error.as_response(),
+            }
+
+            if !blocking::until(changed, deadline).await {
+                return err!(embedded, WaitTimeout).as_response();
+            }
+        }
+    }
+
+    #[cfg(feature = "filesystem-storage")]
+    async fn backup(&self, name: String) -> Response {
+        let Some(directory) = self.snapshot_directory.read().await.clone() else {
+            return err!(embedded, SnapshotDirectoryMissing).as_response();
+        };
+
+        // only a new directory right under snapshot directory, no separators, roots or parents
+        let mut components = Path::new(&name).components();
+
+        let (Some(Component::Normal(_)), None) = (components.next(), components.next()) else {
+            return err!(embedded, InvalidSnapshotName).as_response();
+        };
+
+        match self.snapshot(directory.join(name)).await {
+            Ok(count) => Response::Number(count as usize),
+            Err(error) => error.as_response(),
+        }
+    }
+
+    #[cfg(not(feature = "filesystem-storage"))]
+    async fn backup(&self, _name: String) -> Response {
+        Response::Error(crate::embedded::Error::Custom(
+            "Backup requires filesystem storage feature".to_string(),
+        ))
+    }
+
+    /// Stream changes of keys starting with prefix. Events are sent after a mutating request succeeds, flushes are sent to every watcher.
+    ///
+    /// Every watcher buffers up to 1024 events, see [`Server::watch_with_capacity`].
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::{server::Server, storage::memory, messaging::{KeyEvent, Request}};
+    /// use futures::StreamExt;
+    ///
+    /// let server = Server::new(memory::Storage::new());
+    /// server.start().await;
+    ///
+    /// let mut events = Box::pin(server.watch("user:"));
+    ///
+    /// server.call(Request::Set("user:1".into(), "bob".into())).await.unwrap();
+    /// server.call(Request::Set("session:1".into(), "token".into())).await.unwrap();
+    /// server.call(Request::Delete("user:1".into())).await.unwrap();
+    ///
+    /// assert_eq!(events.next().await, Some(KeyEvent::Set("user:1".into())));
+    /// assert_eq!(events.next().await, Some(KeyEvent::Delete("user:1".into())));
+    /// # });
+    /// ```
+    pub fn watch<T>(&self, prefix: T) -> impl Stream<Item = KeyEvent>
+    where
+        T: ToString,
+    {
+        self.watch_with_capacity(prefix, DEFAULT_WATCH_CAPACITY)
+    }
+
+    /// Same with [`Server::watch`], with a custom buffer size.
+    ///
+    /// Events are dropped while buffer is full, watcher receives [`KeyEvent::Lagged`] with the number of dropped events
+    /// in their place, after the events buffered before them.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::{server::Server, storage::memory, messaging::{KeyEvent, Request}};
+    /// use futures::StreamExt;
+    ///
+    /// let server = Server::new(memory::Storage::new());
+    /// server.start().await;
+    ///
+    /// let mut events = Box::pin(server.watch_with_capacity("", 2));
+    ///
+    /// for i in 0..5 {
+    ///   server.call(Request::Set(format!("key{i}"), "value".into())).await.unwrap();
+    /// }
+    ///
+    /// assert_eq!(events.next().await, Some(KeyEvent::Set("key0".into())));
+    /// assert_eq!(events.next().await, Some(KeyEvent::Set("key1".into())));
+    /// assert_eq!(events.next().await, Some(KeyEvent::Lagged(3)));
+    ///
+    /// server.call(Request::Set("key5".into(), "value".into())).await.unwrap();
+    /// assert_eq!(events.next().await, Some(KeyEvent::Set("key5".into())));
+    /// # });
+    /// ```
+    pub fn watch_with_capacity<T>(&self, prefix: T, capacity: usize) -> impl Stream<Item = KeyEvent>
+    where
+        T: ToString,
+    {
+        self.watchers.subscribe(prefix.to_string(), capacity)
+    }
+
+    /// Create a new subscription, returns its handle and the stream of messages published to its channels.
+    ///
+    /// Subscription is removed when stream is dropped. Up to 1024 messages are buffered, messages are dropped while buffer is full.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::{server::Server, storage::memory, messaging::{Message, Request, Response}};
+    /// use futures::StreamExt;
+    ///
+    /// let server = Server::new(memory::Storage::new());
+    /// server.start().await;
+    ///
+    /// let (subscription, messages) = server.subscription();
+    /// let mut messages = Box::pin(messages);
+    ///
+    /// subscription.subscribe("news");
+    /// subscription.pattern_subscribe("user:*");
+    ///
+    /// let response = server.call(Request::Publish("user:42".into(), "hello".into())).await.unwrap();
+    /// assert_eq!(response, Response::Number(1));
+    ///
+    /// let message = messages.next().await.unwrap();
+    /// assert_eq!(message.channel, "user:42");
+    /// assert_eq!(message.pattern.as_deref(), Some("user:*"));
+    /// assert_eq!(message.message, "hello");
+    /// # });
+    /// ```
+    pub fn subscription(&self) -> (Subscription, impl Stream<Item = Message>) {
+        self.hub.register()
+    }
+
+    /// Same with [`Server::query`], subscription commands change given subscription.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::{server::Server, storage::memory, messaging::Response};
+    /// use futures::StreamExt;
+    /// use std::collections::HashMap;
+    ///
+    /// let server = Server::new(memory::Storage::new());
+    /// server.start().await;
+    ///
+    /// let (subscription, messages) = server.subscription();
+    /// let mut messages = Box::pin(messages);
+    ///
+    /// let results = server
+    ///   .query_with_subscription("subscribe news; publish news hello;", HashMap::new(), &subscription)
+    ///   .await
+    ///   .unwrap();
+    ///
+    /// assert_eq!(results, vec![Response::Ok, Response::Number(1)]);
+    /// assert_eq!(messages.next().await.unwrap().message, "hello");
+    /// # });
+    /// ```
+    pub async fn query_with_subscription<T>(
+        &self,
+        query: T,
+        env: HashMap<String, String>,
+        subscription: &Subscription,
+    ) -> super::Result<Vec<Response>>
+    where
+        T: ToString,
+    {
+        let mut runtime = QueryExecutor::new(query.to_string(), env);
+        runtime.execute(self, Some(subscription)).await
+    }
+
+    pub(crate) async fn subscription_request(
+        &self,
+        request: Request,
+        subscription: &Subscription,
+    ) -> Response {
+        if let Err(error) = self.permission.read().await.allowed(&request) {
+            return error.as_response();
+        }
+
+        match request {
+            Request::Subscribe(channel) => {
+                subscription.subscribe(channel);
+                Response::Ok
+            }
+            Request::PatternSubscribe(pattern) => {
+                subscription.pattern_subscribe(pattern);
+                Response::Ok
+            }
+            Request::Unsubscribe(name) => Response::Boolean(subscription.unsubscribe(&name)),
+            request => self.execute(request).await,
+        }
+    }
+
+    /// Returns request and storage statistics.
+    ///
+    /// Storage numbers are only available if storage records them, like [`metrics::Storage`] does.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::{server::Server, storage::{memory, metrics, Operation}, messaging::Request};
+    ///
+    /// let server = Server::new(metrics::Storage::new(memory::Storage::new()));
+    /// server.start().await;
+    ///
+    /// server.call(Request::Set("user".into(), "bob".into())).await.unwrap();
+    /// server.call(Request::Get("other".into())).await.unwrap();
+    ///
+    /// let stats = server.stats();
+    /// assert_eq!(stats.requests.calls, 2);
+    /// assert_eq!(stats.requests.errors, 1);
+    ///
+    /// let storage = stats.storage.unwrap();
+    /// assert_eq!(storage.operation(Operation::Set).calls, 1);
+    /// # });
+    /// ```
+    ///
+    /// [`metrics::Storage`]: crate::embedded::storage::metrics::Storage
+    pub fn stats(&self) -> Stats {
+        let in_flight_limit = self.limits.in_flight.unwrap_or(Semaphore::MAX_PERMITS);
+        let blocking_limit = self.limits.blocking.unwrap_or(Semaphore::MAX_PERMITS);
+
+        Stats {
+            requests: self.requests.summary(),
+            queued: self.sender.max_capacity() - self.sender.capacity(),
+            in_flight: in_flight_limit - self.in_flight.available_permits(),
+            blocking: blocking_limit - self.blocking.available_permits(),
+            storage: self.executor.storage().metrics(),
+        }
+    }
+
     /// Sends request to the server and returns response receiver. This function is useful when you need to run a command and get its result later.
     ///
+    /// If request queue is full, waits until it has room or fails with [`Error::Overloaded`], depending on [`Limits::overflow`].
+    ///
     /// ```
     /// # tokio_test::block_on(async {
     /// use eight::embedded::{server::Server, storage::memory::Storage, messaging::{Request, Response}};
@@ -169,13 +837,25 @@ impl Server {
     /// ```
     pub async fn cast(&self, request: Request) -> super::Result<oneshot::Receiver<Response>> {
         let (sender, receiver) = oneshot::channel();
-        let request = ServerRequest { sender, request };
+        let request = ServerRequest {
+            sender,
+            request,
+            sent: Instant::now(),
+        };
 
-        if self.sender.send(request).is_err() {
-            Err(err!(embedded, SendFail))
-        } else {
-            Ok(receiver)
-        }
+        let sent = match self.limits.overflow {
+            Overflow::Wait => self
+                .sender
+                .send(request)
+                .await
+                .map_err(|_| err!(embedded, SendFail)),
+            Overflow::Reject => self.sender.try_send(request).map_err(|error| match error {
+                TrySendError::Full(_) => err!(embedded, Overloaded),
+                TrySendError::Closed(_) => err!(embedded, SendFail),
+            }),
+        };
+
+        sent.map(|_| receiver)
     }
 
     /// Sends request to the server and returns response.
@@ -202,12 +882,241 @@ impl Server {
     }
 
     /// Same with call, but also takes a duration as a parameter which allows you to set a timeout for call.
+    ///
+    /// Blocking requests, like [`Request::BlockingLeftPop`] and [`Request::Wait`], stop waiting when timeout is reached.
     pub async fn call_in(&self, request: Request, timeout: Duration) -> super::Result<Response> {
         time::timeout(timeout, self.call(request))
             .await
             .map_err(|_| err!(embedded, RecvTimeout))?
     }
 
+    /// Copy every key-value into another storage, as they are at the moment snapshot starts. Returns how many entries are copied.
+    ///
+    /// Writes are not stopped while copying. Before a write changes a key, its old value is copied into snapshot first.
+    /// Listener must be running, writes that are already running are waited before starting.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::{server::Server, storage::{Storage, memory}, messaging::Request};
+    ///
+    /// let server = Server::new(memory::Storage::new());
+    /// server.start().await;
+    ///
+    /// server.call(Request::Set("user".into(), "bob".into())).await.unwrap();
+    ///
+    /// let target = memory::Storage::new();
+    /// // ...
+    /// # let target = std::sync::Arc::new(target);
+    /// # struct Shared(std::sync::Arc<memory::Storage>);
+    /// # #[eight::embedded::storage::async_trait]
+    /// # impl Storage for Shared {
+    /// #   async fn set(&self, key: String, value: String) -> eight::embedded::Result<()> { self.0.set(key, value).await }
+    /// #   async fn get(&self, key: String) -> eight::embedded::Result<String> { self.0.get(key).await }
+    /// #   async fn delete(&self, key: String) -> eight::embedded::Result<()> { self.0.delete(key).await }
+    /// #   async fn exists(&self, key: String) -> eight::embedded::Result<bool> { self.0.exists(key).await }
+    /// #   async fn increment(&self, key: String, num: usize) -> eight::embedded::Result<usize> { self.0.increment(key, num).await }
+    /// #   async fn decrement(&self, key: String, num: usize) -> eight::embedded::Result<usize> { self.0.decrement(key, num).await }
+    /// #   async fn search(&self, key: String) -> eight::embedded::Result<Vec<String>> { self.0.search(key).await }
+    /// #   async fn flush(&self) -> eight::embedded::Result<()> { self.0.flush().await }
+    /// # }
+    /// assert_eq!(server.snapshot_into(Shared(target.clone())).await.unwrap(), 1);
+    /// assert_eq!(target.get("user".into()).await.unwrap(), "bob");
+    /// # });
+    /// ```
+    pub async fn snapshot_into(&self, target: impl Storage) -> super::Result<u64> {
+        self.snapshots
+            .run(self.executor.storage(), Box::new(target))
+            .await
+    }
+
+    /// Create a consistent snapshot of server in a new directory, as a filesystem storage. Returns how many entries are copied.
+    ///
+    /// See [`Server::snapshot_into`] for details.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::{server::Server, storage::{Storage, memory, filesystem}, messaging::Request};
+    ///
+    /// let server = Server::new(memory::Storage::new());
+    /// server.start().await;
+    ///
+    /// server.call(Request::Set("user".into(), "bob".into())).await.unwrap();
+    /// server.snapshot("./snapshot_server_test").await.unwrap();
+    ///
+    /// let snapshot = filesystem::Storage::from_path("./snapshot_server_test");
+    /// assert_eq!(snapshot.get("user".into()).await.unwrap(), "bob");
+    ///
+    /// # snapshot.flush().await;
+    /// # });
+    /// ```
+    #[cfg(feature = "filesystem-storage")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "filesystem-storage")))]
+    pub async fn snapshot<P>(&self, path: P) -> super::Result<u64>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+
+        if fs::try_exists(&path).await.unwrap_or(true) {
+            return Err(err!(embedded, SnapshotPathExists));
+        }
+
+        self.snapshot_into(filesystem::Storage::from_path(path))
+            .await
+    }
+
+    /// Set the directory [`Request::Backup`] creates snapshots in. Backups fail until it is set.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::{server::Server, storage::memory, messaging::{Request, Response}};
+    ///
+    /// let server = Server::new(memory::Storage::new());
+    /// server.set_snapshot_directory("./backup_server_test").await;
+    /// server.start().await;
+    ///
+    /// server.call(Request::Set("user".into(), "bob".into())).await.unwrap();
+    ///
+    /// let response = server.call(Request::Backup("first".into())).await.unwrap();
+    /// assert_eq!(response, Response::Number(1));
+    ///
+    /// let response = server.call(Request::Backup("../outside".into())).await.unwrap();
+    /// assert!(matches!(response, Response::Error(_)));
+    /// # std::fs::remove_dir_all("./backup_server_test").ok();
+    /// # });
+    /// ```
+    #[cfg(feature = "filesystem-storage")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "filesystem-storage")))]
+    pub async fn set_snapshot_directory<P>(&self, directory: P)
+    where
+        P: Into<PathBuf>,
+    {
+        *self.snapshot_directory.write().await = Some(directory.into());
+    }
+
+    /// Create snapshots into given directory periodically, keeping only the newest ones.
+    ///
+    /// Every snapshot is placed in its own `snapshot-<unix time in milliseconds>` directory.
+    /// Failed ones are renamed to `failed-snapshot-<unix time in milliseconds>`, they are kept separately and don't count as snapshots.
+    ///
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::{server::Server, storage::memory};
+    /// use std::time::Duration;
+    ///
+    /// let server = Server::new(memory::Storage::new());
+    /// server.start().await;
+    ///
+    /// // every hour, keep last day
+    /// server.schedule_snapshots("/path/to/snapshots", Duration::from_secs(3600), 24).await;
+    /// # });
+    /// ```
+    #[cfg(feature = "filesystem-storage")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "filesystem-storage")))]
+    pub async fn schedule_snapshots<P>(&self, directory: P, interval: Duration, keep: usize)
+    where
+        P: Into<PathBuf>,
+    {
+        let server = self.clone();
+        let directory = directory.into();
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(interval);
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                let path = directory.join(format!("{SNAPSHOT_PREFIX}{timestamp:020}"));
+
+                // failed snapshots are left for inspection, apart so they don't push complete ones out of retention
+                if server.snapshot(path.clone()).await.is_err() {
+                    let failed = format!("{FAILED_SNAPSHOT_PREFIX}{timestamp:020}");
+                    fs::rename(&path, directory.join(failed)).await.ok();
+                }
+
+                snapshot::remove_old(&directory, SNAPSHOT_PREFIX, keep).await;
+                snapshot::remove_old(&directory, FAILED_SNAPSHOT_PREFIX, keep).await;
+            }
+        });
+    }
+
+    /// Write every key-value into writer with a versioned dump format and returns how many entries are written.
+    ///
+    /// Entries are streamed from storage one by one, so the keyspace never has to fit in memory.
+    /// Dump doesn't stop writes, values changed while dumping may or may not be included.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::{server::Server, storage::memory, messaging::{Request, Response}};
+    ///
+    /// let server = Server::new(memory::Storage::new());
+    /// server.start().await;
+    ///
+    /// server.call(Request::Set("user".into(), "bob".into())).await.unwrap();
+    ///
+    /// let mut dump = Vec::new();
+    /// assert_eq!(server.dump(&mut dump).await.unwrap(), 1);
+    ///
+    /// let other = Server::new(memory::Storage::new());
+    /// other.start().await;
+    ///
+    /// assert_eq!(other.restore(dump.as_slice()).await.unwrap(), 1);
+    /// assert_eq!(other.call(Request::Get("user".into())).await.unwrap(), Response::Text("bob".into()));
+    /// # });
+    /// ```
+    pub async fn dump<W>(&self, mut writer: W) -> super::Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        dump::write(self.executor.storage(), &mut writer).await
+    }
+
+    /// Read a dump created by [`Server::dump`] and set every entry. Returns how many entries are restored.
+    ///
+    /// Existing keys are kept unless dump has the same key. Entries before an invalid or truncated part stay restored.
+    /// Entries are set like [`Request::Set`] does, so watchers and waiting requests see them.
+    /// Indexes are rebuilt if dump has index definitions.
+    pub async fn restore<R>(&self, reader: R) -> super::Result<u64>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut entries = dump::Reader::open(reader).await?;
+        let mut count = 0;
+        let mut indexes_changed = false;
+
+        let result = loop {
+            match entries.next().await {
+                Ok(Some((key, value))) => {
+                    indexes_changed |= is_reserved(&key);
+
+                    if let Response::Error(error) = self.run(Request::Set(key, value)).await {
+                        break Err(error);
+                    }
+
+                    count += 1;
+                }
+                Ok(None) => break Ok(count),
+                Err(error) => break Err(error),
+            }
+        };
+
+        // definitions restored from dump may cover keys the dump doesn't have
+        if indexes_changed {
+            let storage = self.executor.storage();
+
+            self.indexes.rebuild(storage).await?;
+            self.fulltext.rebuild(storage).await?;
+        }
+
+        result
+    }
+
     /// Sends query to the server and returns response(s).
     ///
     /// ```
@@ -243,6 +1152,15 @@ impl Server {
         T: ToString,
     {
         let mut runtime = QueryExecutor::new(query.to_string(), env);
-        runtime.execute(self).await
+        runtime.execute(self, None).await
     }
 }
+
+fn is_blocking(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::BlockingLeftPop(_, _)
+            | Request::Wait(_, _)
+            | Request::BlockingStreamRead(_, _, _, _)
+    )
+}
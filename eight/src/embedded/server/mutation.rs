@@ -0,0 +1,83 @@
+use crate::embedded::messaging::Request;
+
+/// Keys a request changes.
+pub(super) enum Mutation<'a> {
+    /// Request doesn't change anything.
+    None,
+    /// Request changes given keys.
+    Keys(Vec<&'a str>),
+    /// Request can change every key.
+    All,
+}
+
+impl<'a> Mutation<'a> {
+    pub fn of(request: &'a Request) -> Self {
+        match request {
+            Request::Set(key, _)
+            | Request::Delete(key)
+            | Request::Increment(key, _)
+            | Request::Decrement(key, _)
+            | Request::LeftPush(key, _)
+            | Request::RightPush(key, _)
+            | Request::LeftPop(key)
+            | Request::Copy(_, key)
+            | Request::SetNx(key, _)
+            | Request::SetXx(key, _)
+            | Request::GetSet(key, _)
+            | Request::GetDelete(key)
+            | Request::Append(key, _)
+            | Request::RateLimit(key, _, _, _)
+            | Request::Lock(key, _, _)
+            | Request::Unlock(key, _)
+            | Request::Renew(key, _, _)
+            | Request::StreamAdd(key, _, _)
+            | Request::StreamTrim(key, _)
+            | Request::StreamGroupCreate(key, _, _, _)
+            | Request::StreamGroupDestroy(key, _)
+            | Request::StreamReadGroup(key, _, _, _)
+            | Request::StreamAck(key, _, _)
+            | Request::HyperLogLogAdd(key, _)
+            | Request::HyperLogLogMerge(key, _)
+            | Request::BloomReserve(key, _, _)
+            | Request::BloomAdd(key, _)
+            | Request::TimeSeriesCreate(key, _)
+            | Request::TimeSeriesAdd(key, _, _)
+            | Request::TimeSeriesRule(key, _, _, _) => Mutation::Keys(vec![key]),
+            Request::Rename(from, to) | Request::RenameNx(from, to) => {
+                Mutation::Keys(vec![from, to])
+            }
+            // index entries are written for every indexed key
+            Request::Flush
+            | Request::CreateIndex(_, _, _)
+            | Request::DropIndex(_)
+            | Request::FullTextIndex(_)
+            | Request::FullTextDrop(_) => Mutation::All,
+            Request::Get(_)
+            | Request::Exists(_)
+            | Request::Search(_)
+            | Request::Backup(_)
+            | Request::DowngradePermission
+            | Request::Publish(_, _)
+            | Request::Subscribe(_)
+            | Request::PatternSubscribe(_)
+            | Request::Unsubscribe(_)
+            | Request::Wait(_, _)
+            | Request::Length(_)
+            | Request::GetRange(_, _, _)
+            | Request::Metadata(_)
+            | Request::History(_)
+            | Request::GetAt(_, _)
+            | Request::Find(_, _)
+            | Request::FullTextSearch(_, _)
+            | Request::StreamRange(_, _, _, _)
+            | Request::StreamRead(_, _, _)
+            | Request::HyperLogLogCount(_)
+            | Request::BloomExists(_, _)
+            | Request::TimeSeriesRange(_, _, _, _) => Mutation::None,
+            // waiting must not hold snapshots back, every pop attempt is checked on its own
+            Request::BlockingLeftPop(_, _) | Request::BlockingStreamRead(_, _, _, _) => {
+                Mutation::None
+            }
+        }
+    }
+}
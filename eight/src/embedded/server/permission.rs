@@ -20,13 +20,67 @@ impl Permission {
     pub fn is_allowed(&self, request: &Request) -> bool {
         match request {
             // read-only
-            Request::Get(_) | Request::Exists(_) | Request::DowngradePermission => true,
+            Request::Get(_)
+            | Request::Exists(_)
+            | Request::DowngradePermission
+            | Request::Subscribe(_)
+            | Request::PatternSubscribe(_)
+            | Request::Unsubscribe(_)
+            | Request::Wait(_, _)
+            | Request::Length(_)
+            | Request::GetRange(_, _, _)
+            | Request::Metadata(_)
+            | Request::History(_)
+            | Request::GetAt(_, _)
+            | Request::Find(_, _)
+            | Request::FullTextSearch(_, _)
+            | Request::StreamRange(_, _, _, _)
+            | Request::StreamRead(_, _, _)
+            | Request::BlockingStreamRead(_, _, _, _)
+            | Request::HyperLogLogCount(_)
+            | Request::BloomExists(_, _)
+            | Request::TimeSeriesRange(_, _, _, _) => true,
             // requires admin or higher
             Request::Set(_, _)
             | Request::Delete(_)
             | Request::Increment(_, _)
             | Request::Decrement(_, _)
-            | Request::Search(_) => self == &Permission::Admin || self == &Permission::Owner,
+            | Request::Search(_)
+            | Request::Publish(_, _)
+            | Request::LeftPush(_, _)
+            | Request::RightPush(_, _)
+            | Request::LeftPop(_)
+            | Request::BlockingLeftPop(_, _)
+            | Request::Rename(_, _)
+            | Request::RenameNx(_, _)
+            | Request::Copy(_, _)
+            | Request::SetNx(_, _)
+            | Request::SetXx(_, _)
+            | Request::GetSet(_, _)
+            | Request::GetDelete(_)
+            | Request::Append(_, _)
+            | Request::CreateIndex(_, _, _)
+            | Request::DropIndex(_)
+            | Request::FullTextIndex(_)
+            | Request::FullTextDrop(_)
+            | Request::RateLimit(_, _, _, _)
+            | Request::Lock(_, _, _)
+            | Request::Unlock(_, _)
+            | Request::Renew(_, _, _)
+            | Request::StreamAdd(_, _, _)
+            | Request::StreamTrim(_, _)
+            | Request::StreamGroupCreate(_, _, _, _)
+            | Request::StreamGroupDestroy(_, _)
+            | Request::StreamReadGroup(_, _, _, _)
+            | Request::StreamAck(_, _, _)
+            | Request::HyperLogLogAdd(_, _)
+            | Request::HyperLogLogMerge(_, _)
+            | Request::BloomReserve(_, _, _)
+            | Request::BloomAdd(_, _)
+            | Request::TimeSeriesCreate(_, _)
+            | Request::TimeSeriesAdd(_, _, _)
+            | Request::TimeSeriesRule(_, _, _, _)
+            | Request::Backup(_) => self == &Permission::Admin || self == &Permission::Owner,
             // owner only
             Request::Flush => self == &Permission::Owner,
         }
@@ -0,0 +1,172 @@
+use crate::embedded::messaging::Message;
+use futures::{stream, Stream};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+use tokio::sync::mpsc;
+
+const SUBSCRIPTION_CAPACITY: usize = 1024;
+
+struct Subscriber {
+    channels: HashSet<String>,
+    patterns: HashSet<String>,
+    sender: mpsc::Sender<Message>,
+}
+
+/// Every subscription of server. Messages are dropped for subscribers with a full buffer.
+#[derive(Default)]
+pub(super) struct Hub {
+    subscribers: Mutex<HashMap<u64, Subscriber>>,
+    next_id: AtomicU64,
+}
+
+impl Hub {
+    pub fn register(self: &Arc<Self>) -> (Subscription, impl Stream<Item = Message>) {
+        let (sender, receiver) = mpsc::channel(SUBSCRIPTION_CAPACITY);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        self.lock().insert(
+            id,
+            Subscriber {
+                channels: HashSet::new(),
+                patterns: HashSet::new(),
+                sender,
+            },
+        );
+
+        let subscription = Subscription {
+            id,
+            hub: Arc::clone(self),
+        };
+
+        let messages = stream::unfold(receiver, |mut receiver| async move {
+            let message = receiver.recv().await?;
+            Some((message, receiver))
+        });
+
+        (subscription, messages)
+    }
+
+    pub fn publish(&self, channel: &str, message: &str) -> usize {
+        let mut subscribers = self.lock();
+        let mut received = 0;
+
+        // dropped streams are removed here
+        subscribers.retain(|_, subscriber| !subscriber.sender.is_closed());
+
+        for subscriber in subscribers.values() {
+            let direct = subscriber
+                .channels
+                .contains(channel)
+                .then_some(None)
+                .into_iter();
+
+            let patterns = subscriber
+                .patterns
+                .iter()
+                .filter(|pattern| matches(pattern, channel))
+                .map(|pattern| Some(pattern.clone()));
+
+            for pattern in direct.chain(patterns) {
+                let message = Message {
+                    channel: channel.to_string(),
+                    pattern,
+                    message: message.to_string(),
+                };
+
+                if subscriber.sender.try_send(message).is_ok() {
+                    received += 1;
+                }
+            }
+        }
+
+        received
+    }
+
+    fn update<T>(&self, id: u64, function: impl FnOnce(&mut Subscriber) -> T) -> Option<T> {
+        self.lock().get_mut(&id).map(function)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Subscriber>> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Channels and patterns a subscriber listens to. Created with [`Server::subscription`], together with the stream of messages.
+///
+/// Handle can be cloned, every clone changes the same subscription.
+///
+/// [`Server::subscription`]: super::Server::subscription
+#[derive(Clone)]
+pub struct Subscription {
+    id: u64,
+    hub: Arc<Hub>,
+}
+
+impl Subscription {
+    /// Subscribe to a channel.
+    pub fn subscribe<T: ToString>(&self, channel: T) {
+        self.hub.update(self.id, |subscriber| {
+            subscriber.channels.insert(channel.to_string())
+        });
+    }
+
+    /// Subscribe to channels matching a pattern. `*` matches any text and `?` matches a single character.
+    pub fn pattern_subscribe<T: ToString>(&self, pattern: T) {
+        self.hub.update(self.id, |subscriber| {
+            subscriber.patterns.insert(pattern.to_string())
+        });
+    }
+
+    /// Unsubscribe from a channel or pattern. Returns whether it was subscribed.
+    pub fn unsubscribe(&self, name: &str) -> bool {
+        self.hub
+            .update(self.id, |subscriber| {
+                let channel = subscriber.channels.remove(name);
+                let pattern = subscriber.patterns.remove(name);
+
+                channel || pattern
+            })
+            .unwrap_or(false)
+    }
+}
+
+// glob matching with backtracking to the last star
+fn matches(pattern: &str, text: &str) -> bool {
+    let (pattern, text) = (
+        pattern.chars().collect::<Vec<_>>(),
+        text.chars().collect::<Vec<_>>(),
+    );
+
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&character) if character == '?' || character == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&character| character == '*')
+}
@@ -0,0 +1,156 @@
+//! Rate limiting decisions. State is kept as the value of key, times are in unix milliseconds.
+//!
+//! - Fixed window: `fixed:<window start>:<count>`, window starts with the first request.
+//! - Sliding window: `sliding:<window start>:<count>:<previous count>`, windows are aligned and previous window is weighted by its overlap.
+//! - Token bucket: `bucket:<tokens>:<last refill>`, bucket holds up to limit tokens and refills limit tokens every window.
+
+use crate::{
+    embedded::{messaging::RateLimiter, Result},
+    err,
+};
+use std::time::Duration;
+
+#[derive(Debug, PartialEq)]
+pub(super) struct Decision {
+    pub allowed: bool,
+    pub remaining: usize,
+    pub retry_after: Duration,
+}
+
+/// Decide request at given time, returns decision and new state.
+pub(super) fn decide(
+    algorithm: RateLimiter,
+    state: Option<&str>,
+    limit: usize,
+    window: Duration,
+    now: u64,
+) -> Result<(Decision, String)> {
+    let window = u64::try_from(window.as_millis()).unwrap_or(u64::MAX).max(1);
+
+    match algorithm {
+        RateLimiter::FixedWindow => fixed(state, limit, window, now),
+        RateLimiter::SlidingWindow => sliding(state, limit, window, now),
+        RateLimiter::TokenBucket => bucket(state, limit, window, now),
+    }
+}
+
+fn fixed(state: Option<&str>, limit: usize, window: u64, now: u64) -> Result<(Decision, String)> {
+    let (start, mut count) = match parse(state, "fixed")?.as_deref() {
+        Some(&[start, count]) if now < (start as u64).saturating_add(window) => {
+            (start as u64, count as usize)
+        }
+        _ => (now, 0),
+    };
+
+    let allowed = count < limit;
+
+    if allowed {
+        count += 1;
+    }
+
+    let decision = Decision {
+        allowed,
+        remaining: limit.saturating_sub(count),
+        retry_after: if allowed {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(start.saturating_add(window).saturating_sub(now))
+        },
+    };
+
+    Ok((decision, format!("fixed:{start}:{count}")))
+}
+
+fn sliding(state: Option<&str>, limit: usize, window: u64, now: u64) -> Result<(Decision, String)> {
+    let start = now - now % window;
+
+    let (mut count, previous) = match parse(state, "sliding")?.as_deref() {
+        Some(&[last, count, previous]) if last as u64 == start => (count, previous),
+        Some(&[last, count, _]) if (last as u64).saturating_add(window) == start => (0.0, count),
+        _ => (0.0, 0.0),
+    };
+
+    let (window, limit) = (window as f64, limit as f64);
+    let elapsed = (now - start) as f64;
+    let weight = 1.0 - elapsed / window;
+
+    let allowed = previous * weight + count + 1.0 <= limit;
+
+    if allowed {
+        count += 1.0;
+    }
+
+    let retry_after = if allowed {
+        0.0
+    } else if limit < 1.0 {
+        window
+    } else if count + 1.0 <= limit {
+        // current window has room, wait until previous window weighs less
+        window * (1.0 - (limit - count - 1.0) / previous) - elapsed
+    } else {
+        // this window is the previous one after it ends
+        window - elapsed + (window * (1.0 - (limit - 1.0) / count)).max(0.0)
+    };
+
+    let decision = Decision {
+        allowed,
+        remaining: (limit - previous * weight - count).max(0.0) as usize,
+        retry_after: Duration::from_millis(retry_after.max(0.0).ceil() as u64),
+    };
+
+    Ok((decision, format!("sliding:{start}:{count}:{previous}")))
+}
+
+fn bucket(state: Option<&str>, limit: usize, window: u64, now: u64) -> Result<(Decision, String)> {
+    let (capacity, rate) = (limit as f64, limit as f64 / window as f64);
+
+    let mut tokens = match parse(state, "bucket")?.as_deref() {
+        Some(&[tokens, last]) => {
+            (tokens + now.saturating_sub(last as u64) as f64 * rate).min(capacity)
+        }
+        _ => capacity,
+    };
+
+    let allowed = tokens >= 1.0;
+
+    if allowed {
+        tokens -= 1.0;
+    }
+
+    let retry_after = if allowed {
+        0.0
+    } else if rate > 0.0 {
+        (1.0 - tokens) / rate
+    } else {
+        window as f64
+    };
+
+    let decision = Decision {
+        allowed,
+        remaining: tokens as usize,
+        retry_after: Duration::from_millis(retry_after.ceil() as u64),
+    };
+
+    Ok((decision, format!("bucket:{tokens}:{now}")))
+}
+
+// state written by another algorithm starts over, anything else isn't a rate limit
+fn parse(state: Option<&str>, name: &str) -> Result<Option<Vec<f64>>> {
+    let Some(state) = state else {
+        return Ok(None);
+    };
+
+    let mut parts = state.split(':');
+    let algorithm = parts.next().unwrap_or_default();
+
+    let numbers = parts
+        .map(|part| part.parse::<f64>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|_| err!(embedded, RateLimitParseFail))?;
+
+    match algorithm {
+        _ if algorithm == name => Ok(Some(numbers)),
+        "fixed" | "sliding" | "bucket" => Ok(None),
+        _ => Err(err!(embedded, RateLimitParseFail)),
+    }
+}
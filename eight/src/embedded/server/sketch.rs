@@ -0,0 +1,318 @@
+//! Probabilistic data structures kept as a single value, HyperLogLog for counting unique items and Bloom filter for membership.
+//!
+//! - HyperLogLog: tagged as `hyperloglog`, `s:<index>:<rank>,...` while few registers are set, `d:<register>...` with a character
+//!   for every register after.
+//! - Bloom filter: tagged as `bloom`, `<hash count>:<bit count>:<bits>` with six (6) bits in a character.
+//!
+//! Values are persisted, so items are hashed with FNV-1a instead of the standard hasher which may change between releases.
+
+use super::typed::{self, Type};
+use crate::{embedded::Result, err};
+use std::f64::consts::LN_2;
+
+// 2^14 registers, standard error is about 0.81%
+const PRECISION: u32 = 14;
+const REGISTERS: usize = 1 << PRECISION;
+const MAX_RANK: u8 = (64 - PRECISION + 1) as u8;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    /// Add item, returns true if estimation may have changed.
+    pub fn add(&mut self, item: &str) -> bool {
+        let hash = hash(item.as_bytes());
+
+        let index = (hash >> (64 - PRECISION)) as usize;
+        // marker bit keeps rank in range when the rest is all zeros
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        let size = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / size);
+
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|register| 2f64.powi(-(*register as i32)))
+            .sum();
+        let estimate = alpha * size * size / sum;
+
+        let zeros = self
+            .registers
+            .iter()
+            .filter(|register| **register == 0)
+            .count();
+
+        // linear counting is more accurate for small cardinalities
+        if estimate <= 2.5 * size && zeros > 0 {
+            (size * (size / zeros as f64).ln()).round() as usize
+        } else {
+            estimate.round() as usize
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let sparse: Vec<String> = self
+            .registers
+            .iter()
+            .enumerate()
+            .filter(|(_, register)| **register > 0)
+            .map(|(index, register)| format!("{index}:{register}"))
+            .collect();
+
+        let sparse = sparse.join(",");
+
+        if sparse.len() < REGISTERS {
+            typed::tag(Type::HyperLogLog, &format!("s:{sparse}"))
+        } else {
+            let dense: String = self
+                .registers
+                .iter()
+                .map(|register| ALPHABET[*register as usize] as char)
+                .collect();
+
+            typed::tag(Type::HyperLogLog, &format!("d:{dense}"))
+        }
+    }
+
+    pub fn decode(raw: &str) -> Result<Self> {
+        let fail = || err!(embedded, HyperLogLogParseFail);
+        let mut sketch = Self::default();
+        let raw = typed::untag(Type::HyperLogLog, raw).ok_or_else(fail)?;
+
+        if let Some(sparse) = raw.strip_prefix("s:") {
+            for register in sparse.split(',').filter(|register| !register.is_empty()) {
+                let (index, rank) = register.split_once(':').ok_or_else(fail)?;
+                let index = index.parse::<usize>().map_err(|_| fail())?;
+                let rank = rank.parse::<u8>().map_err(|_| fail())?;
+
+                if rank > MAX_RANK {
+                    return Err(fail());
+                }
+
+                *sketch.registers.get_mut(index).ok_or_else(fail)? = rank;
+            }
+        } else if let Some(dense) = raw.strip_prefix("d:") {
+            if dense.len() != REGISTERS {
+                return Err(fail());
+            }
+
+            for (register, character) in sketch.registers.iter_mut().zip(dense.bytes()) {
+                *register = sextet(character)
+                    .filter(|rank| *rank <= MAX_RANK)
+                    .ok_or_else(fail)?;
+            }
+        } else {
+            return Err(fail());
+        }
+
+        Ok(sketch)
+    }
+}
+
+// filters created on first add
+const ERROR_RATE: f64 = 0.01;
+const CAPACITY: usize = 1000;
+
+// 8 MiB of bits, about 7 million items with 1% error rate
+const MAX_BITS: usize = 1 << 26;
+// false positive rate can't get lower than 2^-64 with more hashes
+const MAX_HASHES: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Bloom {
+    hashes: usize,
+    size: usize,
+    bits: Vec<u64>,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self::new(ERROR_RATE, CAPACITY).expect("Default Bloom filter size is valid")
+    }
+}
+
+impl Bloom {
+    /// Filter sized for capacity items with given false positive rate.
+    ///
+    /// Fails with [`Error::InvalidFilter`] if error rate is not between zero (0) and one (1), capacity is zero (0)
+    /// or filter would be larger than 8 MiB.
+    ///
+    /// [`Error::InvalidFilter`]: crate::embedded::Error::InvalidFilter
+    pub fn new(error_rate: f64, capacity: usize) -> Result<Self> {
+        if !(error_rate > 0.0 && error_rate < 1.0) || capacity == 0 {
+            return Err(err!(embedded, InvalidFilter));
+        }
+
+        let capacity = capacity as f64;
+        let bits = (-capacity * error_rate.ln() / (LN_2 * LN_2))
+            .ceil()
+            .max(1.0);
+
+        if bits > MAX_BITS as f64 {
+            return Err(err!(embedded, InvalidFilter));
+        }
+
+        let hashes = (bits / capacity * LN_2)
+            .round()
+            .clamp(1.0, MAX_HASHES as f64);
+
+        Ok(Self::empty(hashes as usize, bits as usize))
+    }
+
+    fn empty(hashes: usize, size: usize) -> Self {
+        Self {
+            hashes,
+            size,
+            bits: vec![0; size.div_ceil(64)],
+        }
+    }
+
+    /// Add item, returns false if item may already be in the filter.
+    pub fn add(&mut self, item: &str) -> bool {
+        let positions = self.positions(item);
+
+        if positions.iter().all(|position| self.bit(*position)) {
+            return false;
+        }
+
+        for position in positions {
+            self.bits[position / 64] |= 1 << (position % 64);
+        }
+
+        true
+    }
+
+    /// False if item is definitely not in the filter.
+    pub fn contains(&self, item: &str) -> bool {
+        self.positions(item)
+            .into_iter()
+            .all(|position| self.bit(position))
+    }
+
+    fn bit(&self, position: usize) -> bool {
+        self.bits[position / 64] & (1 << (position % 64)) != 0
+    }
+
+    // double hashing, every position is first hash plus a multiple of the second
+    fn positions(&self, item: &str) -> Vec<usize> {
+        let first = hash(item.as_bytes());
+        let second = mix(first ^ 0x9e37_79b9_7f4a_7c15) | 1;
+        let size = self.size as u64;
+
+        (0..self.hashes as u64)
+            .map(|index| (first.wrapping_add(index.wrapping_mul(second)) % size) as usize)
+            .collect()
+    }
+
+    pub fn encode(&self) -> String {
+        let bits: String = (0..self.size)
+            .step_by(6)
+            .map(|start| {
+                let sextet = (start..(start + 6).min(self.size))
+                    .enumerate()
+                    .fold(0, |sextet, (index, position)| {
+                        sextet | (self.bit(position) as usize) << index
+                    });
+
+                ALPHABET[sextet] as char
+            })
+            .collect();
+
+        typed::tag(
+            Type::Bloom,
+            &format!("{}:{}:{bits}", self.hashes, self.size),
+        )
+    }
+
+    pub fn decode(raw: &str) -> Result<Self> {
+        let fail = || err!(embedded, BloomParseFail);
+
+        let mut parts = typed::untag(Type::Bloom, raw)
+            .ok_or_else(fail)?
+            .splitn(3, ':');
+        let mut number = || {
+            parts
+                .next()
+                .and_then(|part| part.parse::<usize>().ok())
+                .ok_or_else(fail)
+        };
+
+        let (hashes, size) = (number()?, number()?);
+        let encoded = parts.next().ok_or_else(fail)?;
+
+        if !(1..=MAX_HASHES).contains(&hashes)
+            || !(1..=MAX_BITS).contains(&size)
+            || encoded.len() != size.div_ceil(6)
+        {
+            return Err(fail());
+        }
+
+        let mut filter = Self::empty(hashes, size);
+
+        for (chunk, character) in encoded.bytes().enumerate() {
+            let sextet = sextet(character).ok_or_else(fail)?;
+
+            for index in (0..6).filter(|index| sextet & (1 << index) != 0) {
+                let position = chunk * 6 + index;
+
+                if position < size {
+                    filter.bits[position / 64] |= 1 << (position % 64);
+                }
+            }
+        }
+
+        Ok(filter)
+    }
+}
+
+fn sextet(character: u8) -> Option<u8> {
+    ALPHABET
+        .iter()
+        .position(|known| *known == character)
+        .map(|position| position as u8)
+}
+
+// 64-bit FNV-1a, mixed so that every bit depends on every input bit
+fn hash(bytes: &[u8]) -> u64 {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+
+    mix(hash)
+}
+
+// splitmix64 finalizer
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
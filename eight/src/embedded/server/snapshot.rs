@@ -0,0 +1,184 @@
+use super::{fulltext::FullText, index::Indexes, mutation::Mutation};
+use crate::{
+    embedded::{messaging::Request, storage::Storage, Error, Result},
+    err,
+};
+use futures::StreamExt;
+use std::collections::HashSet;
+use tokio::sync::{Mutex, RwLock, RwLockReadGuard};
+
+/// Copy-on-write snapshots.
+///
+/// While a snapshot is running, every mutation copies the old value of its keys into snapshot before changing them.
+/// Keys are copied only once, so snapshot ends up with the values from the moment it started.
+/// Index and full-text entries of changed keys are copied too, so a restored snapshot finds what it holds.
+#[derive(Default)]
+pub(super) struct Snapshots {
+    // mutations hold it while running, snapshot takes it only to start
+    barrier: RwLock<()>,
+    // one snapshot at a time
+    running: Mutex<()>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    active: Option<Active>,
+    failure: Option<Error>,
+}
+
+struct Active {
+    target: Box<dyn Storage>,
+    // copied or created after snapshot started
+    done: HashSet<String>,
+    // every key is copied, anything else is created after snapshot started
+    complete: bool,
+    count: u64,
+}
+
+impl Active {
+    async fn preserve(&mut self, storage: &dyn Storage, key: &str) -> Result<()> {
+        if self.complete || !self.done.insert(key.to_string()) {
+            return Ok(());
+        }
+
+        // key may not exist, it is created after snapshot started then
+        if let Ok(value) = storage.get(key.to_string()).await {
+            self.target.set(key.to_string(), value).await?;
+            self.count += 1;
+        }
+
+        Ok(())
+    }
+
+    async fn preserve_all(&mut self, storage: &dyn Storage) -> Result<()> {
+        let mut keys = storage.keys(String::new()).await?;
+
+        while let Some(key) = keys.next().await {
+            self.preserve(storage, &key?).await?;
+        }
+
+        self.complete = true;
+        Ok(())
+    }
+}
+
+impl Snapshots {
+    /// Preserve keys request is going to change, with their index and full-text entries.
+    /// Returned guard must be kept until request is done.
+    pub async fn before(
+        &self,
+        request: &Request,
+        storage: &dyn Storage,
+        indexes: &Indexes,
+        fulltext: &FullText,
+    ) -> Option<RwLockReadGuard<'_, ()>> {
+        let mutation = Mutation::of(request);
+
+        if let Mutation::None = mutation {
+            return None;
+        }
+
+        let guard = self.barrier.read().await;
+        let mut state = self.state.lock().await;
+
+        if let Some(active) = state.active.as_mut() {
+            let result = match mutation {
+                Mutation::Keys(keys) => {
+                    let mut result = Ok(());
+
+                    for key in keys {
+                        let mut entries = indexes.entries(key).await;
+                        entries.extend(fulltext.entries(key).await);
+
+                        result = result.and(active.preserve(storage, key).await);
+
+                        for entry in entries {
+                            result = result.and(active.preserve(storage, &entry).await);
+                        }
+                    }
+
+                    result
+                }
+                // everything is going to change, finish snapshot right now
+                Mutation::All => active.preserve_all(storage).await,
+                Mutation::None => Ok(()),
+            };
+
+            // a broken snapshot must not block writes
+            if let Err(error) = result {
+                state.active = None;
+                state.failure = Some(error);
+            }
+        }
+
+        Some(guard)
+    }
+
+    pub async fn run(&self, storage: &dyn Storage, target: Box<dyn Storage>) -> Result<u64> {
+        let _running = self.running.lock().await;
+
+        {
+            // wait for running mutations, they started before snapshot
+            let _barrier = self.barrier.write().await;
+
+            *self.state.lock().await = State {
+                active: Some(Active {
+                    target,
+                    done: HashSet::new(),
+                    complete: false,
+                    count: 0,
+                }),
+                failure: None,
+            };
+        }
+
+        let result = self.copy(storage).await;
+        let mut state = self.state.lock().await;
+
+        match (state.active.take(), state.failure.take(), result) {
+            (_, Some(error), _) | (_, _, Err(error)) => Err(error),
+            (Some(active), None, Ok(_)) => Ok(active.count),
+            (None, None, Ok(_)) => Err(err!(embedded, SnapshotFail)),
+        }
+    }
+
+    async fn copy(&self, storage: &dyn Storage) -> Result<()> {
+        let mut keys = storage.keys(String::new()).await?;
+
+        while let Some(key) = keys.next().await {
+            let key = key?;
+            let mut state = self.state.lock().await;
+
+            let Some(active) = state.active.as_mut() else {
+                break;
+            };
+
+            active.preserve(storage, &key).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "filesystem-storage")]
+pub(super) async fn remove_old(directory: &std::path::Path, prefix: &str, keep: usize) {
+    let Ok(mut entries) = tokio::fs::read_dir(directory).await else {
+        return;
+    };
+
+    let mut snapshots = Vec::new();
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if entry.file_name().to_string_lossy().starts_with(prefix) {
+            snapshots.push(entry.path());
+        }
+    }
+
+    // names has a fixed width timestamp, newest is the last one
+    snapshots.sort();
+
+    for path in snapshots.iter().rev().skip(keep) {
+        tokio::fs::remove_dir_all(path).await.ok();
+    }
+}
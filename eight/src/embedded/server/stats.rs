@@ -0,0 +1,29 @@
+use crate::embedded::storage::metrics::{Metrics, Summary};
+
+/// Server statistics, returned by [`Server::stats`].
+///
+/// Request latency covers waiting in queue, task scheduling and storage together.
+/// Comparing it with storage latency tells where the time goes.
+///
+/// [`Server::stats`]: super::Server::stats
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Requests handled by server, from [`Server::cast`] until response is ready. Requests responded with an error are counted as errors.
+    ///
+    /// [`Server::cast`]: super::Server::cast
+    pub requests: Summary,
+    /// Requests waiting in queue to be picked up by server.
+    pub queued: usize,
+    /// Requests running at the moment, blocking requests are not counted. See [`Limits::in_flight`].
+    ///
+    /// [`Limits::in_flight`]: super::Limits::in_flight
+    pub in_flight: usize,
+    /// Blocking requests waiting at the moment. See [`Limits::blocking`].
+    ///
+    /// [`Limits::blocking`]: super::Limits::blocking
+    pub blocking: usize,
+    /// Metrics recorded by storage, if it records any. See [`Storage::metrics`].
+    ///
+    /// [`Storage::metrics`]: crate::embedded::storage::Storage::metrics
+    pub storage: Option<Metrics>,
+}
@@ -0,0 +1,408 @@
+//! Append-only streams with consumer groups, kept as a single value like lists.
+//!
+//! Every change reads and writes the whole value, so streams should be kept short with `MAXLEN` or `XTRIM`.
+//!
+//! Value is a list tagged as `stream`, of last ID, groups and entries. Entries are lists of ID and field value pairs,
+//! groups are lists of name, last delivered ID, redelivery timeout and pending entries.
+
+use super::{
+    list,
+    typed::{self, Type},
+};
+use crate::{embedded::Result, err};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    str::FromStr,
+    time::Duration,
+};
+
+/// Entry ID, unix milliseconds and a sequence for entries added in the same millisecond.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct Id {
+    time: u64,
+    sequence: u64,
+}
+
+impl Id {
+    const MAX: Id = Id {
+        time: u64::MAX,
+        sequence: u64::MAX,
+    };
+
+    /// Parse ID, a missing sequence is filled with given one.
+    fn parse(raw: &str, sequence: u64) -> Result<Self> {
+        let (time, sequence) = match raw.split_once('-') {
+            Some((time, sequence)) => (time, sequence.parse()),
+            None => (raw, Ok(sequence)),
+        };
+
+        match (time.parse(), sequence) {
+            (Ok(time), Ok(sequence)) => Ok(Self { time, sequence }),
+            _ => Err(err!(embedded, InvalidStreamId)),
+        }
+    }
+}
+
+impl FromStr for Id {
+    type Err = crate::embedded::Error;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        Id::parse(raw, 0)
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.time, self.sequence)
+    }
+}
+
+pub(super) type Fields = Vec<(String, String)>;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pending {
+    consumer: String,
+    delivered: u64,
+    count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Group {
+    last: Id,
+    timeout: Duration,
+    pending: BTreeMap<Id, Pending>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(super) struct Stream {
+    last: Id,
+    entries: BTreeMap<Id, Fields>,
+    groups: BTreeMap<String, Group>,
+}
+
+impl Stream {
+    /// Append entry with a generated ID, IDs keep growing even if clock goes back.
+    pub fn add(&mut self, fields: Fields, now: u64) -> Id {
+        let id = if now > self.last.time {
+            Id {
+                time: now,
+                sequence: 0,
+            }
+        } else {
+            Id {
+                time: self.last.time,
+                sequence: self.last.sequence + 1,
+            }
+        };
+
+        self.last = id;
+        self.entries.insert(id, fields);
+
+        id
+    }
+
+    /// Remove oldest entries until at most given count is left, with their pending deliveries. Returns removed entry count.
+    pub fn trim(&mut self, max: usize) -> usize {
+        let count = self.entries.len().saturating_sub(max);
+
+        if count == 0 {
+            return 0;
+        }
+
+        self.entries = match self.entries.keys().nth(count).copied() {
+            Some(first) => self.entries.split_off(&first),
+            None => BTreeMap::new(),
+        };
+
+        for group in self.groups.values_mut() {
+            group.pending.retain(|id, _| self.entries.contains_key(id));
+        }
+
+        count
+    }
+
+    pub fn last(&self) -> Id {
+        self.last
+    }
+
+    /// Entries between start and end, both inclusive. `-` and `+` are the first and last IDs.
+    pub fn range(&self, start: &str, end: &str, count: usize) -> Result<Vec<(Id, &Fields)>> {
+        let start = match start {
+            "-" => Id::default(),
+            start => Id::parse(start, 0)?,
+        };
+        let end = match end {
+            "+" => Id::MAX,
+            end => Id::parse(end, u64::MAX)?,
+        };
+
+        if start > end {
+            return Ok(vec![]);
+        }
+
+        Ok(take(self.entries.range(start..=end), count))
+    }
+
+    /// Entries after ID, `$` is the last ID.
+    pub fn read(&self, after: &str, count: usize) -> Result<Vec<(Id, &Fields)>> {
+        let after = self.position(after)?;
+        let entries = self.entries.range(after..).filter(|(id, _)| **id > after);
+
+        Ok(take(entries, count))
+    }
+
+    pub fn create_group(&mut self, name: String, start: &str, timeout: Duration) -> Result<()> {
+        if self.groups.contains_key(&name) {
+            return Err(err!(embedded, GroupExists));
+        }
+
+        let group = Group {
+            last: self.position(start)?,
+            timeout,
+            pending: BTreeMap::new(),
+        };
+
+        self.groups.insert(name, group);
+
+        Ok(())
+    }
+
+    pub fn destroy_group(&mut self, name: &str) -> Result<()> {
+        self.groups
+            .remove(name)
+            .map(|_| ())
+            .ok_or(err!(embedded, GroupNotFound))
+    }
+
+    /// Deliver entries to consumer. Pending entries not acknowledged within timeout come first, then new entries.
+    pub fn read_group(
+        &mut self,
+        name: &str,
+        consumer: &str,
+        count: usize,
+        now: u64,
+    ) -> Result<Vec<(Id, Fields)>> {
+        let group = self
+            .groups
+            .get_mut(name)
+            .ok_or(err!(embedded, GroupNotFound))?;
+        let limit = limit(count);
+        let timeout = group.timeout.as_millis() as u64;
+
+        let mut delivered: Vec<Id> = group
+            .pending
+            .iter()
+            .filter(|(_, pending)| now.saturating_sub(pending.delivered) >= timeout)
+            .map(|(id, _)| *id)
+            .take(limit)
+            .collect();
+
+        for id in &delivered {
+            if let Some(pending) = group.pending.get_mut(id) {
+                pending.consumer = consumer.to_string();
+                pending.delivered = now;
+                pending.count += 1;
+            }
+        }
+
+        let fresh: Vec<Id> = self
+            .entries
+            .range(group.last..)
+            .map(|(id, _)| *id)
+            .filter(|id| id > &group.last)
+            .take(limit - delivered.len())
+            .collect();
+
+        for id in fresh {
+            group.last = id;
+            group.pending.insert(
+                id,
+                Pending {
+                    consumer: consumer.to_string(),
+                    delivered: now,
+                    count: 1,
+                },
+            );
+            delivered.push(id);
+        }
+
+        Ok(delivered
+            .into_iter()
+            .filter_map(|id| Some((id, self.entries.get(&id)?.clone())))
+            .collect())
+    }
+
+    /// Acknowledge entries for group, returns acknowledged entry count.
+    pub fn ack(&mut self, name: &str, ids: &[String]) -> Result<usize> {
+        let group = self
+            .groups
+            .get_mut(name)
+            .ok_or(err!(embedded, GroupNotFound))?;
+
+        let mut count = 0;
+
+        for id in ids {
+            if group.pending.remove(&id.parse()?).is_some() {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    fn position(&self, id: &str) -> Result<Id> {
+        match id {
+            "$" => Ok(self.last),
+            id => id.parse(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let groups: VecDeque<String> = self
+            .groups
+            .iter()
+            .map(|(name, group)| {
+                let mut items = VecDeque::from([
+                    name.clone(),
+                    group.last.to_string(),
+                    group.timeout.as_millis().to_string(),
+                ]);
+
+                items.extend(group.pending.iter().map(|(id, pending)| {
+                    list::encode(&VecDeque::from([
+                        id.to_string(),
+                        pending.delivered.to_string(),
+                        pending.count.to_string(),
+                        pending.consumer.clone(),
+                    ]))
+                }));
+
+                list::encode(&items)
+            })
+            .collect();
+
+        let mut items = VecDeque::from([self.last.to_string(), list::encode(&groups)]);
+
+        items.extend(self.entries.iter().map(|(id, fields)| {
+            let mut items = VecDeque::from([id.to_string()]);
+
+            for (field, value) in fields {
+                items.push_back(field.clone());
+                items.push_back(value.clone());
+            }
+
+            list::encode(&items)
+        }));
+
+        typed::tag(Type::Stream, &list::encode(&items))
+    }
+
+    pub fn decode(raw: &str) -> Result<Self> {
+        let fail = |_| err!(embedded, StreamParseFail);
+
+        let raw = typed::untag(Type::Stream, raw).ok_or(err!(embedded, StreamParseFail))?;
+        let mut items = list::decode(raw).map_err(fail)?;
+
+        let last = items
+            .pop_front()
+            .ok_or(err!(embedded, StreamParseFail))?
+            .parse()
+            .map_err(fail)?;
+        let raw_groups = items.pop_front().ok_or(err!(embedded, StreamParseFail))?;
+
+        let mut groups = BTreeMap::new();
+
+        for group in list::decode(&raw_groups).map_err(fail)? {
+            let mut items = list::decode(&group).map_err(fail)?;
+
+            let (Some(name), Some(last), Some(timeout)) =
+                (items.pop_front(), items.pop_front(), items.pop_front())
+            else {
+                return Err(err!(embedded, StreamParseFail));
+            };
+
+            let mut pending = BTreeMap::new();
+
+            for item in items {
+                let item = list::decode(&item).map_err(fail)?;
+
+                let [id, delivered, count, consumer] = Vec::from(item)
+                    .try_into()
+                    .map_err(|_| err!(embedded, StreamParseFail))?;
+
+                pending.insert(
+                    id.parse().map_err(fail)?,
+                    Pending {
+                        consumer,
+                        delivered: delivered
+                            .parse()
+                            .map_err(|_| err!(embedded, StreamParseFail))?,
+                        count: count.parse().map_err(|_| err!(embedded, StreamParseFail))?,
+                    },
+                );
+            }
+
+            groups.insert(
+                name,
+                Group {
+                    last: last.parse().map_err(fail)?,
+                    timeout: Duration::from_millis(
+                        timeout
+                            .parse()
+                            .map_err(|_| err!(embedded, StreamParseFail))?,
+                    ),
+                    pending,
+                },
+            );
+        }
+
+        let mut entries = BTreeMap::new();
+
+        for entry in items {
+            let mut items = list::decode(&entry).map_err(fail)?;
+
+            let id = items
+                .pop_front()
+                .ok_or(err!(embedded, StreamParseFail))?
+                .parse()
+                .map_err(fail)?;
+
+            if items.len() % 2 != 0 {
+                return Err(err!(embedded, StreamParseFail));
+            }
+
+            let fields = Vec::from(items)
+                .chunks(2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect();
+
+            entries.insert(id, fields);
+        }
+
+        Ok(Self {
+            last,
+            entries,
+            groups,
+        })
+    }
+}
+
+// zero (0) count means every entry
+fn limit(count: usize) -> usize {
+    if count == 0 {
+        usize::MAX
+    } else {
+        count
+    }
+}
+
+fn take<'a>(
+    entries: impl Iterator<Item = (&'a Id, &'a Fields)>,
+    count: usize,
+) -> Vec<(Id, &'a Fields)> {
+    entries
+        .take(limit(count))
+        .map(|(id, fields)| (*id, fields))
+        .collect()
+}
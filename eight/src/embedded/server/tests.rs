@@ -0,0 +1,1936 @@
+use super::{
+    list, ratelimit,
+    sketch::{Bloom, HyperLogLog},
+    timeseries::Series,
+    typed::{self, Type},
+    Limits, Overflow, Server,
+};
+use crate::embedded::{
+    messaging::{Aggregation, KeyEvent, RateLimiter, Request, Response},
+    storage::memory,
+    Error,
+};
+use futures::StreamExt;
+use std::{collections::VecDeque, time::Duration};
+use tokio::time;
+
+fn server() -> Server {
+    let server = Server::new(memory::Storage::new());
+    tokio::spawn({
+        let server = server.clone();
+        async move { server.listen().await }
+    });
+
+    server
+}
+
+#[test]
+fn list_encoding() {
+    let list = VecDeque::from(["".to_string(), "a:b".to_string(), "çay".to_string()]);
+    let raw = list::encode(&list);
+
+    assert_eq!(raw, "0:3:a:b4:çay");
+    assert_eq!(list::decode(&raw).unwrap(), list);
+
+    for raw in ["hello", "3:ab", "1:ç", "x:abc"] {
+        assert_eq!(list::decode(raw), Err(Error::ListParseFail));
+    }
+}
+
+#[test]
+fn fixed_window_rate_limit() {
+    let window = Duration::from_secs(1);
+    let decide = |state: Option<&str>, now| {
+        ratelimit::decide(RateLimiter::FixedWindow, state, 2, window, now).unwrap()
+    };
+
+    let (first, state) = decide(None, 1000);
+    assert!(first.allowed);
+    assert_eq!(first.remaining, 1);
+
+    let (_, state) = decide(Some(&state), 1100);
+    let (denied, state) = decide(Some(&state), 1400);
+    assert!(!denied.allowed);
+    assert_eq!(denied.remaining, 0);
+    assert_eq!(denied.retry_after, Duration::from_millis(600));
+
+    let (next, _) = decide(Some(&state), 2000);
+    assert!(next.allowed);
+    assert_eq!(next.remaining, 1);
+}
+
+#[test]
+fn sliding_window_rate_limit() {
+    let window = Duration::from_secs(1);
+    let decide = |state: Option<&str>, now| {
+        ratelimit::decide(RateLimiter::SlidingWindow, state, 4, window, now).unwrap()
+    };
+
+    let mut state = None;
+
+    for now in [1000, 1100, 1200, 1300] {
+        let (decision, next) = decide(state.as_deref(), now);
+        assert!(decision.allowed);
+        state = Some(next);
+    }
+
+    // previous window still weighs three (3) requests at the start of next window
+    let (decision, next) = decide(state.as_deref(), 2250);
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 0);
+
+    let (denied, _) = decide(Some(&next), 2300);
+    assert!(!denied.allowed);
+    assert!(denied.retry_after > Duration::ZERO);
+
+    let (later, _) = decide(Some(&next), 2300 + denied.retry_after.as_millis() as u64);
+    assert!(later.allowed);
+}
+
+#[test]
+fn token_bucket_rate_limit() {
+    let window = Duration::from_secs(1);
+    let decide = |state: Option<&str>, now| {
+        ratelimit::decide(RateLimiter::TokenBucket, state, 2, window, now).unwrap()
+    };
+
+    let (_, state) = decide(None, 0);
+    let (_, state) = decide(Some(&state), 0);
+    let (denied, state) = decide(Some(&state), 100);
+    assert!(!denied.allowed);
+    assert_eq!(denied.retry_after, Duration::from_millis(400));
+
+    let (refilled, _) = decide(Some(&state), 500);
+    assert!(refilled.allowed);
+    assert_eq!(refilled.remaining, 0);
+
+    assert_eq!(
+        ratelimit::decide(RateLimiter::TokenBucket, Some("hello"), 2, window, 0),
+        Err(Error::RateLimitParseFail)
+    );
+}
+
+#[test]
+fn rate_limit_huge_window() {
+    for algorithm in [
+        RateLimiter::FixedWindow,
+        RateLimiter::SlidingWindow,
+        RateLimiter::TokenBucket,
+    ] {
+        for window in [Duration::from_millis(u64::MAX), Duration::MAX] {
+            let (first, state) = ratelimit::decide(algorithm, None, 1, window, 1000).unwrap();
+            assert!(first.allowed);
+
+            let (second, _) = ratelimit::decide(algorithm, Some(&state), 1, window, 2000).unwrap();
+            assert!(!second.allowed);
+            assert!(second.retry_after > Duration::from_secs(3600));
+        }
+    }
+}
+
+#[tokio::test]
+async fn rate_limit_command() {
+    let server = server();
+
+    let results = server
+        .query(
+            "ratelimit api 2 60000; ratelimit api 2 60000; ratelimit api 2 60000; set plain hello; ratelimit plain 2 60000;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let decision = |allowed, remaining| {
+        Response::Map(
+            [
+                ("allowed".to_string(), Response::Boolean(allowed)),
+                ("remaining".to_string(), Response::Number(remaining)),
+            ]
+            .into(),
+        )
+    };
+
+    let Response::Map(denied) = &results[2] else {
+        panic!("rate limit result is not a map: {:?}", results[2]);
+    };
+
+    let retry_after = match denied.get("retry_after") {
+        Some(Response::Number(retry_after)) => *retry_after,
+        other => panic!("retry after is not a number: {other:?}"),
+    };
+
+    assert!(retry_after > 0 && retry_after <= 60000);
+
+    for (result, expected) in results[..2]
+        .iter()
+        .zip([decision(true, 1), decision(true, 0)])
+    {
+        let Response::Map(mut map) = result.clone() else {
+            panic!("rate limit result is not a map: {result:?}");
+        };
+
+        assert_eq!(map.remove("retry_after"), Some(Response::Number(0)));
+        assert_eq!(Response::Map(map), expected);
+    }
+
+    assert_eq!(results[4], Response::Error(Error::RateLimitParseFail));
+
+    // concurrent requests never exceed limit
+    let tasks: Vec<_> = (0..20)
+        .map(|_| {
+            let server = server.clone();
+            tokio::spawn(async move {
+                server
+                    .call(Request::RateLimit(
+                        "burst".into(),
+                        5,
+                        Duration::from_secs(60),
+                        RateLimiter::TokenBucket,
+                    ))
+                    .await
+                    .unwrap()
+            })
+        })
+        .collect();
+
+    let mut allowed = 0;
+
+    for task in tasks {
+        if let Response::Map(map) = task.await.unwrap() {
+            if map.get("allowed") == Some(&Response::Boolean(true)) {
+                allowed += 1;
+            }
+        }
+    }
+
+    assert_eq!(allowed, 5);
+}
+
+#[tokio::test]
+async fn lease_locks() {
+    let server = server();
+
+    let results = server
+        .query(
+            r#"
+            lock cron machine-1 60000;
+            lock cron machine-2 60000;
+            renew cron machine-1 60000;
+            renew cron machine-2 60000;
+            unlock cron machine-2;
+            lock cron machine-1 60000;
+            unlock cron machine-1;
+            unlock cron machine-1;
+            lock cron machine-2 60000;
+            set plain hello;
+            lock plain machine-1 60000;
+            "#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results,
+        vec![
+            Response::Number(1),
+            Response::Error(Error::LockHeld),
+            Response::Number(1),
+            Response::Error(Error::LockNotHeld),
+            Response::Error(Error::LockNotHeld),
+            Response::Number(1),
+            Response::Ok,
+            Response::Error(Error::LockNotHeld),
+            Response::Number(2),
+            Response::Ok,
+            Response::Error(Error::LockParseFail),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn lease_lock_expires() {
+    let server = server();
+    let lock = |owner: &str| Request::Lock("job".into(), owner.into(), Duration::from_millis(20));
+
+    assert_eq!(
+        server.call(lock("machine-1")).await.unwrap(),
+        Response::Number(1)
+    );
+
+    time::sleep(Duration::from_millis(30)).await;
+
+    // holder disappeared, lease is over
+    assert_eq!(
+        server
+            .call(Request::Renew(
+                "job".into(),
+                "machine-1".into(),
+                Duration::from_millis(20)
+            ))
+            .await
+            .unwrap(),
+        Response::Error(Error::LockNotHeld)
+    );
+    assert_eq!(
+        server.call(lock("machine-2")).await.unwrap(),
+        Response::Number(2)
+    );
+
+    // only one of concurrent owners gets the lock
+    let tasks: Vec<_> = (0..10)
+        .map(|index| {
+            let server = server.clone();
+            tokio::spawn(async move {
+                server
+                    .call(Request::Lock(
+                        "race".into(),
+                        format!("machine-{index}"),
+                        Duration::from_secs(60),
+                    ))
+                    .await
+                    .unwrap()
+            })
+        })
+        .collect();
+
+    let mut winners = 0;
+
+    for task in tasks {
+        if task.await.unwrap() == Response::Number(1) {
+            winners += 1;
+        }
+    }
+
+    assert_eq!(winners, 1);
+}
+
+#[tokio::test]
+async fn lease_lock_never_overflows() {
+    let server = server();
+
+    let results = server
+        .query(
+            "lock job a 18446744073709551615; renew job a 18446744073709551615; lock job b 10;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results,
+        vec![
+            Response::Number(1),
+            Response::Number(1),
+            Response::Error(Error::LockHeld),
+        ]
+    );
+
+    let response = server
+        .call(Request::Lock("other".into(), "a".into(), Duration::MAX))
+        .await
+        .unwrap();
+
+    assert_eq!(response, Response::Number(1));
+}
+
+#[tokio::test]
+async fn list_commands() {
+    let server = server();
+
+    let results = server
+        .query(
+            "rpush jobs b; lpush jobs a; rpush jobs c; lpop jobs; lpop jobs; lpop jobs; exists jobs; lpop jobs;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results,
+        vec![
+            Response::Number(1),
+            Response::Number(2),
+            Response::Number(3),
+            Response::Text("a".into()),
+            Response::Text("b".into()),
+            Response::Text("c".into()),
+            Response::Boolean(false),
+            Response::Error(Error::ListEmpty),
+        ]
+    );
+
+    server
+        .call(Request::Set("plain".into(), "text".into()))
+        .await
+        .unwrap();
+
+    let response = server.call(Request::LeftPop("plain".into())).await.unwrap();
+    assert_eq!(response, Response::Error(Error::ListParseFail));
+
+    // strings looking like a list encoding are still strings
+    let results = server
+        .query(
+            "set encoded 3:abc; lpop encoded; rpush encoded d;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results,
+        vec![
+            Response::Ok,
+            Response::Error(Error::ListParseFail),
+            Response::Error(Error::ListParseFail),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn concurrent_pushes_keep_every_value() {
+    let server = server();
+
+    let pushes =
+        (0..50).map(|index| server.call(Request::RightPush("jobs".into(), index.to_string())));
+    let pushes = futures::future::join_all(pushes).await;
+
+    assert!(pushes
+        .into_iter()
+        .all(|push| matches!(push, Ok(Response::Number(_)))));
+
+    let mut values = Vec::new();
+
+    while let Ok(Response::Text(value)) = server.call(Request::LeftPop("jobs".into())).await {
+        values.push(value.parse::<usize>().unwrap());
+    }
+
+    values.sort_unstable();
+
+    assert_eq!(values, (0..50).collect::<Vec<_>>());
+    assert_eq!(
+        server.call(Request::Exists("jobs".into())).await,
+        Ok(Response::Boolean(false))
+    );
+}
+
+#[tokio::test]
+async fn full_queue_rejects_requests() {
+    let limits = Limits {
+        queue: 1,
+        in_flight: Some(1),
+        overflow: Overflow::Reject,
+        ..Default::default()
+    };
+    let server = Server::with_limits(memory::Storage::new(), limits);
+
+    // nothing is picked up until server listens
+    let receiver = server.cast(Request::Exists("key".into())).await.unwrap();
+
+    assert_eq!(
+        server.cast(Request::Exists("key".into())).await.err(),
+        Some(Error::Overloaded)
+    );
+    assert_eq!(server.stats().queued, 1);
+
+    server.start().await;
+
+    assert_eq!(receiver.await.unwrap(), Response::Boolean(false));
+    assert_eq!(server.stats().queued, 0);
+}
+
+#[tokio::test]
+async fn full_queue_waits_for_room() {
+    let limits = Limits {
+        queue: 1,
+        ..Default::default()
+    };
+    let server = Server::with_limits(memory::Storage::new(), limits);
+
+    let first = server.cast(Request::Exists("key".into())).await.unwrap();
+    let second = time::timeout(
+        Duration::from_millis(20),
+        server.cast(Request::Exists("key".into())),
+    )
+    .await;
+
+    assert!(second.is_err());
+
+    server.start().await;
+
+    let response = server.call(Request::Exists("key".into())).await.unwrap();
+
+    assert_eq!(response, Response::Boolean(false));
+    assert_eq!(first.await.unwrap(), Response::Boolean(false));
+}
+
+#[tokio::test]
+async fn blocking_requests_are_not_in_flight() {
+    let limits = Limits {
+        in_flight: Some(1),
+        ..Default::default()
+    };
+    let server = Server::with_limits(memory::Storage::new(), limits);
+    server.start().await;
+
+    let receiver = server
+        .cast(Request::BlockingLeftPop("jobs".into(), Duration::ZERO))
+        .await
+        .unwrap();
+
+    // push is not stuck behind the pop waiting for it
+    time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(server.stats().in_flight, 0);
+    assert_eq!(server.stats().blocking, 1);
+
+    server
+        .call(Request::RightPush("jobs".into(), "first".into()))
+        .await
+        .unwrap();
+
+    assert_eq!(receiver.await.unwrap(), Response::Text("first".into()));
+}
+
+#[tokio::test]
+async fn blocking_requests_over_limit_are_rejected() {
+    let limits = Limits {
+        blocking: Some(1),
+        ..Default::default()
+    };
+    let server = Server::with_limits(memory::Storage::new(), limits);
+    server.start().await;
+
+    let receiver = server
+        .cast(Request::BlockingLeftPop("jobs".into(), Duration::ZERO))
+        .await
+        .unwrap();
+
+    time::sleep(Duration::from_millis(20)).await;
+
+    let response = server
+        .call(Request::Wait("result".into(), Duration::ZERO))
+        .await
+        .unwrap();
+
+    assert_eq!(response, Response::Error(Error::Overloaded));
+
+    // other requests still run, and the limit frees up once blocking request is done
+    server
+        .call(Request::RightPush("jobs".into(), "first".into()))
+        .await
+        .unwrap();
+
+    assert_eq!(receiver.await.unwrap(), Response::Text("first".into()));
+
+    time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(server.stats().blocking, 0);
+}
+
+#[tokio::test]
+async fn blocking_pop_waits_for_push() {
+    let server = server();
+
+    let receiver = server
+        .cast(Request::BlockingLeftPop("jobs".into(), Duration::ZERO))
+        .await
+        .unwrap();
+
+    // other requests are not blocked meanwhile
+    time::sleep(Duration::from_millis(20)).await;
+    let response = server.call(Request::Exists("jobs".into())).await.unwrap();
+    assert_eq!(response, Response::Boolean(false));
+
+    server
+        .call(Request::RightPush("jobs".into(), "first".into()))
+        .await
+        .unwrap();
+
+    assert_eq!(receiver.await.unwrap(), Response::Text("first".into()));
+    assert_eq!(
+        server.call(Request::Exists("jobs".into())).await.unwrap(),
+        Response::Boolean(false)
+    );
+}
+
+#[tokio::test]
+async fn blocking_pop_timeout() {
+    let server = server();
+
+    let response = server
+        .call(Request::BlockingLeftPop(
+            "jobs".into(),
+            Duration::from_millis(20),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response, Response::Error(Error::WaitTimeout));
+}
+
+#[tokio::test]
+async fn abandoned_blocking_pop_keeps_value() {
+    let server = server();
+
+    let result = server
+        .call_in(
+            Request::BlockingLeftPop("jobs".into(), Duration::ZERO),
+            Duration::from_millis(20),
+        )
+        .await;
+    assert_eq!(result, Err(Error::RecvTimeout));
+
+    time::sleep(Duration::from_millis(20)).await;
+
+    server
+        .call(Request::RightPush("jobs".into(), "job".into()))
+        .await
+        .unwrap();
+
+    time::sleep(Duration::from_millis(20)).await;
+
+    let response = server.call(Request::LeftPop("jobs".into())).await.unwrap();
+    assert_eq!(response, Response::Text("job".into()));
+}
+
+#[tokio::test]
+async fn wait_for_key() {
+    let server = server();
+
+    let receiver = server
+        .cast(Request::Wait("result".into(), Duration::ZERO))
+        .await
+        .unwrap();
+
+    time::sleep(Duration::from_millis(20)).await;
+
+    server
+        .call(Request::Set("result".into(), "done".into()))
+        .await
+        .unwrap();
+
+    assert_eq!(receiver.await.unwrap(), Response::Ok);
+
+    // key created before waiting is not missed
+    let response = server
+        .call(Request::Wait("result".into(), Duration::from_millis(20)))
+        .await
+        .unwrap();
+
+    assert_eq!(response, Response::Ok);
+
+    let response = server
+        .call(Request::Wait("missing".into(), Duration::from_millis(20)))
+        .await
+        .unwrap();
+
+    assert_eq!(response, Response::Error(Error::WaitTimeout));
+}
+
+#[tokio::test]
+async fn lagged_events_are_reported_in_place() {
+    let server = server();
+    let mut events = Box::pin(server.watch_with_capacity("", 2));
+
+    for i in 0..5 {
+        server
+            .call(Request::Set(format!("key{i}"), "1".into()))
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(events.next().await, Some(KeyEvent::Set("key0".into())));
+
+    server
+        .call(Request::Set("key5".into(), "1".into()))
+        .await
+        .unwrap();
+
+    let expected = [
+        KeyEvent::Set("key1".into()),
+        KeyEvent::Lagged(3),
+        KeyEvent::Set("key5".into()),
+    ];
+
+    for event in expected {
+        assert_eq!(events.next().await, Some(event));
+    }
+}
+
+#[tokio::test]
+async fn rename_and_copy() {
+    let server = server();
+    let mut events = Box::pin(server.watch(""));
+
+    let results = server
+        .query(
+            "set a 1; set b 2; renamenx a b; copy a b; rename a c; copy c a; get a; exists b;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results,
+        vec![
+            Response::Ok,
+            Response::Ok,
+            Response::Boolean(false),
+            Response::Boolean(false),
+            Response::Ok,
+            Response::Boolean(true),
+            Response::Text("1".into()),
+            Response::Boolean(true),
+        ]
+    );
+
+    // conditional requests that changed nothing send no event
+    let expected = [
+        KeyEvent::Set("a".into()),
+        KeyEvent::Set("b".into()),
+        KeyEvent::Delete("a".into()),
+        KeyEvent::Set("c".into()),
+        KeyEvent::Set("a".into()),
+    ];
+
+    for event in expected {
+        assert_eq!(events.next().await, Some(event));
+    }
+}
+
+#[tokio::test]
+async fn string_commands() {
+    let server = server();
+
+    let results = server
+        .query(
+            "setnx a 1; set a 2 NX; set b 1 XX; getset a hello; append a world; strlen a; getrange a 0 4; getrange a -5 -1; getdel a; exists a; getset a 1;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results,
+        vec![
+            Response::Boolean(true),
+            Response::Boolean(false),
+            Response::Boolean(false),
+            Response::Text("1".into()),
+            Response::Number(10),
+            Response::Number(10),
+            Response::Text("hello".into()),
+            Response::Text("world".into()),
+            Response::Text("helloworld".into()),
+            Response::Boolean(false),
+            Response::Ok,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn metadata() {
+    let server = server();
+
+    let results = server
+        .query(
+            "set a 42; set b hello; rpush c hello; meta a; meta b; meta c; meta d; set e 5:hello; meta e; xadd f field value; meta f;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let Response::Map(map) = &results[3] else {
+        panic!("metadata is not a map: {:?}", results[3]);
+    };
+
+    assert_eq!(map["size"], Response::Number(2));
+    assert_eq!(map["type"], Response::Text("string".into()));
+    assert_eq!(map["created"], map["updated"]);
+    assert!(!map.contains_key("ttl"));
+
+    for (result, kind) in results[4..6].iter().zip(["string", "list"]) {
+        let Response::Map(map) = result else {
+            panic!("metadata is not a map: {result:?}");
+        };
+
+        assert_eq!(map["type"], Response::Text(kind.into()));
+    }
+
+    assert_eq!(results[6], Response::Error(Error::GetKeyFail));
+
+    // only tagged values have a type, an untagged list encoding is a string
+    for (result, kind) in [(&results[8], "string"), (&results[10], "stream")] {
+        let Response::Map(map) = result else {
+            panic!("metadata is not a map: {result:?}");
+        };
+
+        assert_eq!(map["type"], Response::Text(kind.into()));
+    }
+}
+
+#[cfg(feature = "history-storage")]
+#[tokio::test]
+async fn history() {
+    use crate::embedded::storage::history::{self, Retention};
+
+    let storage =
+        history::Storage::new(memory::Storage::new()).track("config:", Retention::Versions(5));
+    let server = Server::new(storage);
+    tokio::spawn({
+        let server = server.clone();
+        async move { server.listen().await }
+    });
+
+    server
+        .call(Request::Set("config:mode".into(), "fast".into()))
+        .await
+        .unwrap();
+
+    // versions are kept in milliseconds
+    time::sleep(Duration::from_millis(2)).await;
+
+    let results = server
+        .query(
+            "delete config:mode; history config:mode; history other;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let Response::List(versions) = &results[1] else {
+        panic!("history is not a list: {:?}", results[1]);
+    };
+
+    let values = versions
+        .iter()
+        .map(|version| match version {
+            Response::Map(map) => map.get("value").cloned(),
+            _ => panic!("version is not a map: {version:?}"),
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(values, vec![Some(Response::Text("fast".into())), None]);
+    assert_eq!(results[2], Response::Error(Error::HistoryDisabled));
+
+    let Response::Map(first) = &versions[0] else {
+        unreachable!()
+    };
+
+    let Response::Number(time) = first["time"] else {
+        panic!("time is not a number: {:?}", first["time"]);
+    };
+
+    let response = server
+        .call(Request::GetAt(
+            "config:mode".into(),
+            std::time::UNIX_EPOCH + Duration::from_millis(time as u64),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response, Response::Text("fast".into()));
+}
+
+#[tokio::test]
+async fn indexes() {
+    let server = server();
+
+    let results = server
+        .query(
+            r#"
+            set user:1 "{\"email\": \"a@eight\", \"age\": 30}";
+            set user:2 "{\"email\": \"b@eight\", \"age\": 30}";
+            set admin:1 "{\"email\": \"a@eight\"}";
+            create index by_email on user: field $.email;
+            create index by_age on user: field $.age;
+            set user:3 "{\"email\": \"a@eight\"}";
+            set user:2 "{\"email\": \"a@eight\", \"age\": 31}";
+            delete user:1;
+            find by_email a@eight;
+            find by_age 30;
+            rename user:2 user:4;
+            find by_email a@eight;
+            set user:3 "not json";
+            find by_email a@eight;
+            search user;
+            create index by_email on user: field $.email;
+            drop index by_age;
+            find by_age 31;
+            "#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(results[3], Response::Number(2));
+    assert_eq!(results[4], Response::Number(2));
+    assert_eq!(
+        results[8],
+        Response::TextList(vec!["user:2".into(), "user:3".into()])
+    );
+    assert_eq!(results[9], Response::TextList(vec![]));
+    assert_eq!(
+        results[11],
+        Response::TextList(vec!["user:3".into(), "user:4".into()])
+    );
+    assert_eq!(results[13], Response::TextList(vec!["user:4".into()]));
+
+    let Response::TextList(mut keys) = results[14].clone() else {
+        panic!("search result is not a list: {:?}", results[14]);
+    };
+
+    keys.sort();
+    assert_eq!(keys, vec!["user:3".to_string(), "user:4".to_string()]);
+
+    assert_eq!(results[15], Response::Error(Error::IndexExists));
+    assert_eq!(results[16], Response::Ok);
+    assert_eq!(results[17], Response::Error(Error::IndexNotFound));
+}
+
+#[tokio::test]
+async fn reserved_keys_are_not_writable() {
+    let server = server();
+
+    let results = server
+        .query(
+            r#"
+            create index by_email on user: field $.email;
+            set user:1 "{\"email\": \"a@eight\"}";
+            set __index__:by_email:user:2 a@eight;
+            delete __index__:by_email:user:1;
+            rename user:1 __fulltext__;
+            set __fulltextual 1;
+            search __fulltext;
+            find by_email a@eight;
+            "#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results[2..],
+        [
+            Response::Error(Error::ReservedKey),
+            Response::Error(Error::ReservedKey),
+            Response::Error(Error::ReservedKey),
+            Response::Ok,
+            Response::TextList(vec!["__fulltextual".into()]),
+            Response::TextList(vec!["user:1".into()]),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn restore_goes_through_server() {
+    let source = server();
+
+    source
+        .query(
+            r#"
+            set user:1 "{\"email\": \"a@eight\"}";
+            set note:1 "printer is broken";
+            create index by_email on user: field $.email;
+            ftindex note:;
+            "#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let mut dump = Vec::new();
+    source.dump(&mut dump).await.unwrap();
+
+    let target = server();
+
+    // existing keys under restored index definitions are indexed too
+    target
+        .call(Request::Set(
+            "user:2".into(),
+            r#"{"email": "a@eight"}"#.into(),
+        ))
+        .await
+        .unwrap();
+
+    let mut events = Box::pin(target.watch("user:1"));
+
+    assert!(target.restore(dump.as_slice()).await.unwrap() > 2);
+    assert_eq!(events.next().await, Some(KeyEvent::Set("user:1".into())));
+
+    let results = target
+        .query(
+            "find by_email a@eight; ftsearch note: printer;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results,
+        vec![
+            Response::TextList(vec!["user:1".into(), "user:2".into()]),
+            Response::TextList(vec!["note:1".into()]),
+        ]
+    );
+}
+
+#[cfg(feature = "chaos-storage")]
+#[tokio::test]
+async fn dump_fails_on_unreadable_value() {
+    use crate::embedded::storage::chaos::{self, Operation};
+
+    let storage = chaos::Storage::new(memory::Storage::new(), 0).fail_nth(Operation::Get, 2);
+    let server = Server::new(storage);
+    server.start().await;
+
+    for key in ["a", "b", "c"] {
+        server
+            .call(Request::Set(key.into(), "value".into()))
+            .await
+            .unwrap();
+    }
+
+    let mut dump = Vec::new();
+
+    assert_eq!(server.dump(&mut dump).await, Err(Error::GetKeyFail));
+}
+
+#[cfg(feature = "filesystem-storage")]
+#[tokio::test]
+async fn backups_stay_in_snapshot_directory() {
+    use super::Permission;
+
+    let path = std::env::temp_dir().join("eight_server_backups");
+    std::fs::remove_dir_all(&path).ok();
+
+    let server = server();
+    server.set_permission(Permission::Admin).await;
+
+    let response = server.call(Request::Backup("first".into())).await.unwrap();
+    assert_eq!(response, Response::Error(Error::SnapshotDirectoryMissing));
+
+    server.set_snapshot_directory(&path).await;
+    server
+        .call(Request::Set("a".into(), "1".into()))
+        .await
+        .unwrap();
+
+    for name in ["../first", "/tmp/first", "first/second", "..", ""] {
+        let response = server.call(Request::Backup(name.into())).await.unwrap();
+        assert_eq!(response, Response::Error(Error::InvalidSnapshotName));
+    }
+
+    let response = server.call(Request::Backup("first".into())).await.unwrap();
+    assert_eq!(response, Response::Number(1));
+    assert!(path.join("first").exists());
+
+    std::fs::remove_dir_all(&path).ok();
+}
+
+#[cfg(feature = "filesystem-storage")]
+#[tokio::test]
+async fn indexes_survive_restart() {
+    use crate::embedded::storage::{filesystem, Storage};
+
+    let path = std::env::temp_dir().join("eight_server_indexes");
+    let start = || {
+        let server = Server::new(filesystem::Storage::from_path(&path));
+        tokio::spawn({
+            let server = server.clone();
+            async move { server.listen().await }
+        });
+
+        server
+    };
+
+    let server = start();
+    server.call(Request::Flush).await.unwrap();
+
+    server
+        .query(
+            r#"
+            create index by_status on job: field $.state.status;
+            set job:1 "{\"state\": {\"status\": \"done\"}}";
+            "#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let server = start();
+    let response = server
+        .call(Request::Find("by_status".into(), "done".into()))
+        .await
+        .unwrap();
+
+    assert_eq!(response, Response::TextList(vec!["job:1".into()]));
+
+    filesystem::Storage::from_path(&path).flush().await.unwrap();
+}
+
+#[cfg(feature = "filesystem-storage")]
+#[tokio::test]
+async fn snapshots_keep_indexes_consistent() {
+    use crate::embedded::storage::{
+        chaos::{self, Latency, Operation},
+        filesystem,
+    };
+
+    let path = std::env::temp_dir().join("eight_server_snapshot_indexes");
+    std::fs::remove_dir_all(&path).ok();
+
+    // slow reads keep snapshot copying while key changes
+    let storage = chaos::Storage::new(memory::Storage::new(), 7)
+        .set_latency(Operation::Get, Latency::Fixed(Duration::from_millis(5)));
+    let server = Server::new(storage);
+    server.start().await;
+
+    let mut query = "create index by_city on user: field $.city; ftindex user:;".to_string();
+
+    for i in 0..20 {
+        query.push_str(&format!(r#"set user:{i:02} "{{\"city\": \"a\"}}";"#));
+    }
+
+    server.query(query, Default::default()).await.unwrap();
+
+    let snapshot = tokio::spawn({
+        let server = server.clone();
+        let path = path.clone();
+        async move { server.snapshot(path).await }
+    });
+
+    time::sleep(Duration::from_millis(20)).await;
+
+    let response = server
+        .call(Request::Set("user:19".into(), r#"{"city": "b"}"#.into()))
+        .await
+        .unwrap();
+    assert_eq!(response, Response::Ok);
+
+    assert_eq!(snapshot.await.unwrap().unwrap(), 62);
+
+    let restored = Server::new(filesystem::Storage::from_path(&path));
+    restored.start().await;
+
+    let results = restored
+        .query(
+            "find by_city a; find by_city b; ftsearch user: b;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let all = (0..20).map(|i| format!("user:{i:02}")).collect::<Vec<_>>();
+    assert_eq!(results[0], Response::TextList(all));
+    assert_eq!(results[1], Response::TextList(vec![]));
+    assert_eq!(results[2], Response::TextList(vec![]));
+
+    std::fs::remove_dir_all(&path).ok();
+}
+
+#[tokio::test]
+async fn full_text_search() {
+    let server = server();
+
+    let results = server
+        .query(
+            r#"
+            set ticket:1 "Printer is on fire";
+            set ticket:2 "printer jammed, printer ink low. PRINTER!";
+            set ticket:3 "Password reset";
+            set note:1 "printer";
+            ftindex ticket:;
+            ftsearch ticket: printer;
+            set ticket:4 "Reset my printer password";
+            ftsearch ticket: "password reset";
+            delete ticket:3;
+            ftsearch ticket: "password reset";
+            ftsearch ticket: scanner;
+            ftsearch note: printer;
+            search ticket;
+            ftdrop ticket:;
+            ftsearch ticket: printer;
+            "#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(results[4], Response::Number(3));
+    assert_eq!(
+        results[5],
+        Response::TextList(vec!["ticket:2".into(), "ticket:1".into()])
+    );
+    assert_eq!(
+        results[7],
+        Response::TextList(vec!["ticket:3".into(), "ticket:4".into()])
+    );
+    assert_eq!(results[9], Response::TextList(vec!["ticket:4".into()]));
+    assert_eq!(results[10], Response::TextList(vec![]));
+    assert_eq!(results[11], Response::Error(Error::IndexNotFound));
+
+    let Response::TextList(keys) = &results[12] else {
+        panic!("search result is not a list: {:?}", results[12]);
+    };
+
+    assert_eq!(keys.len(), 3);
+    assert_eq!(results[13], Response::Ok);
+    assert_eq!(results[14], Response::Error(Error::IndexNotFound));
+}
+
+#[cfg(feature = "filesystem-storage")]
+#[tokio::test]
+async fn full_text_survives_restart() {
+    use crate::embedded::storage::{filesystem, Storage};
+
+    let path = std::env::temp_dir().join("eight_server_full_text");
+    let start = || {
+        let server = Server::new(filesystem::Storage::from_path(&path));
+        tokio::spawn({
+            let server = server.clone();
+            async move { server.listen().await }
+        });
+
+        server
+    };
+
+    let server = start();
+    server.call(Request::Flush).await.unwrap();
+
+    server
+        .query(
+            r#"ftindex doc:; set doc:1 "hello world"; set doc:2 "goodbye world";"#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let server = start();
+    let response = server
+        .call(Request::FullTextSearch("doc:".into(), "hello".into()))
+        .await
+        .unwrap();
+
+    assert_eq!(response, Response::TextList(vec!["doc:1".into()]));
+
+    filesystem::Storage::from_path(&path).flush().await.unwrap();
+}
+
+fn entry_ids(response: &Response) -> Vec<String> {
+    let Response::List(entries) = response else {
+        panic!("stream result is not a list: {response:?}");
+    };
+
+    entries
+        .iter()
+        .map(|entry| match entry {
+            Response::Map(entry) => match entry.get("id") {
+                Some(Response::Text(id)) => id.clone(),
+                other => panic!("entry id is not a text: {other:?}"),
+            },
+            other => panic!("entry is not a map: {other:?}"),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn streams() {
+    let server = server();
+
+    let results = server
+        .query(
+            r#"
+            xadd events user bob action login;
+            xadd events user alice action login;
+            xadd events user bob action logout;
+            xrange events - +;
+            xrange events - + 2;
+            xread events $;
+            set plain hello;
+            xadd plain user bob;
+            "#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let ids: Vec<String> = results[..3]
+        .iter()
+        .map(|result| match result {
+            Response::Text(id) => id.clone(),
+            other => panic!("stream add result is not an id: {other:?}"),
+        })
+        .collect();
+
+    assert!(ids[0] < ids[1] && ids[1] < ids[2]);
+    assert_eq!(entry_ids(&results[3]), ids);
+    assert_eq!(entry_ids(&results[4]), ids[..2]);
+    assert_eq!(entry_ids(&results[5]), Vec::<String>::new());
+    assert_eq!(results[7], Response::Error(Error::StreamParseFail));
+
+    let Response::List(entries) = &results[3] else {
+        unreachable!()
+    };
+
+    assert_eq!(
+        entries[0],
+        Response::Map(
+            [
+                ("id".to_string(), Response::Text(ids[0].clone())),
+                (
+                    "fields".to_string(),
+                    Response::Map(
+                        [
+                            ("action".to_string(), Response::Text("login".into())),
+                            ("user".to_string(), Response::Text("bob".into())),
+                        ]
+                        .into()
+                    )
+                ),
+            ]
+            .into()
+        )
+    );
+
+    let response = server
+        .call(Request::StreamRead("events".into(), ids[0].clone(), 0))
+        .await
+        .unwrap();
+    assert_eq!(entry_ids(&response), ids[1..]);
+
+    let response = server
+        .call(Request::StreamRange(
+            "events".into(),
+            ids[1].clone(),
+            "+".into(),
+            0,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(entry_ids(&response), ids[1..]);
+
+    let response = server
+        .call(Request::StreamRead("events".into(), "nope".into(), 0))
+        .await
+        .unwrap();
+    assert_eq!(response, Response::Error(Error::InvalidStreamId));
+}
+
+#[tokio::test]
+async fn stream_consumer_groups() {
+    let server = server();
+    let read = |consumer: &str, count| {
+        Request::StreamReadGroup("jobs".into(), "workers".into(), consumer.into(), count)
+    };
+
+    let results = server
+        .query(
+            r#"
+            xgroup create jobs workers 50;
+            xgroup create jobs workers 50;
+            xadd jobs task a;
+            xadd jobs task b;
+            xadd jobs task c;
+            "#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(results[0], Response::Ok);
+    assert_eq!(results[1], Response::Error(Error::GroupExists));
+
+    let first = entry_ids(&server.call(read("worker-1", 2)).await.unwrap());
+    let second = entry_ids(&server.call(read("worker-2", 0)).await.unwrap());
+
+    assert_eq!(first.len(), 2);
+    assert_eq!(second.len(), 1);
+    assert!(entry_ids(&server.call(read("worker-2", 0)).await.unwrap()).is_empty());
+
+    // worker 1 acknowledges only the first entry
+    let response = server
+        .call(Request::StreamAck(
+            "jobs".into(),
+            "workers".into(),
+            vec![first[0].clone(), "0-1".into()],
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response, Response::Number(1));
+
+    let response = server
+        .call(Request::StreamAck(
+            "jobs".into(),
+            "workers".into(),
+            vec![second[0].clone()],
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response, Response::Number(1));
+
+    // unacknowledged entry is delivered again after timeout
+    time::sleep(Duration::from_millis(60)).await;
+
+    let redelivered = entry_ids(&server.call(read("worker-2", 0)).await.unwrap());
+    assert_eq!(redelivered, vec![first[1].clone()]);
+
+    let response = server
+        .call(Request::StreamGroupDestroy("jobs".into(), "workers".into()))
+        .await
+        .unwrap();
+    assert_eq!(response, Response::Ok);
+    assert_eq!(
+        server.call(read("worker-1", 0)).await.unwrap(),
+        Response::Error(Error::GroupNotFound)
+    );
+}
+
+#[tokio::test]
+async fn stream_trimming() {
+    let server = server();
+
+    let results = server
+        .query(
+            r#"
+            xgroup create log readers 60000;
+            xadd log n 1;
+            xadd log n 2;
+            xadd log n 3;
+            xreadgroup log readers r1;
+            xadd log maxlen 2 n 4;
+            xrange log - +;
+            xtrim log 1;
+            xrange log - +;
+            xtrim log 5;
+            xtrim missing 1;
+            exists missing;
+            "#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let ids: Vec<String> = [1, 2, 3, 5]
+        .iter()
+        .map(|index| match &results[*index] {
+            Response::Text(id) => id.clone(),
+            other => panic!("stream add result is not an id: {other:?}"),
+        })
+        .collect();
+
+    assert_eq!(entry_ids(&results[4]).len(), 3);
+    assert_eq!(entry_ids(&results[6]), ids[2..].to_vec());
+    assert_eq!(results[7], Response::Number(1));
+    assert_eq!(entry_ids(&results[8]), ids[3..].to_vec());
+    assert_eq!(
+        results[9..],
+        [
+            Response::Number(0),
+            Response::Number(0),
+            Response::Boolean(false)
+        ]
+    );
+
+    // pending deliveries of removed entries are gone too
+    let response = server
+        .call(Request::StreamAck("log".into(), "readers".into(), ids))
+        .await
+        .unwrap();
+    assert_eq!(response, Response::Number(0));
+}
+
+#[tokio::test]
+async fn blocking_stream_read() {
+    let server = server();
+
+    server
+        .call(Request::StreamAdd(
+            "feed".into(),
+            vec![("n".into(), "1".into())],
+            None,
+        ))
+        .await
+        .unwrap();
+
+    let reader = tokio::spawn({
+        let server = server.clone();
+        async move {
+            server
+                .call(Request::BlockingStreamRead(
+                    "feed".into(),
+                    "$".into(),
+                    0,
+                    Duration::ZERO,
+                ))
+                .await
+                .unwrap()
+        }
+    });
+
+    time::sleep(Duration::from_millis(20)).await;
+
+    let Response::Text(id) = server
+        .call(Request::StreamAdd(
+            "feed".into(),
+            vec![("n".into(), "2".into())],
+            None,
+        ))
+        .await
+        .unwrap()
+    else {
+        panic!("stream add result is not an id");
+    };
+
+    assert_eq!(entry_ids(&reader.await.unwrap()), vec![id]);
+
+    let response = server
+        .call(Request::BlockingStreamRead(
+            "feed".into(),
+            "$".into(),
+            0,
+            Duration::from_millis(20),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response, Response::Error(Error::WaitTimeout));
+}
+
+#[cfg(feature = "filesystem-storage")]
+#[tokio::test]
+async fn streams_survive_restart() {
+    use crate::embedded::storage::{filesystem, Storage};
+
+    let path = std::env::temp_dir().join("eight_server_streams");
+    let start = || {
+        let server = Server::new(filesystem::Storage::from_path(&path));
+        tokio::spawn({
+            let server = server.clone();
+            async move { server.listen().await }
+        });
+
+        server
+    };
+
+    let server = start();
+    server.call(Request::Flush).await.unwrap();
+
+    let results = server
+        .query(
+            "xgroup create log readers 60000; xadd log line one; xadd log line two; xreadgroup log readers r1 1;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let server = start();
+    let results_after = server
+        .query(
+            "xrange log - +; xreadgroup log readers r2; xadd log line three;",
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let Response::Text(id) = &results_after[2] else {
+        panic!("stream add result is not an id: {:?}", results_after[2]);
+    };
+
+    assert_eq!(entry_ids(&results_after[0]).len(), 2);
+    // first entry is still pending for r1, so r2 gets the second one
+    assert_eq!(
+        entry_ids(&results_after[1]),
+        vec![entry_ids(&results_after[0])[1].clone()]
+    );
+    assert_eq!(entry_ids(&results[3]), entry_ids(&results_after[0])[..1]);
+    assert!(id.as_str() > entry_ids(&results_after[0])[1].as_str());
+
+    filesystem::Storage::from_path(&path).flush().await.unwrap();
+}
+
+#[test]
+fn hyper_log_log_estimation() {
+    let mut sketch = HyperLogLog::default();
+
+    assert_eq!(sketch.count(), 0);
+    assert_eq!(HyperLogLog::decode(&sketch.encode()), Ok(sketch.clone()));
+
+    for item in 0..100 {
+        sketch.add(&format!("user:{item}"));
+    }
+
+    assert_eq!(HyperLogLog::decode(&sketch.encode()), Ok(sketch.clone()));
+    assert!(sketch.count().abs_diff(100) <= 2);
+
+    for item in 0..50_000 {
+        sketch.add(&format!("user:{item}"));
+    }
+
+    // most registers are set now, so dense encoding is used
+    let raw = sketch.encode();
+    assert!(typed::untag(Type::HyperLogLog, &raw).is_some_and(|raw| raw.starts_with("d:")));
+    assert_eq!(HyperLogLog::decode(&raw), Ok(sketch.clone()));
+    assert!(sketch.count().abs_diff(50_000) <= 1_000);
+
+    for raw in ["s:99999:1", "s:1:99", "d:AAA"] {
+        assert_eq!(
+            HyperLogLog::decode(&typed::tag(Type::HyperLogLog, raw)),
+            Err(Error::HyperLogLogParseFail)
+        );
+    }
+
+    // encoding without tag is a plain string
+    assert_eq!(
+        HyperLogLog::decode("s:1:1"),
+        Err(Error::HyperLogLogParseFail)
+    );
+}
+
+#[test]
+fn bloom_filter_error_rate() {
+    let mut filter = Bloom::new(0.01, 1000).unwrap();
+
+    // adding may hit a false positive too
+    let added = (0..1000)
+        .filter(|item| filter.add(&format!("seen:{item}")))
+        .count();
+
+    assert!(added > 980, "{added} items added");
+
+    assert!(!filter.add("seen:0"));
+    assert!((0..1000).all(|item| filter.contains(&format!("seen:{item}"))));
+
+    let false_positives = (0..10_000)
+        .filter(|item| filter.contains(&format!("unseen:{item}")))
+        .count();
+
+    assert!(false_positives < 200, "{false_positives} false positives");
+    assert_eq!(Bloom::decode(&filter.encode()), Ok(filter));
+
+    for raw in [
+        "hello",
+        "0:10:AB",
+        "3:10:A",
+        "3:10:A!",
+        "3:18446744073709551615:A",
+    ] {
+        assert_eq!(
+            Bloom::decode(&typed::tag(Type::Bloom, raw)),
+            Err(Error::BloomParseFail)
+        );
+    }
+
+    assert_eq!(Bloom::decode("3:10:AB"), Err(Error::BloomParseFail));
+
+    for (error_rate, capacity) in [
+        (0.0, 1000),
+        (1.0, 1000),
+        (f64::NAN, 1000),
+        (0.01, 0),
+        (0.01, usize::MAX),
+    ] {
+        assert_eq!(Bloom::new(error_rate, capacity), Err(Error::InvalidFilter));
+    }
+}
+
+#[tokio::test]
+async fn probabilistic_commands() {
+    let server = server();
+
+    let results = server
+        .query(
+            r#"
+            pfadd visitors:mon alice bob carol;
+            pfadd visitors:mon alice;
+            pfadd visitors:tue bob dave;
+            pfcount visitors:mon;
+            pfcount visitors:mon visitors:tue;
+            pfmerge visitors:week visitors:mon visitors:tue;
+            pfcount visitors:week;
+            pfcount visitors:none;
+            bfreserve seen 0.001 100;
+            bfreserve seen 0.001 100;
+            bfadd seen alice;
+            bfadd seen alice;
+            bfexists seen alice;
+            bfexists seen bob;
+            bfexists nothing alice;
+            bfadd visitors:mon alice;
+            pfadd seen alice;
+            "#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results,
+        vec![
+            Response::Boolean(true),
+            Response::Boolean(false),
+            Response::Boolean(true),
+            Response::Number(3),
+            Response::Number(4),
+            Response::Ok,
+            Response::Number(4),
+            Response::Number(0),
+            Response::Ok,
+            Response::Error(Error::FilterExists),
+            Response::Boolean(true),
+            Response::Boolean(false),
+            Response::Boolean(true),
+            Response::Boolean(false),
+            Response::Boolean(false),
+            Response::Error(Error::BloomParseFail),
+            Response::Error(Error::HyperLogLogParseFail),
+        ]
+    );
+
+    // concurrent adds are not lost
+    let tasks: Vec<_> = (0..50)
+        .map(|item| {
+            let server = server.clone();
+            tokio::spawn(async move {
+                server
+                    .call(Request::BloomAdd("crowd".into(), format!("user:{item}")))
+                    .await
+                    .unwrap()
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    for item in 0..50 {
+        let response = server
+            .call(Request::BloomExists("crowd".into(), format!("user:{item}")))
+            .await
+            .unwrap();
+
+        assert_eq!(response, Response::Boolean(true));
+    }
+
+    // requests don't always come from parser
+    let response = server
+        .call(Request::BloomReserve("huge".into(), 0.0, usize::MAX))
+        .await
+        .unwrap();
+
+    assert_eq!(response, Response::Error(Error::InvalidFilter));
+}
+
+#[test]
+fn time_series() {
+    let mut series = Series::new(10_000).unwrap();
+
+    for index in 0..20 {
+        series.add(1000 + index * 500, index as f64);
+    }
+
+    assert_eq!(
+        series.range(1000, 2000, None),
+        vec![(1000, 0.0), (1500, 1.0), (2000, 2.0)]
+    );
+    assert_eq!(series.range(2000, 1000, None), vec![]);
+    assert_eq!(
+        series.range(2000, 1000, Some((Aggregation::Avg, 10))),
+        vec![]
+    );
+    assert_eq!(
+        series.range(0, 3999, Some((Aggregation::Avg, 2000))),
+        vec![(0, 0.5), (2000, 3.5)]
+    );
+    assert_eq!(
+        series.range(0, 3999, Some((Aggregation::Max, 2000))),
+        vec![(0, 1.0), (2000, 5.0)]
+    );
+
+    // rules downsample samples kept so far
+    series.add_rule(Aggregation::Sum, 5000, 100_000).unwrap();
+
+    assert_eq!(
+        series.add_rule(Aggregation::Sum, 5000, 100_000),
+        Err(Error::RuleExists)
+    );
+    assert_eq!(
+        series.add_rule(Aggregation::Avg, 5000, 0),
+        Err(Error::InvalidRetention)
+    );
+    assert_eq!(Series::new(0), Err(Error::InvalidRetention));
+    assert_eq!(
+        series.range(0, u64::MAX, Some((Aggregation::Sum, 5000))),
+        vec![(0, 28.0), (5000, 125.0), (10000, 37.0)]
+    );
+
+    // raw samples expire, downsampled ones are kept for their own retention
+    series.add(20_000, 1.0);
+
+    assert_eq!(
+        series.range(0, u64::MAX, None),
+        vec![(10000, 18.0), (10500, 19.0), (20000, 1.0)]
+    );
+    assert_eq!(
+        series.range(0, u64::MAX, Some((Aggregation::Sum, 5000))),
+        vec![(0, 28.0), (5000, 125.0), (10000, 37.0), (20000, 1.0)]
+    );
+
+    assert_eq!(Series::decode(&series.encode()), Ok(series));
+
+    // series created by adding a sample keeps a day
+    let mut series = Series::default();
+    series.add(1000, 1.0);
+    series.add(1000 + 24 * 60 * 60 * 1000 + 1, 2.0);
+
+    assert_eq!(
+        series.range(0, u64::MAX, None),
+        vec![(24 * 60 * 60 * 1000 + 1001, 2.0)]
+    );
+
+    let untagged = list::encode(&VecDeque::from(["0".to_string(), String::new()]));
+    let stream = typed::tag(Type::Stream, &untagged);
+    let broken = typed::tag(
+        Type::TimeSeries,
+        &list::encode(&VecDeque::from(["0".to_string(), "1:a".to_string()])),
+    );
+
+    for raw in ["hello", &untagged, &stream, &broken] {
+        assert_eq!(Series::decode(raw), Err(Error::SeriesParseFail));
+    }
+}
+
+#[test]
+fn time_series_replaces_samples() {
+    let mut series = Series::default();
+
+    for aggregation in [Aggregation::Sum, Aggregation::Avg, Aggregation::Min] {
+        series.add_rule(aggregation, 1000, 100_000).unwrap();
+    }
+
+    series.add(1000, 1.0);
+    series.add(1000, 5.0);
+    series.add(1500, 3.0);
+    series.add(1000, 2.0);
+
+    assert_eq!(
+        series.range(0, u64::MAX, None),
+        vec![(1000, 2.0), (1500, 3.0)]
+    );
+
+    for (aggregation, value) in [
+        (Aggregation::Sum, 5.0),
+        (Aggregation::Avg, 2.5),
+        (Aggregation::Min, 2.0),
+    ] {
+        assert_eq!(
+            series.range(0, u64::MAX, Some((aggregation, 1000))),
+            vec![(1000, value)]
+        );
+    }
+
+    // raw samples of an open bucket outlive a shorter retention, so the bucket can be aggregated again
+    let mut series = Series::new(100).unwrap();
+    series.add_rule(Aggregation::Max, 1000, 100_000).unwrap();
+
+    series.add(1000, 9.0);
+    series.add(1500, 1.0);
+    series.add(1000, 0.0);
+
+    assert_eq!(
+        series.range(0, u64::MAX, Some((Aggregation::Max, 1000))),
+        vec![(1000, 1.0)]
+    );
+
+    // they expire once bucket is closed
+    series.add(2000, 4.0);
+    assert_eq!(series.range(0, u64::MAX, None), vec![(2000, 4.0)]);
+}
+
+#[tokio::test]
+async fn time_series_commands() {
+    let server = server();
+
+    let results = server
+        .query(
+            r#"
+            tsadd temp 1000 20.5;
+            tsadd temp 2000 21.5;
+            tsadd temp 61000 30;
+            tsrange temp - +;
+            tsrange temp 1500 60000;
+            tsrange temp - + avg 60000;
+            tscreate temp 0;
+            tsrule temp max 60000 3600000;
+            tsrule temp max 60000 3600000;
+            tsrange temp - + max 60000;
+            tscreate short 1000;
+            tsadd short 1000 1;
+            tsadd short 3000 2;
+            tsrange short - +;
+            tsrange nothing - +;
+            tsrange temp 100 50 avg 10;
+            set name alice;
+            tsadd name 1000 1;
+            tscreate forever 0;
+            tsrule temp min 60000 0;
+            "#,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+    let samples = |samples: &[(usize, &str)]| {
+        Response::List(
+            samples
+                .iter()
+                .map(|(time, value)| {
+                    Response::Map(
+                        [
+                            ("time".to_string(), Response::Number(*time)),
+                            ("value".to_string(), Response::Text(value.to_string())),
+                        ]
+                        .into(),
+                    )
+                })
+                .collect(),
+        )
+    };
+
+    assert_eq!(
+        results,
+        vec![
+            Response::Number(1000),
+            Response::Number(2000),
+            Response::Number(61000),
+            samples(&[(1000, "20.5"), (2000, "21.5"), (61000, "30")]),
+            samples(&[(2000, "21.5")]),
+            samples(&[(0, "21"), (60000, "30")]),
+            Response::Error(Error::SeriesExists),
+            Response::Ok,
+            Response::Error(Error::RuleExists),
+            samples(&[(0, "21.5"), (60000, "30")]),
+            Response::Ok,
+            Response::Number(1000),
+            Response::Number(3000),
+            samples(&[(3000, "2")]),
+            samples(&[]),
+            samples(&[]),
+            Response::Ok,
+            Response::Error(Error::SeriesParseFail),
+            Response::Error(Error::InvalidRetention),
+            Response::Error(Error::InvalidRetention),
+        ]
+    );
+
+    // sample without timestamp is added at current time
+    let response = server
+        .call(Request::TimeSeriesAdd("now".into(), None, 1.0))
+        .await
+        .unwrap();
+
+    let Response::Number(time) = response else {
+        panic!("unexpected response {response:?}");
+    };
+
+    assert!(time > 1_600_000_000_000);
+}
@@ -0,0 +1,421 @@
+//! Time series kept as a single value, with retention and downsampling rules kept next to samples.
+//!
+//! Value is a list tagged as `timeseries`, of retention, samples and rules. Samples are written as `<time>:<value>` pairs
+//! separated by commas, every time after the first is the difference from previous one. Rules are lists of
+//! aggregation, bucket size, retention, open bucket and downsampled samples. Times and durations are in milliseconds.
+//!
+//! Every sample rewrites the whole value, so retention is never unlimited and keeps series from growing without bound.
+
+use super::{
+    list,
+    typed::{self, Type},
+};
+use crate::{
+    embedded::{messaging::Aggregation, Result},
+    err,
+};
+use std::collections::{BTreeMap, VecDeque};
+
+type Samples = BTreeMap<u64, f64>;
+
+/// Retention of series created by adding a sample, a day.
+const DEFAULT_RETENTION: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Bucket {
+    start: u64,
+    sum: f64,
+    count: usize,
+    min: f64,
+    max: f64,
+}
+
+impl Bucket {
+    fn new(start: u64, value: f64) -> Self {
+        Self {
+            start,
+            sum: value,
+            count: 1,
+            min: value,
+            max: value,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn value(&self, aggregation: Aggregation) -> f64 {
+        match aggregation {
+            Aggregation::Avg => self.sum / self.count as f64,
+            Aggregation::Min => self.min,
+            Aggregation::Max => self.max,
+            Aggregation::Sum => self.sum,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    aggregation: Aggregation,
+    bucket: u64,
+    retention: u64,
+    // samples are aggregated as they come, bucket is written to samples once a later one starts
+    open: Option<Bucket>,
+    samples: Samples,
+}
+
+impl Rule {
+    /// Samples of closed buckets are ignored, they are already downsampled.
+    fn add(&mut self, time: u64, value: f64) {
+        let start = time - time % self.bucket;
+
+        match &mut self.open {
+            Some(open) if open.start == start => open.add(value),
+            Some(open) if open.start > start => {}
+            open => {
+                if let Some(closed) = open.replace(Bucket::new(start, value)) {
+                    self.samples
+                        .insert(closed.start, closed.value(self.aggregation));
+                }
+            }
+        }
+    }
+
+    /// Aggregate open bucket again from raw samples if time is in it, after a sample in it is replaced.
+    fn replace(&mut self, samples: &Samples, time: u64) {
+        let start = time - time % self.bucket;
+
+        let Some(open) = self.open.as_mut().filter(|open| open.start == start) else {
+            return;
+        };
+
+        let mut values = samples
+            .range(start..=start.saturating_add(self.bucket - 1))
+            .map(|(_, value)| *value);
+
+        if let Some(first) = values.next() {
+            *open = values.fold(Bucket::new(start, first), |mut bucket, value| {
+                bucket.add(value);
+                bucket
+            });
+        }
+    }
+
+    // open bucket is included with its value so far
+    fn samples(&self) -> Samples {
+        let mut samples = self.samples.clone();
+
+        if let Some(open) = &self.open {
+            samples.insert(open.start, open.value(self.aggregation));
+        }
+
+        samples
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Series {
+    retention: u64,
+    samples: Samples,
+    rules: Vec<Rule>,
+}
+
+impl Default for Series {
+    fn default() -> Self {
+        Self {
+            retention: DEFAULT_RETENTION,
+            samples: Samples::new(),
+            rules: vec![],
+        }
+    }
+}
+
+impl Series {
+    /// Create series keeping samples for retention, it must be positive.
+    pub fn new(retention: u64) -> Result<Self> {
+        if retention == 0 {
+            return Err(err!(embedded, InvalidRetention));
+        }
+
+        Ok(Self {
+            retention,
+            ..Default::default()
+        })
+    }
+
+    /// Add sample, a sample at the same time is replaced.
+    pub fn add(&mut self, time: u64, value: f64) {
+        let replaced = self.samples.insert(time, value).is_some();
+
+        for rule in &mut self.rules {
+            if replaced {
+                rule.replace(&self.samples, time);
+            } else {
+                rule.add(time, value);
+            }
+        }
+
+        self.trim();
+    }
+
+    /// Add downsampling rule keeping downsampled samples for a positive retention, samples kept so far are downsampled too.
+    pub fn add_rule(
+        &mut self,
+        aggregation: Aggregation,
+        bucket: u64,
+        retention: u64,
+    ) -> Result<()> {
+        if retention == 0 {
+            return Err(err!(embedded, InvalidRetention));
+        }
+
+        if self
+            .rules
+            .iter()
+            .any(|rule| rule.aggregation == aggregation && rule.bucket == bucket)
+        {
+            return Err(err!(embedded, RuleExists));
+        }
+
+        let mut rule = Rule {
+            aggregation,
+            bucket: bucket.max(1),
+            retention,
+            open: None,
+            samples: Samples::new(),
+        };
+
+        for (time, value) in &self.samples {
+            rule.add(*time, *value);
+        }
+
+        self.rules.push(rule);
+        self.trim();
+
+        Ok(())
+    }
+
+    /// Samples between times, both inclusive. Samples are aggregated in buckets if aggregation is given,
+    /// downsampled samples are used if there is a rule for it.
+    pub fn range(
+        &self,
+        from: u64,
+        to: u64,
+        aggregation: Option<(Aggregation, u64)>,
+    ) -> Vec<(u64, f64)> {
+        let Some((aggregation, bucket)) = aggregation else {
+            return within(&self.samples, from, to);
+        };
+
+        if from > to {
+            return vec![];
+        }
+
+        let bucket = bucket.max(1);
+
+        if let Some(rule) = self
+            .rules
+            .iter()
+            .find(|rule| rule.aggregation == aggregation && rule.bucket == bucket)
+        {
+            return within(&rule.samples(), from, to);
+        }
+
+        let mut buckets: BTreeMap<u64, Bucket> = BTreeMap::new();
+
+        for (time, value) in self.samples.range(from..=to) {
+            let start = time - time % bucket;
+
+            buckets
+                .entry(start)
+                .and_modify(|bucket| bucket.add(*value))
+                .or_insert_with(|| Bucket::new(start, *value));
+        }
+
+        buckets
+            .into_iter()
+            .map(|(start, bucket)| (start, bucket.value(aggregation)))
+            .collect()
+    }
+
+    // retention is counted back from the latest sample, raw samples of open buckets are kept until they are closed
+    fn trim(&mut self) {
+        let Some(latest) = self.samples.keys().next_back().copied() else {
+            return;
+        };
+
+        let open = self.rules.iter().filter_map(|rule| rule.open.as_ref());
+        let start = open.fold(latest.saturating_sub(self.retention), |start, open| {
+            start.min(open.start)
+        });
+
+        self.samples = self.samples.split_off(&start);
+
+        for rule in &mut self.rules {
+            retain(&mut rule.samples, latest, rule.retention);
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let mut items = VecDeque::from([self.retention.to_string(), encode_samples(&self.samples)]);
+
+        items.extend(self.rules.iter().map(|rule| {
+            let open = rule
+                .open
+                .as_ref()
+                .map(|open| {
+                    format!(
+                        "{}:{}:{}:{}:{}",
+                        open.start, open.sum, open.count, open.min, open.max
+                    )
+                })
+                .unwrap_or_default();
+
+            list::encode(&VecDeque::from([
+                aggregation_name(rule.aggregation).to_string(),
+                rule.bucket.to_string(),
+                rule.retention.to_string(),
+                open,
+                encode_samples(&rule.samples),
+            ]))
+        }));
+
+        typed::tag(Type::TimeSeries, &list::encode(&items))
+    }
+
+    pub fn decode(raw: &str) -> Result<Self> {
+        let fail = || err!(embedded, SeriesParseFail);
+
+        let raw = typed::untag(Type::TimeSeries, raw).ok_or_else(fail)?;
+        let mut items = list::decode(raw).map_err(|_| fail())?;
+
+        let (Some(retention), Some(samples)) = (items.pop_front(), items.pop_front()) else {
+            return Err(fail());
+        };
+
+        let mut series = Self {
+            retention: retention
+                .parse()
+                .ok()
+                .filter(|retention| *retention > 0)
+                .ok_or_else(fail)?,
+            samples: decode_samples(&samples)?,
+            rules: vec![],
+        };
+
+        for rule in items {
+            let rule: [String; 5] = Vec::from(list::decode(&rule).map_err(|_| fail())?)
+                .try_into()
+                .map_err(|_| fail())?;
+            let [aggregation, bucket, retention, open, samples] = rule;
+
+            let open = match open.as_str() {
+                "" => None,
+                open => {
+                    let parts: Vec<&str> = open.split(':').collect();
+
+                    let [start, sum, count, min, max] = parts[..] else {
+                        return Err(fail());
+                    };
+
+                    Some(Bucket {
+                        start: start.parse().map_err(|_| fail())?,
+                        sum: sum.parse().map_err(|_| fail())?,
+                        count: count.parse().map_err(|_| fail())?,
+                        min: min.parse().map_err(|_| fail())?,
+                        max: max.parse().map_err(|_| fail())?,
+                    })
+                }
+            };
+
+            series.rules.push(Rule {
+                aggregation: parse_aggregation(&aggregation).ok_or_else(fail)?,
+                bucket: bucket
+                    .parse()
+                    .ok()
+                    .filter(|bucket| *bucket > 0)
+                    .ok_or_else(fail)?,
+                retention: retention
+                    .parse()
+                    .ok()
+                    .filter(|retention| *retention > 0)
+                    .ok_or_else(fail)?,
+                open,
+                samples: decode_samples(&samples)?,
+            });
+        }
+
+        Ok(series)
+    }
+}
+
+fn aggregation_name(aggregation: Aggregation) -> &'static str {
+    match aggregation {
+        Aggregation::Avg => "avg",
+        Aggregation::Min => "min",
+        Aggregation::Max => "max",
+        Aggregation::Sum => "sum",
+    }
+}
+
+fn parse_aggregation(name: &str) -> Option<Aggregation> {
+    match name {
+        "avg" => Some(Aggregation::Avg),
+        "min" => Some(Aggregation::Min),
+        "max" => Some(Aggregation::Max),
+        "sum" => Some(Aggregation::Sum),
+        _ => None,
+    }
+}
+
+fn within(samples: &Samples, from: u64, to: u64) -> Vec<(u64, f64)> {
+    if from > to {
+        return vec![];
+    }
+
+    samples
+        .range(from..=to)
+        .map(|(time, value)| (*time, *value))
+        .collect()
+}
+
+fn retain(samples: &mut Samples, latest: u64, retention: u64) {
+    *samples = samples.split_off(&latest.saturating_sub(retention));
+}
+
+fn encode_samples(samples: &Samples) -> String {
+    let mut previous = 0;
+
+    samples
+        .iter()
+        .map(|(time, value)| {
+            let delta = time - previous;
+            previous = *time;
+
+            format!("{delta}:{value}")
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn decode_samples(raw: &str) -> Result<Samples> {
+    let fail = || err!(embedded, SeriesParseFail);
+    let mut time = 0u64;
+
+    raw.split(',')
+        .filter(|sample| !sample.is_empty())
+        .map(|sample| {
+            let (delta, value) = sample.split_once(':').ok_or_else(fail)?;
+
+            time = time
+                .checked_add(delta.parse().map_err(|_| fail())?)
+                .ok_or_else(fail)?;
+
+            Ok((time, value.parse().map_err(|_| fail())?))
+        })
+        .collect()
+}
@@ -0,0 +1,54 @@
+//! Typed values start with a tag naming their type, so they are told apart from plain strings without parsing them.
+//!
+//! Tag is the type name between `\u{1}` control characters, like `\u{1}list\u{1}`.
+
+const MARK: char = '\u{1}';
+
+/// Length of the longest tag, reading this many characters of a value is enough to find its type.
+pub(super) const MAX_TAG_LENGTH: usize = 2 + 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Type {
+    List,
+    Stream,
+    TimeSeries,
+    HyperLogLog,
+    Bloom,
+}
+
+impl Type {
+    const ALL: [Type; 5] = [
+        Type::List,
+        Type::Stream,
+        Type::TimeSeries,
+        Type::HyperLogLog,
+        Type::Bloom,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::List => "list",
+            Type::Stream => "stream",
+            Type::TimeSeries => "timeseries",
+            Type::HyperLogLog => "hyperloglog",
+            Type::Bloom => "bloom",
+        }
+    }
+}
+
+pub(super) fn tag(kind: Type, payload: &str) -> String {
+    format!("{MARK}{}{MARK}{payload}", kind.name())
+}
+
+/// Payload of value, [`None`] if value is not tagged with given type.
+pub(super) fn untag(kind: Type, raw: &str) -> Option<&str> {
+    raw.strip_prefix(MARK)?
+        .strip_prefix(kind.name())?
+        .strip_prefix(MARK)
+}
+
+/// Type value is tagged with, [`None`] for plain strings.
+pub(super) fn of(raw: &str) -> Option<Type> {
+    let (name, _) = raw.strip_prefix(MARK)?.split_once(MARK)?;
+    Type::ALL.into_iter().find(|kind| kind.name() == name)
+}
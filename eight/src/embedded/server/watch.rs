@@ -0,0 +1,90 @@
+use crate::embedded::messaging::KeyEvent;
+use futures::{stream, Stream};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex, PoisonError,
+};
+use tokio::sync::mpsc::{
+    self,
+    error::{TryRecvError, TrySendError},
+};
+
+// every event carries the number of events dropped right before it
+type Item = (u64, KeyEvent);
+
+struct Watcher {
+    prefix: String,
+    sender: mpsc::Sender<Item>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Subscribers of key events. Every watcher has its own bounded buffer, a slow one never blocks server or other watchers.
+#[derive(Default)]
+pub(super) struct Watchers {
+    list: Mutex<Vec<Watcher>>,
+}
+
+impl Watchers {
+    pub fn subscribe(&self, prefix: String, capacity: usize) -> impl Stream<Item = KeyEvent> {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        self.list
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Watcher {
+                prefix,
+                sender,
+                dropped: Arc::clone(&dropped),
+            });
+
+        let state = (receiver, dropped, None::<KeyEvent>);
+
+        stream::unfold(state, |(mut receiver, dropped, pending)| async move {
+            if let Some(event) = pending {
+                return Some((event, (receiver, dropped, None)));
+            }
+
+            let (lagged, event) = match receiver.try_recv() {
+                Ok(item) => item,
+                // buffer is drained, events dropped after the last buffered one are reported before waiting
+                Err(TryRecvError::Empty) => match dropped.swap(0, Ordering::SeqCst) {
+                    0 => receiver.recv().await?,
+                    lagged => return Some((KeyEvent::Lagged(lagged), (receiver, dropped, None))),
+                },
+                Err(TryRecvError::Disconnected) => return None,
+            };
+
+            match lagged {
+                0 => Some((event, (receiver, dropped, None))),
+                lagged => Some((KeyEvent::Lagged(lagged), (receiver, dropped, Some(event)))),
+            }
+        })
+    }
+
+    pub fn publish(&self, event: KeyEvent) {
+        let mut list = self.list.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // dropped streams are removed here
+        list.retain(|watcher| {
+            let matches = event
+                .key()
+                .is_none_or(|key| key.starts_with(&watcher.prefix));
+
+            if !matches {
+                return !watcher.sender.is_closed();
+            }
+
+            let lagged = watcher.dropped.swap(0, Ordering::SeqCst);
+
+            match watcher.sender.try_send((lagged, event.clone())) {
+                Ok(_) => true,
+                Err(TrySendError::Full(_)) => {
+                    watcher.dropped.fetch_add(lagged + 1, Ordering::SeqCst);
+                    true
+                }
+                Err(TrySendError::Closed(_)) => false,
+            }
+        });
+    }
+}
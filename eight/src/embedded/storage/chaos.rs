@@ -0,0 +1,390 @@
+//! Fault-injecting storage wrapper for eight.
+//!
+//! Wraps any [`Storage`] and makes its operations fail or slow down, so error paths can be tested without a broken disk.
+//! Faults are decided before the inner storage is called, a failed operation never reaches it.
+//!
+//! Every random decision comes from the seed. Same seed and same order of calls always inject the same faults,
+//! calls running concurrently may take their decisions in a different order.
+//!
+//! [`Storage`]: super::Storage
+
+use crate::{embedded, err};
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::time;
+
+pub use super::Operation;
+
+/// Distribution of injected latency.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Latency {
+    /// Same delay for every call.
+    Fixed(Duration),
+    /// Delay picked evenly between minimum and maximum.
+    Uniform { min: Duration, max: Duration },
+    /// Mostly short delays with a long tail, like a busy disk.
+    Exponential { mean: Duration },
+}
+
+impl Latency {
+    fn sample(self, random: &mut Random) -> Duration {
+        match self {
+            Latency::Fixed(delay) => delay,
+            Latency::Uniform { min, max } => {
+                let (min, max) = (min.min(max), min.max(max));
+                min + (max - min).mul_f64(random.next_f64())
+            }
+            Latency::Exponential { mean } => mean.mul_f64(-(1.0 - random.next_f64()).ln()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct Faults {
+    error_rate: f64,
+    error: Option<embedded::Error>,
+    latency: Option<Latency>,
+    // 1-based call numbers that always fail
+    scripted: HashSet<u64>,
+}
+
+// SplitMix64, small and stable across versions, so a seed always means the same faults
+#[derive(Debug)]
+struct Random(u64);
+
+impl Random {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Fault-injecting storage wrapper. Without any fault configured, it behaves exactly like the inner storage.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use eight::embedded::{storage::{Storage, chaos::{self, Operation}, memory}, Error};
+///
+/// let storage = chaos::Storage::new(memory::Storage::new(), 42)
+///   .fail_nth(Operation::Set, 3)
+///   .set_error(Operation::Get, Error::GetKeyFail)
+///   .set_error_rate(Operation::Get, 0.5);
+///
+/// storage.set("first".to_string(), "1".to_string()).await.unwrap();
+/// storage.set("second".to_string(), "2".to_string()).await.unwrap();
+///
+/// // fail the 3rd set
+/// assert_eq!(storage.set("third".to_string(), "3".to_string()).await, Err(Error::SetKeyFail));
+/// assert!(!storage.inner().exists("third".to_string()).await.unwrap());
+///
+/// // about half of the reads fail
+/// let mut failed = 0;
+///
+/// for _ in 0..100 {
+///   if storage.get("first".to_string()).await.is_err() {
+///     failed += 1;
+///   }
+/// }
+///
+/// assert!((30..70).contains(&failed));
+/// assert_eq!(storage.calls(Operation::Get), 100);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Storage<S> {
+    inner: S,
+    faults: HashMap<Operation, Faults>,
+    random: Mutex<Random>,
+    calls: [AtomicU64; Operation::ALL.len()],
+}
+
+impl<S> Storage<S>
+where
+    S: super::Storage,
+{
+    /// Create new chaos storage on top of another storage. Random faults are picked from given seed.
+    pub fn new(inner: S, seed: u64) -> Self {
+        Self {
+            inner,
+            faults: HashMap::new(),
+            random: Mutex::new(Random(seed)),
+            calls: Default::default(),
+        }
+    }
+
+    /// Set the chance of an operation failing, between `0.0` (never) and `1.0` (always).
+    pub fn set_error_rate(mut self, operation: Operation, rate: f64) -> Self {
+        self.faults.entry(operation).or_default().error_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set error returned by failing operation. By default, operation fails with the error bundled storages would return.
+    pub fn set_error(mut self, operation: Operation, error: embedded::Error) -> Self {
+        self.faults.entry(operation).or_default().error = Some(error);
+        self
+    }
+
+    /// Delay every call of operation with given latency.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::storage::{Storage, chaos::{self, Latency, Operation}, memory};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let storage = chaos::Storage::new(memory::Storage::new(), 7).set_latency(
+    ///   Operation::Set,
+    ///   Latency::Uniform { min: Duration::from_millis(10), max: Duration::from_millis(20) },
+    /// );
+    ///
+    /// let start = Instant::now();
+    /// storage.set("slow".to_string(), "disk".to_string()).await.unwrap();
+    ///
+    /// assert!(start.elapsed() >= Duration::from_millis(10));
+    /// # });
+    /// ```
+    pub fn set_latency(mut self, operation: Operation, latency: Latency) -> Self {
+        self.faults.entry(operation).or_default().latency = Some(latency);
+        self
+    }
+
+    /// Make the nth call of operation fail, counting from one (1). Can be used multiple times.
+    pub fn fail_nth(mut self, operation: Operation, nth: u64) -> Self {
+        self.faults
+            .entry(operation)
+            .or_default()
+            .scripted
+            .insert(nth);
+        self
+    }
+
+    /// How many times operation is called, including failed calls.
+    pub fn calls(&self, operation: Operation) -> u64 {
+        self.counter(operation).load(Ordering::SeqCst)
+    }
+
+    /// Returns inner storage.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consumes chaos storage and returns inner storage.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn counter(&self, operation: Operation) -> &AtomicU64 {
+        &self.calls[operation as usize]
+    }
+
+    async fn inject(&self, operation: Operation) -> embedded::Result<()> {
+        let call = self.counter(operation).fetch_add(1, Ordering::SeqCst) + 1;
+
+        let Some(faults) = self.faults.get(&operation) else {
+            return Ok(());
+        };
+
+        let (delay, fail) = {
+            let mut random = self.random.lock().unwrap_or_else(PoisonError::into_inner);
+
+            let delay = faults.latency.map(|latency| latency.sample(&mut random));
+            let fail = faults.scripted.contains(&call)
+                || (faults.error_rate > 0.0 && random.next_f64() < faults.error_rate);
+
+            (delay, fail)
+        };
+
+        if let Some(delay) = delay {
+            time::sleep(delay).await;
+        }
+
+        if fail {
+            Err(faults.error.clone().unwrap_or(default_error(operation)))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[async_trait]
+impl<S> super::Storage for Storage<S>
+where
+    S: super::Storage,
+{
+    async fn set(&self, key: String, value: String) -> embedded::Result<()> {
+        self.inject(Operation::Set).await?;
+        self.inner.set(key, value).await
+    }
+
+    async fn get(&self, key: String) -> embedded::Result<String> {
+        self.inject(Operation::Get).await?;
+        self.inner.get(key).await
+    }
+
+    async fn delete(&self, key: String) -> embedded::Result<()> {
+        self.inject(Operation::Delete).await?;
+        self.inner.delete(key).await
+    }
+
+    async fn exists(&self, key: String) -> embedded::Result<bool> {
+        self.inject(Operation::Exists).await?;
+        self.inner.exists(key).await
+    }
+
+    async fn increment(&self, key: String, num: usize) -> embedded::Result<usize> {
+        self.inject(Operation::Increment).await?;
+        self.inner.increment(key, num).await
+    }
+
+    async fn decrement(&self, key: String, num: usize) -> embedded::Result<usize> {
+        self.inject(Operation::Decrement).await?;
+        self.inner.decrement(key, num).await
+    }
+
+    async fn search(&self, key: String) -> embedded::Result<Vec<String>> {
+        self.inject(Operation::Search).await?;
+        self.inner.search(key).await
+    }
+
+    async fn flush(&self) -> embedded::Result<()> {
+        self.inject(Operation::Flush).await?;
+        self.inner.flush().await
+    }
+
+    async fn keys(
+        &self,
+        prefix: String,
+    ) -> embedded::Result<BoxStream<'static, embedded::Result<String>>> {
+        self.inject(Operation::Keys).await?;
+        self.inner.keys(prefix).await
+    }
+
+    async fn rename(&self, from: String, to: String) -> embedded::Result<()> {
+        self.inject(Operation::Rename).await?;
+        self.inner.rename(from, to).await
+    }
+
+    async fn rename_nx(&self, from: String, to: String) -> embedded::Result<bool> {
+        self.inject(Operation::RenameNx).await?;
+        self.inner.rename_nx(from, to).await
+    }
+
+    async fn copy(&self, from: String, to: String) -> embedded::Result<bool> {
+        self.inject(Operation::Copy).await?;
+        self.inner.copy(from, to).await
+    }
+
+    async fn set_nx(&self, key: String, value: String) -> embedded::Result<bool> {
+        self.inject(Operation::SetNx).await?;
+        self.inner.set_nx(key, value).await
+    }
+
+    async fn set_xx(&self, key: String, value: String) -> embedded::Result<bool> {
+        self.inject(Operation::SetXx).await?;
+        self.inner.set_xx(key, value).await
+    }
+
+    async fn get_set(&self, key: String, value: String) -> embedded::Result<Option<String>> {
+        self.inject(Operation::GetSet).await?;
+        self.inner.get_set(key, value).await
+    }
+
+    async fn get_delete(&self, key: String) -> embedded::Result<String> {
+        self.inject(Operation::GetDelete).await?;
+        self.inner.get_delete(key).await
+    }
+
+    async fn append(&self, key: String, value: String) -> embedded::Result<usize> {
+        self.inject(Operation::Append).await?;
+        self.inner.append(key, value).await
+    }
+
+    async fn length(&self, key: String) -> embedded::Result<usize> {
+        self.inject(Operation::Length).await?;
+        self.inner.length(key).await
+    }
+
+    async fn get_range(&self, key: String, start: isize, end: isize) -> embedded::Result<String> {
+        self.inject(Operation::GetRange).await?;
+        self.inner.get_range(key, start, end).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: String,
+        current: Option<String>,
+        value: String,
+    ) -> embedded::Result<bool> {
+        self.inject(Operation::CompareAndSwap).await?;
+        self.inner.compare_and_swap(key, current, value).await
+    }
+
+    async fn compare_and_delete(&self, key: String, current: String) -> embedded::Result<bool> {
+        self.inject(Operation::CompareAndDelete).await?;
+        self.inner.compare_and_delete(key, current).await
+    }
+
+    async fn metadata(&self, key: String) -> embedded::Result<super::Metadata> {
+        self.inject(Operation::Metadata).await?;
+        self.inner.metadata(key).await
+    }
+
+    async fn history(&self, key: String) -> embedded::Result<Vec<super::Version>> {
+        self.inject(Operation::History).await?;
+        self.inner.history(key).await
+    }
+
+    async fn get_at(&self, key: String, time: SystemTime) -> embedded::Result<String> {
+        self.inject(Operation::GetAt).await?;
+        self.inner.get_at(key, time).await
+    }
+
+    fn metrics(&self) -> Option<super::metrics::Metrics> {
+        self.inner.metrics()
+    }
+}
+
+// errors bundled storages return for the same operation
+fn default_error(operation: Operation) -> embedded::Error {
+    match operation {
+        Operation::Set
+        | Operation::Increment
+        | Operation::Decrement
+        | Operation::Rename
+        | Operation::RenameNx
+        | Operation::Copy
+        | Operation::SetNx
+        | Operation::SetXx
+        | Operation::GetSet
+        | Operation::Append
+        | Operation::CompareAndSwap => err!(embedded, SetKeyFail),
+        Operation::Get
+        | Operation::Search
+        | Operation::Keys
+        | Operation::Length
+        | Operation::GetRange
+        | Operation::Metadata
+        | Operation::History
+        | Operation::GetAt => err!(embedded, GetKeyFail),
+        Operation::Delete | Operation::GetDelete | Operation::CompareAndDelete => {
+            err!(embedded, DeleteKeyFail)
+        }
+        Operation::Exists => err!(embedded, CheckExistsFail),
+        Operation::Flush => err!(embedded, DirRemoveFail),
+    }
+}
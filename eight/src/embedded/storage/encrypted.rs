@@ -0,0 +1,497 @@
+//! Encrypting storage wrapper for eight.
+//!
+//! Wraps any [`Storage`] and encrypts every value with XChaCha20-Poly1305 before it reaches the inner storage.
+//! Each record carries the id of the key it was encrypted with, so old keys can stay in the [`Keyring`] for reading while new writes use the primary key.
+//!
+//! Key names are not encrypted, they are passed to the inner storage as is.
+//! Every value is bound to its key, so renames and copies decrypt and encrypt the value again instead of using the inner storage.
+//! Conditional sets and [`Storage::get_set`] use the inner storage and stay atomic. Increments and appends can't work on ciphertext,
+//! they are retried on [`Storage::compare_and_swap`] of the inner storage until no one changes the value in between.
+//! Renames delete the source only if it is unchanged, so a concurrent write to the source is never lost.
+//! Rotation swaps every record the same way, a value written while rotating is kept as is.
+//!
+//! [`Storage`]: super::Storage
+//! [`Storage::get_set`]: super::Storage::get_set
+//! [`Storage::compare_and_swap`]: super::Storage::compare_and_swap
+
+use crate::{embedded, err};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use std::{collections::HashMap, fmt};
+
+const RECORD_PREFIX: &str = "eight:v1:";
+const NONCE_LENGTH: usize = 24;
+
+/// Set of encryption keys used by encrypted storage.
+///
+/// The primary key encrypts new values, other keys are only used for decrypting values written before a rotation.
+///
+/// ```
+/// use eight::embedded::storage::encrypted::Keyring;
+///
+/// let keyring = Keyring::new("2023-06", [1; 32])
+///   .add_key("2023-07", [2; 32])
+///   .set_primary("2023-07");
+///
+/// assert_eq!(keyring.primary(), "2023-07");
+/// ```
+#[derive(Clone)]
+pub struct Keyring {
+    primary: String,
+    ciphers: HashMap<String, XChaCha20Poly1305>,
+}
+
+impl Keyring {
+    /// Create new keyring with a primary key.
+    pub fn new<T: ToString>(id: T, key: [u8; 32]) -> Self {
+        let id = id.to_string();
+
+        let mut ciphers = HashMap::new();
+        ciphers.insert(id.clone(), XChaCha20Poly1305::new(&key.into()));
+
+        Self {
+            primary: id,
+            ciphers,
+        }
+    }
+
+    /// Add a key to keyring. Replaces the key if id already exists.
+    pub fn add_key<T: ToString>(mut self, id: T, key: [u8; 32]) -> Self {
+        self.ciphers
+            .insert(id.to_string(), XChaCha20Poly1305::new(&key.into()));
+        self
+    }
+
+    /// Set primary key. Does nothing if there is no key with given id.
+    pub fn set_primary<T: ToString>(mut self, id: T) -> Self {
+        let id = id.to_string();
+
+        if self.ciphers.contains_key(&id) {
+            self.primary = id;
+        }
+
+        self
+    }
+
+    /// Id of the key used for encrypting new values.
+    pub fn primary(&self) -> &str {
+        &self.primary
+    }
+
+    fn seal(&self, key: &str, value: &str) -> embedded::Result<String> {
+        let cipher = &self.ciphers[&self.primary];
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let aad = associated_data(&self.primary, key);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value.as_bytes(),
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| err!(embedded, SetKeyFail))?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend(ciphertext);
+
+        Ok(format!(
+            "{RECORD_PREFIX}{}:{}",
+            self.primary,
+            STANDARD_NO_PAD.encode(payload)
+        ))
+    }
+
+    fn open(&self, key: &str, record: &str) -> embedded::Result<String> {
+        let (id, payload) = parse_record(record)?;
+        let cipher = self
+            .ciphers
+            .get(id)
+            .ok_or(err!(embedded, EncryptionKeyNotFound))?;
+
+        let payload = STANDARD_NO_PAD
+            .decode(payload)
+            .map_err(|_| err!(embedded, DecryptionFail))?;
+
+        if payload.len() < NONCE_LENGTH {
+            return Err(err!(embedded, DecryptionFail));
+        }
+
+        let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+        let aad = associated_data(id, key);
+
+        let plaintext = cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| err!(embedded, DecryptionFail))?;
+
+        String::from_utf8(plaintext).map_err(|_| err!(embedded, DecryptionFail))
+    }
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keyring")
+            .field("primary", &self.primary)
+            .field("keys", &self.ciphers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Encrypting storage wrapper. Every value is encrypted separately and bound to its key name.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use eight::embedded::{storage::{Storage, encrypted::{self, Keyring}, memory}, Error};
+///
+/// let storage = encrypted::Storage::new(memory::Storage::new(), Keyring::new("main", [7; 32]));
+///
+/// storage.set("user".to_string(), "secret".to_string()).await.unwrap();
+/// assert_eq!(storage.get("user".to_string()).await.unwrap(), "secret");
+///
+/// // moving a record under another key is detected
+/// let raw = storage.inner().get("user".to_string()).await.unwrap();
+/// storage.inner().set("other".to_string(), raw).await.unwrap();
+///
+/// assert_eq!(storage.get("other".to_string()).await, Err(Error::DecryptionFail));
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Storage<S> {
+    inner: S,
+    keyring: Keyring,
+}
+
+impl<S> Storage<S>
+where
+    S: super::Storage,
+{
+    /// Create new encrypted storage on top of another storage.
+    pub fn new(inner: S, keyring: Keyring) -> Self {
+        Self { inner, keyring }
+    }
+
+    /// Returns inner storage. Values read from it are encrypted records.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consumes encrypted storage and returns inner storage.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Re-encrypt every value that is not encrypted with the primary key and returns how many values are updated.
+    ///
+    /// Run this after changing primary key, then old keys can be removed from keyring.
+    /// Values written or deleted while rotating are left as they are.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::storage::{Storage, encrypted::{self, Keyring}, memory};
+    ///
+    /// let old = encrypted::Storage::new(memory::Storage::new(), Keyring::new("old", [1; 32]));
+    /// old.set("user".to_string(), "secret".to_string()).await.unwrap();
+    ///
+    /// let keyring = Keyring::new("old", [1; 32]).add_key("new", [2; 32]).set_primary("new");
+    /// let storage = encrypted::Storage::new(old.into_inner(), keyring);
+    ///
+    /// assert_eq!(storage.rotate().await.unwrap(), 1);
+    /// assert_eq!(storage.rotate().await.unwrap(), 0);
+    /// # });
+    /// ```
+    pub async fn rotate(&self) -> embedded::Result<usize> {
+        let mut updated = 0;
+
+        for key in self.inner.search(String::new()).await? {
+            loop {
+                // key can be deleted after search
+                let record = match self.inner.get(key.clone()).await {
+                    Ok(record) => record,
+                    Err(embedded::Error::GetKeyFail) => break,
+                    Err(error) => return Err(error),
+                };
+
+                let (id, _) = parse_record(&record)?;
+
+                if id == self.keyring.primary {
+                    break;
+                }
+
+                let sealed = self
+                    .keyring
+                    .seal(&key, &self.keyring.open(&key, &record)?)?;
+
+                // value is changed meanwhile, it is read again
+                if self
+                    .inner
+                    .compare_and_swap(key.clone(), Some(record), sealed)
+                    .await?
+                {
+                    updated += 1;
+                    break;
+                }
+            }
+        }
+
+        Ok(updated)
+    }
+
+    async fn record(&self, key: &str) -> embedded::Result<Option<String>> {
+        if self.inner.exists(key.to_string()).await? {
+            Ok(Some(self.inner.get(key.to_string()).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Change value of key, retrying until the record is swapped without a concurrent change.
+    async fn swap<T>(
+        &self,
+        key: &str,
+        change: impl Fn(Option<&str>) -> embedded::Result<(T, String)> + Send,
+    ) -> embedded::Result<T> {
+        loop {
+            let record = self.record(key).await?;
+            let value = record
+                .as_deref()
+                .map(|record| self.keyring.open(key, record))
+                .transpose()?;
+
+            let (result, value) = change(value.as_deref())?;
+            let sealed = self.keyring.seal(key, &value)?;
+
+            if self
+                .inner
+                .compare_and_swap(key.to_string(), record, sealed)
+                .await?
+            {
+                return Ok(result);
+            }
+        }
+    }
+
+    async fn count(
+        &self,
+        key: String,
+        change: impl Fn(usize) -> Option<usize> + Send + Sync,
+    ) -> embedded::Result<usize> {
+        self.swap(&key, |value| {
+            let new = value
+                .ok_or(err!(embedded, GetKeyFail))?
+                .parse::<usize>()
+                .ok()
+                .and_then(&change)
+                .ok_or(err!(embedded, UIntParseFail))?;
+
+            Ok((new, new.to_string()))
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl<S> super::Storage for Storage<S>
+where
+    S: super::Storage,
+{
+    async fn set(&self, key: String, value: String) -> embedded::Result<()> {
+        let record = self.keyring.seal(&key, &value)?;
+        self.inner.set(key, record).await
+    }
+
+    async fn get(&self, key: String) -> embedded::Result<String> {
+        let record = self.inner.get(key.clone()).await?;
+        self.keyring.open(&key, &record)
+    }
+
+    async fn delete(&self, key: String) -> embedded::Result<()> {
+        self.inner.delete(key).await
+    }
+
+    async fn exists(&self, key: String) -> embedded::Result<bool> {
+        self.inner.exists(key).await
+    }
+
+    async fn increment(&self, key: String, num: usize) -> embedded::Result<usize> {
+        self.count(key, |value| value.checked_add(num)).await
+    }
+
+    async fn decrement(&self, key: String, num: usize) -> embedded::Result<usize> {
+        self.count(key, |value| value.checked_sub(num)).await
+    }
+
+    async fn search(&self, key: String) -> embedded::Result<Vec<String>> {
+        self.inner.search(key).await
+    }
+
+    async fn flush(&self) -> embedded::Result<()> {
+        self.inner.flush().await
+    }
+
+    async fn rename(&self, from: String, to: String) -> embedded::Result<()> {
+        loop {
+            let record = self.inner.get(from.clone()).await?;
+            let value = self.keyring.open(&from, &record)?;
+
+            if from == to {
+                return Ok(());
+            }
+
+            let sealed = self.keyring.seal(&to, &value)?;
+            self.inner.set(to.clone(), sealed).await?;
+
+            // source changed in the meantime, its new value is moved again
+            if self.inner.compare_and_delete(from.clone(), record).await? {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn rename_nx(&self, from: String, to: String) -> embedded::Result<bool> {
+        loop {
+            let record = self.inner.get(from.clone()).await?;
+            let sealed = self
+                .keyring
+                .seal(&to, &self.keyring.open(&from, &record)?)?;
+
+            if !self.inner.set_nx(to.clone(), sealed.clone()).await? {
+                return Ok(false);
+            }
+
+            if self.inner.compare_and_delete(from.clone(), record).await? {
+                return Ok(true);
+            }
+
+            // source changed in the meantime, target is taken back before trying again
+            self.inner.compare_and_delete(to.clone(), sealed).await?;
+        }
+    }
+
+    async fn copy(&self, from: String, to: String) -> embedded::Result<bool> {
+        let value = self.get(from).await?;
+        let sealed = self.keyring.seal(&to, &value)?;
+
+        self.inner.set_nx(to, sealed).await
+    }
+
+    async fn set_nx(&self, key: String, value: String) -> embedded::Result<bool> {
+        let record = self.keyring.seal(&key, &value)?;
+        self.inner.set_nx(key, record).await
+    }
+
+    async fn set_xx(&self, key: String, value: String) -> embedded::Result<bool> {
+        let record = self.keyring.seal(&key, &value)?;
+        self.inner.set_xx(key, record).await
+    }
+
+    async fn get_set(&self, key: String, value: String) -> embedded::Result<Option<String>> {
+        let record = self.keyring.seal(&key, &value)?;
+
+        match self.inner.get_set(key.clone(), record).await? {
+            Some(old) => self.keyring.open(&key, &old).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_delete(&self, key: String) -> embedded::Result<String> {
+        let record = self.inner.get_delete(key.clone()).await?;
+        self.keyring.open(&key, &record)
+    }
+
+    async fn append(&self, key: String, value: String) -> embedded::Result<usize> {
+        self.swap(&key, |current| {
+            let appended = format!("{}{value}", current.unwrap_or_default());
+            Ok((appended.chars().count(), appended))
+        })
+        .await
+    }
+
+    // records are compared, so swap is as atomic as the inner storage
+    async fn compare_and_swap(
+        &self,
+        key: String,
+        current: Option<String>,
+        value: String,
+    ) -> embedded::Result<bool> {
+        let record = self.record(&key).await?;
+
+        let actual = record
+            .as_deref()
+            .map(|record| self.keyring.open(&key, record))
+            .transpose()?;
+
+        if actual != current {
+            return Ok(false);
+        }
+
+        let sealed = self.keyring.seal(&key, &value)?;
+        self.inner.compare_and_swap(key, record, sealed).await
+    }
+
+    async fn compare_and_delete(&self, key: String, current: String) -> embedded::Result<bool> {
+        if !self.inner.exists(key.clone()).await? {
+            return Ok(false);
+        }
+
+        let record = self.inner.get(key.clone()).await?;
+
+        if self.keyring.open(&key, &record)? != current {
+            return Ok(false);
+        }
+
+        self.inner.compare_and_delete(key, record).await
+    }
+
+    // timestamps come from inner storage, size is of the decrypted value
+    async fn metadata(&self, key: String) -> embedded::Result<super::Metadata> {
+        let metadata = self.inner.metadata(key.clone()).await?;
+        let value = self.get(key).await?;
+
+        Ok(super::Metadata {
+            size: value.len(),
+            ..metadata
+        })
+    }
+
+    async fn history(&self, key: String) -> embedded::Result<Vec<super::Version>> {
+        let mut versions = self.inner.history(key.clone()).await?;
+
+        for version in &mut versions {
+            if let Some(record) = &version.value {
+                version.value = Some(self.keyring.open(&key, record)?);
+            }
+        }
+
+        Ok(versions)
+    }
+
+    fn metrics(&self) -> Option<super::metrics::Metrics> {
+        self.inner.metrics()
+    }
+}
+
+fn parse_record(record: &str) -> embedded::Result<(&str, &str)> {
+    record
+        .strip_prefix(RECORD_PREFIX)
+        .and_then(|rest| rest.rsplit_once(':'))
+        .ok_or(err!(embedded, DecryptionFail))
+}
+
+fn associated_data(id: &str, key: &str) -> Vec<u8> {
+    let mut aad = (id.len() as u64).to_le_bytes().to_vec();
+    aad.extend(id.as_bytes());
+    aad.extend(key.as_bytes());
+
+    aad
+}
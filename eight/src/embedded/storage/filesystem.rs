@@ -1,16 +1,44 @@
 //! Official filesystem based storage implementation for eight.
 
+use super::Metadata;
 use crate::{
     embedded::{self, filesystem},
     err,
 };
 use async_trait::async_trait;
+use futures::stream::BoxStream;
 use std::path::PathBuf;
 
+pub use filesystem::{Layout, Repair, Report};
+
 /// Filesystem based storage. Preferred when you need to keep key-values on disk.
+///
+/// Every key is stored in its own directory, by default split into two (2) character parts. See [`Layout`] for other layouts.
+/// Characters other than alphanumerics and underscore are percent encoded, so any key can be stored.
+/// Stores created before key encoding use the same layout and are read without a migration.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use eight::embedded::storage::{Storage, filesystem};
+///
+/// let storage = filesystem::Storage::from_path("./keys_storage_test");
+///
+/// for key in ["a", "user:42", "../etc", "ünïcödé key"] {
+///   storage.set(key.to_string(), "value".to_string()).await.unwrap();
+/// }
+///
+/// let results = storage.search("user:".to_string()).await.unwrap();
+/// assert_eq!(results, vec!["user:42".to_string()]);
+///
+/// # storage.flush().await;
+/// # });
+/// ```
 #[derive(Debug, Default)]
 pub struct Storage {
     path: PathBuf,
+    layout: Layout,
+    locks: filesystem::Locks,
+    legacy: bool,
 }
 
 impl Storage {
@@ -32,36 +60,143 @@ impl Storage {
     where
         T: Into<PathBuf>,
     {
-        Self { path: path.into() }
+        Self {
+            path: path.into(),
+            layout: Default::default(),
+            locks: Default::default(),
+            legacy: false,
+        }
+    }
+
+    /// Set directory layout. Layout must match the one used while creating storage, see [`Storage::migrate`] to convert.
+    ///
+    /// ```no_run
+    /// use eight::embedded::storage::filesystem::{self, Layout};
+    ///
+    /// filesystem::Storage::from_path("/tmp/test").set_layout(Layout::Nested { width: 4, depth: Some(3) });
+    /// ```
+    pub fn set_layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Current directory layout.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Read values written before checksums as they are. Off by default, reading them fails with [`Error::CorruptedValue`]
+    /// since a value without checksum can't be told apart from a damaged one. See [`Storage::fsck`] to add checksums.
+    ///
+    /// ```no_run
+    /// use eight::embedded::storage::filesystem;
+    ///
+    /// filesystem::Storage::from_path("/tmp/test").set_legacy_values(true);
+    /// ```
+    ///
+    /// [`Error::CorruptedValue`]: crate::embedded::Error::CorruptedValue
+    pub fn set_legacy_values(mut self, accept: bool) -> Self {
+        self.legacy = accept;
+        self
+    }
+
+    /// Move every value into given layout and return the storage using it.
+    ///
+    /// Values are moved one by one, so storage must not be used while migrating.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::storage::{Storage, filesystem::{self, Layout}};
+    ///
+    /// let storage = filesystem::Storage::from_path("./migrate_storage_test");
+    /// storage.set("some_long_key".to_string(), "value".to_string()).await.unwrap();
+    ///
+    /// let storage = storage.migrate(Layout::Hashed { buckets: 16 }).await.unwrap();
+    /// assert_eq!(storage.get("some_long_key".to_string()).await.unwrap(), "value");
+    ///
+    /// let storage = storage.migrate(Layout::Nested { width: 4, depth: Some(2) }).await.unwrap();
+    /// assert!(std::path::Path::new("./migrate_storage_test/some/_long_key/$").exists());
+    ///
+    /// # storage.flush().await;
+    /// # });
+    /// ```
+    pub async fn migrate(self, layout: Layout) -> embedded::Result<Self> {
+        filesystem::migrate(&self.path, &self.layout, &layout).await?;
+        Ok(self.set_layout(layout))
+    }
+
+    /// Walk storage directory and look for corrupt values, stray files and orphaned directories.
+    ///
+    /// This check is meant to be run offline, values written while checking may be reported as problems.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use eight::embedded::storage::{Storage, filesystem::{self, Repair}};
+    ///
+    /// let storage = filesystem::Storage::from_path("./fsck_storage_test");
+    /// storage.set("bob".to_string(), "some session id".to_string()).await.unwrap();
+    ///
+    /// // edit value by hand and leave a stray file behind
+    /// let raw = std::fs::read_to_string("./fsck_storage_test/bo/b/$").unwrap();
+    /// std::fs::write("./fsck_storage_test/bo/b/$", raw.replace("session", "token")).unwrap();
+    /// std::fs::write("./fsck_storage_test/bo/notes.txt", "hello").unwrap();
+    ///
+    /// assert!(storage.get("bob".to_string()).await.is_err());
+    ///
+    /// let report = storage.fsck(Repair::None).await.unwrap();
+    /// assert_eq!(report.corrupt.len(), 1);
+    /// assert_eq!(report.stray.len(), 1);
+    ///
+    /// let report = storage.fsck(Repair::Fix).await.unwrap();
+    /// assert!(!report.is_clean());
+    ///
+    /// let report = storage.fsck(Repair::None).await.unwrap();
+    /// assert!(report.is_clean());
+    ///
+    /// # storage.flush().await;
+    /// # });
+    /// ```
+    pub async fn fsck(&self, repair: Repair) -> embedded::Result<Report> {
+        let (path, layout) = (self.path.clone(), self.layout);
+
+        tokio::task::spawn_blocking(move || filesystem::fsck(&path, &layout, &repair))
+            .await
+            .map_err(|_| err!(embedded, FsckFail))?
     }
 }
 
 #[async_trait]
 impl super::Storage for Storage {
+    // writes of the same key are serialized, so compound operations are atomic
     async fn set(&self, key: String, value: String) -> embedded::Result<()> {
-        let mut path = filesystem::create_path(&self.path, &key)?;
+        let mut path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let _lock = self.locks.lock(&key).await;
+
         filesystem::write(&mut path, value).await
     }
 
     async fn get(&self, key: String) -> embedded::Result<String> {
-        let path = filesystem::create_path(&self.path, &key)?;
-        filesystem::read(&path).await
+        let path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        filesystem::read(&path, self.legacy).await
     }
 
     async fn delete(&self, key: String) -> embedded::Result<()> {
-        let path = filesystem::create_path(&self.path, &key)?;
-        filesystem::delete(&path).await
+        let path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let _lock = self.locks.lock(&key).await;
+
+        filesystem::delete(&self.path, &path).await
     }
 
     async fn exists(&self, key: String) -> embedded::Result<bool> {
-        let path = filesystem::create_path(&self.path, &key)?;
+        let path = filesystem::create_path(&self.path, &self.layout, &key)?;
         filesystem::exists(&path).await
     }
 
     async fn increment(&self, key: String, num: usize) -> embedded::Result<usize> {
-        let mut path = filesystem::create_path(&self.path, &key)?;
+        let mut path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let _lock = self.locks.lock(&key).await;
 
-        let raw = filesystem::read(&path).await?;
+        let raw = filesystem::read(&path, self.legacy).await?;
         let new = raw
             .parse::<usize>()
             .map_err(|_| err!(embedded, UIntParseFail))?
@@ -72,9 +207,10 @@ impl super::Storage for Storage {
     }
 
     async fn decrement(&self, key: String, num: usize) -> embedded::Result<usize> {
-        let mut path = filesystem::create_path(&self.path, &key)?;
+        let mut path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let _lock = self.locks.lock(&key).await;
 
-        let raw = filesystem::read(&path).await?;
+        let raw = filesystem::read(&path, self.legacy).await?;
         let new = raw
             .parse::<usize>()
             .map_err(|_| err!(embedded, UIntParseFail))?
@@ -85,10 +221,155 @@ impl super::Storage for Storage {
     }
 
     async fn search(&self, key: String) -> embedded::Result<Vec<String>> {
-        filesystem::search(&self.path, &key).await
+        filesystem::search(&self.path, &self.layout, &key).await
     }
 
     async fn flush(&self) -> embedded::Result<()> {
         filesystem::flush(&self.path).await
     }
+
+    async fn rename(&self, from: String, to: String) -> embedded::Result<()> {
+        let source = filesystem::create_path(&self.path, &self.layout, &from)?;
+        let target = filesystem::create_path(&self.path, &self.layout, &to)?;
+        let _locks = self.locks.lock_pair(&from, &to).await;
+
+        filesystem::rename(&self.path, &source, &target).await
+    }
+
+    async fn rename_nx(&self, from: String, to: String) -> embedded::Result<bool> {
+        let source = filesystem::create_path(&self.path, &self.layout, &from)?;
+        let target = filesystem::create_path(&self.path, &self.layout, &to)?;
+        let _locks = self.locks.lock_pair(&from, &to).await;
+
+        filesystem::rename_nx(&self.path, &source, &target).await
+    }
+
+    async fn copy(&self, from: String, to: String) -> embedded::Result<bool> {
+        let source = filesystem::create_path(&self.path, &self.layout, &from)?;
+        let target = filesystem::create_path(&self.path, &self.layout, &to)?;
+        let _locks = self.locks.lock_pair(&from, &to).await;
+
+        filesystem::copy(&self.path, &source, &target).await
+    }
+
+    async fn set_nx(&self, key: String, value: String) -> embedded::Result<bool> {
+        let mut path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let _lock = self.locks.lock(&key).await;
+
+        if filesystem::exists(&path).await? {
+            return Ok(false);
+        }
+
+        filesystem::write(&mut path, value).await?;
+        Ok(true)
+    }
+
+    async fn set_xx(&self, key: String, value: String) -> embedded::Result<bool> {
+        let mut path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let _lock = self.locks.lock(&key).await;
+
+        if !filesystem::exists(&path).await? {
+            return Ok(false);
+        }
+
+        filesystem::write(&mut path, value).await?;
+        Ok(true)
+    }
+
+    async fn get_set(&self, key: String, value: String) -> embedded::Result<Option<String>> {
+        let mut path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let _lock = self.locks.lock(&key).await;
+
+        let old = if filesystem::exists(&path).await? {
+            Some(filesystem::read(&path, self.legacy).await?)
+        } else {
+            None
+        };
+
+        filesystem::write(&mut path, value).await?;
+        Ok(old)
+    }
+
+    async fn get_delete(&self, key: String) -> embedded::Result<String> {
+        let path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let _lock = self.locks.lock(&key).await;
+
+        let value = filesystem::read(&path, self.legacy).await?;
+        filesystem::delete(&self.path, &path).await?;
+
+        Ok(value)
+    }
+
+    async fn append(&self, key: String, value: String) -> embedded::Result<usize> {
+        let mut path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let _lock = self.locks.lock(&key).await;
+
+        let mut current = if filesystem::exists(&path).await? {
+            filesystem::read(&path, self.legacy).await?
+        } else {
+            String::new()
+        };
+
+        current.push_str(&value);
+        let length = current.chars().count();
+
+        filesystem::write(&mut path, current).await?;
+        Ok(length)
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: String,
+        current: Option<String>,
+        value: String,
+    ) -> embedded::Result<bool> {
+        let mut path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let _lock = self.locks.lock(&key).await;
+
+        let actual = if filesystem::exists(&path).await? {
+            Some(filesystem::read(&path, self.legacy).await?)
+        } else {
+            None
+        };
+
+        if actual != current {
+            return Ok(false);
+        }
+
+        filesystem::write(&mut path, value).await?;
+        Ok(true)
+    }
+
+    async fn compare_and_delete(&self, key: String, current: String) -> embedded::Result<bool> {
+        let path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let _lock = self.locks.lock(&key).await;
+
+        if !filesystem::exists(&path).await?
+            || filesystem::read(&path, self.legacy).await? != current
+        {
+            return Ok(false);
+        }
+
+        filesystem::delete(&self.path, &path).await?;
+        Ok(true)
+    }
+
+    async fn keys(
+        &self,
+        prefix: String,
+    ) -> embedded::Result<BoxStream<'static, embedded::Result<String>>> {
+        Ok(filesystem::walk(self.path.clone(), self.layout, prefix))
+    }
+
+    // values are replaced on every write, so only the last write time is known
+    async fn metadata(&self, key: String) -> embedded::Result<Metadata> {
+        let path = filesystem::create_path(&self.path, &self.layout, &key)?;
+        let (size, updated) = filesystem::metadata(&path).await?;
+
+        Ok(Metadata {
+            size: size as usize,
+            updated: Some(updated),
+            ..Default::default()
+        })
+    }
 }
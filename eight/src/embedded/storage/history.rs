@@ -0,0 +1,493 @@
+//! Value history storage wrapper for eight.
+//!
+//! Keeps previous versions of keys under tracked prefixes, so a value can be read as it was at any time.
+//! Versions are kept in the inner storage next to the values, under keys starting with [`HISTORY_PREFIX`].
+//! These keys are hidden from [`Storage::search`] and [`Storage::keys`], writing them fails with [`Error::ReservedKey`] and flushing removes them too.
+//!
+//! Only writes made through the wrapper are recorded, a key has no history until it's written once.
+//!
+//! [`Storage::search`]: super::Storage::search
+//! [`Storage::keys`]: super::Storage::keys
+//! [`Error::ReservedKey`]: crate::embedded::Error::ReservedKey
+
+use super::{Metadata, Version};
+use crate::{embedded, err};
+use async_trait::async_trait;
+use futures::{
+    future,
+    stream::{BoxStream, StreamExt, TryStreamExt},
+};
+use std::{
+    future::Future,
+    sync::{Arc, Once, Weak},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::Mutex, time};
+
+/// Keys starting with this prefix hold versions of other keys and are reserved by the wrapper.
+pub const HISTORY_PREFIX: &str = "__history__:";
+
+/// How long versions of a key are kept. The latest version of an existing key is always kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retention {
+    /// Keep given number of latest versions.
+    Versions(usize),
+    /// Keep versions written within given duration.
+    Age(Duration),
+}
+
+impl Retention {
+    // drop versions out of retention, returns dropped version count
+    fn apply(self, versions: &mut Vec<Version>, now: SystemTime) -> usize {
+        let length = versions.len();
+
+        let keep = match self {
+            Retention::Versions(count) => count.max(1),
+            Retention::Age(age) => {
+                let cutoff = now.checked_sub(age).unwrap_or(UNIX_EPOCH);
+                let recent = versions
+                    .iter()
+                    .filter(|version| version.time >= cutoff)
+                    .count();
+
+                // latest value stays readable, unless key is already deleted
+                match versions.last() {
+                    Some(Version { value: Some(_), .. }) => recent.max(1),
+                    _ => recent,
+                }
+            }
+        };
+
+        versions.drain(..length.saturating_sub(keep));
+        length - versions.len()
+    }
+}
+
+/// History storage wrapper. Keys outside of tracked prefixes behave exactly like the inner storage.
+///
+/// Every write to a tracked key stores the new value, or the deletion, with its time.
+/// Versions out of retention are dropped on the next write of the key,
+/// and in the background if [`Storage::cleanup_every`] is set.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use eight::embedded::storage::{Storage, history::{self, Retention}, memory};
+/// use std::time::SystemTime;
+///
+/// let storage = history::Storage::new(memory::Storage::new())
+///   .track("config:", Retention::Versions(10));
+///
+/// storage.set("config:mode".to_string(), "fast".to_string()).await.unwrap();
+/// let before = SystemTime::now();
+///
+/// # tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+/// storage.set("config:mode".to_string(), "safe".to_string()).await.unwrap();
+///
+/// let versions = storage.history("config:mode".to_string()).await.unwrap();
+/// assert_eq!(versions.len(), 2);
+///
+/// assert_eq!(storage.get_at("config:mode".to_string(), before).await.unwrap(), "fast");
+/// assert_eq!(storage.get("config:mode".to_string()).await.unwrap(), "safe");
+///
+/// // versions are hidden from searches
+/// assert_eq!(storage.search(String::new()).await.unwrap(), vec!["config:mode".to_string()]);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Storage<S> {
+    inner: Arc<S>,
+    rules: Vec<(String, Retention)>,
+    // tracked writes and their recording are serialized, so versions are recorded in the order values are written
+    lock: Arc<Mutex<()>>,
+    cleanup: Option<Duration>,
+    started: Once,
+}
+
+impl<S> Storage<S>
+where
+    S: super::Storage,
+{
+    /// Create new history storage on top of another storage. No key is tracked until [`Storage::track`] is called.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            rules: Vec::new(),
+            lock: Default::default(),
+            cleanup: None,
+            started: Once::new(),
+        }
+    }
+
+    /// Keep history of keys starting with prefix. If more than one prefix matches a key, the first tracked one is used.
+    pub fn track<T: ToString>(mut self, prefix: T, retention: Retention) -> Self {
+        self.rules.push((prefix.to_string(), retention));
+        self
+    }
+
+    /// Drop versions out of retention periodically. Cleanup starts with the first write and stops when storage is dropped.
+    pub fn cleanup_every(mut self, interval: Duration) -> Self {
+        self.cleanup = Some(interval);
+        self
+    }
+
+    /// Get a reference to inner storage.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Drop versions out of retention now. Returns dropped version count.
+    pub async fn cleanup(&self) -> embedded::Result<usize> {
+        cleanup(self.inner.as_ref(), &self.rules, &self.lock).await
+    }
+
+    /// Run write on inner storage and record written keys, both under the lock if any of them is tracked.
+    async fn tracked<T>(
+        &self,
+        keys: &[&str],
+        write: impl Future<Output = embedded::Result<T>>,
+    ) -> embedded::Result<T> {
+        if keys.iter().any(|key| key.starts_with(HISTORY_PREFIX)) {
+            return Err(err!(embedded, ReservedKey));
+        }
+
+        if keys.iter().all(|key| retention(&self.rules, key).is_none()) {
+            return write.await;
+        }
+
+        self.start();
+        let _lock = self.lock.lock().await;
+
+        let result = write.await?;
+
+        for key in keys {
+            self.record(key).await?;
+        }
+
+        Ok(result)
+    }
+
+    /// Record current value of key, if key is tracked. Lock must be held.
+    async fn record(&self, key: &str) -> embedded::Result<()> {
+        let Some(retention) = retention(&self.rules, key) else {
+            return Ok(());
+        };
+
+        let value = if self.inner.exists(key.to_string()).await? {
+            Some(self.inner.get(key.to_string()).await?)
+        } else {
+            None
+        };
+
+        let mut versions = read(self.inner.as_ref(), key).await?;
+
+        // conditional writes can leave the value as it is
+        if versions.last().map(|version| &version.value) == Some(&value) {
+            return Ok(());
+        }
+
+        let now = SystemTime::now();
+        versions.push(Version { time: now, value });
+        retention.apply(&mut versions, now);
+
+        write(self.inner.as_ref(), key, &versions).await
+    }
+
+    fn start(&self) {
+        let Some(interval) = self.cleanup else {
+            return;
+        };
+
+        self.started.call_once(|| {
+            let cleanup = clean_periodically(
+                Arc::downgrade(&self.inner),
+                self.rules.clone(),
+                self.lock.clone(),
+                interval,
+            );
+
+            drop(tokio::spawn(cleanup));
+        });
+    }
+}
+
+#[async_trait]
+impl<S> super::Storage for Storage<S>
+where
+    S: super::Storage,
+{
+    async fn set(&self, key: String, value: String) -> embedded::Result<()> {
+        self.tracked(&[&key], self.inner.set(key.clone(), value))
+            .await
+    }
+
+    async fn get(&self, key: String) -> embedded::Result<String> {
+        self.inner.get(key).await
+    }
+
+    async fn delete(&self, key: String) -> embedded::Result<()> {
+        self.tracked(&[&key], self.inner.delete(key.clone())).await
+    }
+
+    async fn exists(&self, key: String) -> embedded::Result<bool> {
+        self.inner.exists(key).await
+    }
+
+    async fn increment(&self, key: String, num: usize) -> embedded::Result<usize> {
+        self.tracked(&[&key], self.inner.increment(key.clone(), num))
+            .await
+    }
+
+    async fn decrement(&self, key: String, num: usize) -> embedded::Result<usize> {
+        self.tracked(&[&key], self.inner.decrement(key.clone(), num))
+            .await
+    }
+
+    async fn search(&self, key: String) -> embedded::Result<Vec<String>> {
+        let mut keys = self.inner.search(key).await?;
+        keys.retain(|key| !key.starts_with(HISTORY_PREFIX));
+
+        Ok(keys)
+    }
+
+    async fn flush(&self) -> embedded::Result<()> {
+        self.inner.flush().await
+    }
+
+    async fn keys(
+        &self,
+        prefix: String,
+    ) -> embedded::Result<BoxStream<'static, embedded::Result<String>>> {
+        Ok(self
+            .inner
+            .keys(prefix)
+            .await?
+            .try_filter(|key| future::ready(!key.starts_with(HISTORY_PREFIX)))
+            .boxed())
+    }
+
+    async fn rename(&self, from: String, to: String) -> embedded::Result<()> {
+        self.tracked(&[&from, &to], self.inner.rename(from.clone(), to.clone()))
+            .await
+    }
+
+    async fn rename_nx(&self, from: String, to: String) -> embedded::Result<bool> {
+        self.tracked(
+            &[&from, &to],
+            self.inner.rename_nx(from.clone(), to.clone()),
+        )
+        .await
+    }
+
+    async fn copy(&self, from: String, to: String) -> embedded::Result<bool> {
+        self.tracked(&[&to], self.inner.copy(from, to.clone()))
+            .await
+    }
+
+    async fn set_nx(&self, key: String, value: String) -> embedded::Result<bool> {
+        self.tracked(&[&key], self.inner.set_nx(key.clone(), value))
+            .await
+    }
+
+    async fn set_xx(&self, key: String, value: String) -> embedded::Result<bool> {
+        self.tracked(&[&key], self.inner.set_xx(key.clone(), value))
+            .await
+    }
+
+    async fn get_set(&self, key: String, value: String) -> embedded::Result<Option<String>> {
+        self.tracked(&[&key], self.inner.get_set(key.clone(), value))
+            .await
+    }
+
+    async fn get_delete(&self, key: String) -> embedded::Result<String> {
+        self.tracked(&[&key], self.inner.get_delete(key.clone()))
+            .await
+    }
+
+    async fn append(&self, key: String, value: String) -> embedded::Result<usize> {
+        self.tracked(&[&key], self.inner.append(key.clone(), value))
+            .await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: String,
+        current: Option<String>,
+        value: String,
+    ) -> embedded::Result<bool> {
+        self.tracked(
+            &[&key],
+            self.inner.compare_and_swap(key.clone(), current, value),
+        )
+        .await
+    }
+
+    async fn compare_and_delete(&self, key: String, current: String) -> embedded::Result<bool> {
+        self.tracked(&[&key], self.inner.compare_and_delete(key.clone(), current))
+            .await
+    }
+
+    async fn length(&self, key: String) -> embedded::Result<usize> {
+        self.inner.length(key).await
+    }
+
+    async fn get_range(&self, key: String, start: isize, end: isize) -> embedded::Result<String> {
+        self.inner.get_range(key, start, end).await
+    }
+
+    async fn metadata(&self, key: String) -> embedded::Result<Metadata> {
+        self.inner.metadata(key).await
+    }
+
+    async fn history(&self, key: String) -> embedded::Result<Vec<Version>> {
+        let Some(retention) = retention(&self.rules, &key) else {
+            return Err(err!(embedded, HistoryDisabled));
+        };
+
+        // versions out of retention may not be cleaned yet
+        let mut versions = read(self.inner.as_ref(), &key).await?;
+        retention.apply(&mut versions, SystemTime::now());
+
+        Ok(versions)
+    }
+
+    fn metrics(&self) -> Option<super::metrics::Metrics> {
+        self.inner.metrics()
+    }
+}
+
+fn retention(rules: &[(String, Retention)], key: &str) -> Option<Retention> {
+    if key.starts_with(HISTORY_PREFIX) {
+        return None;
+    }
+
+    rules
+        .iter()
+        .find(|(prefix, _)| key.starts_with(prefix))
+        .map(|&(_, retention)| retention)
+}
+
+async fn clean_periodically<S: super::Storage>(
+    inner: Weak<S>,
+    rules: Vec<(String, Retention)>,
+    lock: Arc<Mutex<()>>,
+    interval: Duration,
+) {
+    loop {
+        time::sleep(interval).await;
+
+        let Some(inner) = inner.upgrade() else {
+            break;
+        };
+
+        // failed keys are tried again on the next round
+        let _ = cleanup(inner.as_ref(), &rules, &lock).await;
+    }
+}
+
+async fn cleanup<S: super::Storage>(
+    inner: &S,
+    rules: &[(String, Retention)],
+    lock: &Mutex<()>,
+) -> embedded::Result<usize> {
+    let mut dropped = 0;
+
+    for history_key in inner.search(HISTORY_PREFIX.to_string()).await? {
+        let key = &history_key[HISTORY_PREFIX.len()..];
+
+        // history of keys no longer tracked is left as it is
+        let Some(retention) = retention(rules, key) else {
+            continue;
+        };
+
+        let _lock = lock.lock().await;
+
+        let mut versions = read(inner, key).await?;
+        let count = retention.apply(&mut versions, SystemTime::now());
+
+        if count > 0 {
+            write(inner, key, &versions).await?;
+            dropped += count;
+        }
+    }
+
+    Ok(dropped)
+}
+
+async fn read<S: super::Storage + ?Sized>(inner: &S, key: &str) -> embedded::Result<Vec<Version>> {
+    let history_key = format!("{HISTORY_PREFIX}{key}");
+
+    if !inner.exists(history_key.clone()).await? {
+        return Ok(Vec::new());
+    }
+
+    decode(&inner.get(history_key).await?)
+}
+
+async fn write<S: super::Storage + ?Sized>(
+    inner: &S,
+    key: &str,
+    versions: &[Version],
+) -> embedded::Result<()> {
+    let history_key = format!("{HISTORY_PREFIX}{key}");
+
+    if versions.is_empty() {
+        inner.delete(history_key).await
+    } else {
+        inner.set(history_key, encode(versions)).await
+    }
+}
+
+// every version is written as `<unix milliseconds>:<byte length>:<value>`, or `<unix milliseconds>:-` for deletions
+fn encode(versions: &[Version]) -> String {
+    versions
+        .iter()
+        .map(|version| {
+            let millis = version
+                .time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+
+            match &version.value {
+                Some(value) => format!("{millis}:{}:{value}", value.len()),
+                None => format!("{millis}:-"),
+            }
+        })
+        .collect()
+}
+
+fn decode(mut raw: &str) -> embedded::Result<Vec<Version>> {
+    let mut versions = Vec::new();
+
+    while !raw.is_empty() {
+        let (millis, rest) = raw.split_once(':').ok_or(err!(embedded, CorruptedValue))?;
+        let millis = millis
+            .parse::<u64>()
+            .map_err(|_| err!(embedded, CorruptedValue))?;
+
+        let (value, rest) = if let Some(rest) = rest.strip_prefix('-') {
+            (None, rest)
+        } else {
+            let (length, rest) = rest.split_once(':').ok_or(err!(embedded, CorruptedValue))?;
+            let length = length
+                .parse::<usize>()
+                .map_err(|_| err!(embedded, CorruptedValue))?;
+
+            if !rest.is_char_boundary(length) {
+                return Err(err!(embedded, CorruptedValue));
+            }
+
+            let (value, rest) = rest.split_at(length);
+            (Some(value.to_string()), rest)
+        };
+
+        versions.push(Version {
+            time: UNIX_EPOCH + Duration::from_millis(millis),
+            value,
+        });
+
+        raw = rest;
+    }
+
+    Ok(versions)
+}
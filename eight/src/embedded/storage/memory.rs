@@ -1,14 +1,56 @@
 //! Official in-memory storage implementation for eight.
 
+use super::Metadata;
 use crate::{embedded, err};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    time::SystemTime,
+};
 use tokio::sync::RwLock;
 
 /// In-memory storage. Preferred for temporary key-values (like cache).
+///
+/// Keeps creation and last update time of every key, see [`Storage::metadata`](super::Storage::metadata).
 #[derive(Debug, Default)]
 pub struct Storage {
-    values: RwLock<HashMap<String, String>>,
+    values: RwLock<HashMap<String, Value>>,
+}
+
+#[derive(Debug)]
+struct Value {
+    value: String,
+    created: SystemTime,
+    updated: SystemTime,
+}
+
+impl Value {
+    fn new(value: String) -> Self {
+        let now = SystemTime::now();
+
+        Self {
+            value,
+            created: now,
+            updated: now,
+        }
+    }
+
+    // creation time is kept, like files keep it when renamed
+    fn update(&mut self, value: String) -> String {
+        self.updated = SystemTime::now();
+        std::mem::replace(&mut self.value, value)
+    }
+}
+
+// update value if key exists, create otherwise. returns previous value
+fn upsert(values: &mut HashMap<String, Value>, key: String, value: String) -> Option<String> {
+    match values.entry(key) {
+        Entry::Occupied(mut entry) => Some(entry.get_mut().update(value)),
+        Entry::Vacant(entry) => {
+            entry.insert(Value::new(value));
+            None
+        }
+    }
 }
 
 impl Storage {
@@ -23,13 +65,13 @@ impl Storage {
 #[async_trait]
 impl super::Storage for Storage {
     async fn set(&self, key: String, value: String) -> embedded::Result<()> {
-        self.values.write().await.insert(key, value);
+        upsert(&mut *self.values.write().await, key, value);
         Ok(())
     }
 
     async fn get(&self, key: String) -> embedded::Result<String> {
         if let Some(value) = self.values.read().await.get(&key) {
-            Ok(value.to_owned())
+            Ok(value.value.to_owned())
         } else {
             Err(err!(embedded, GetKeyFail))
         }
@@ -84,4 +126,127 @@ impl super::Storage for Storage {
         self.values.write().await.clear();
         Ok(())
     }
+
+    async fn set_nx(&self, key: String, value: String) -> embedded::Result<bool> {
+        match self.values.write().await.entry(key) {
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(entry) => {
+                entry.insert(Value::new(value));
+                Ok(true)
+            }
+        }
+    }
+
+    async fn set_xx(&self, key: String, value: String) -> embedded::Result<bool> {
+        match self.values.write().await.get_mut(&key) {
+            Some(current) => {
+                current.update(value);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn get_set(&self, key: String, value: String) -> embedded::Result<Option<String>> {
+        Ok(upsert(&mut *self.values.write().await, key, value))
+    }
+
+    async fn get_delete(&self, key: String) -> embedded::Result<String> {
+        self.values
+            .write()
+            .await
+            .remove(&key)
+            .map(|value| value.value)
+            .ok_or(err!(embedded, GetKeyFail))
+    }
+
+    async fn append(&self, key: String, value: String) -> embedded::Result<usize> {
+        let mut values = self.values.write().await;
+        let current = values
+            .entry(key)
+            .and_modify(|current| current.updated = SystemTime::now())
+            .or_insert_with(|| Value::new(String::new()));
+
+        current.value.push_str(&value);
+        Ok(current.value.chars().count())
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: String,
+        current: Option<String>,
+        value: String,
+    ) -> embedded::Result<bool> {
+        let mut values = self.values.write().await;
+
+        if values.get(&key).map(|actual| &actual.value) != current.as_ref() {
+            return Ok(false);
+        }
+
+        upsert(&mut values, key, value);
+        Ok(true)
+    }
+
+    async fn compare_and_delete(&self, key: String, current: String) -> embedded::Result<bool> {
+        let mut values = self.values.write().await;
+
+        if values.get(&key).map(|actual| &actual.value) != Some(&current) {
+            return Ok(false);
+        }
+
+        values.remove(&key);
+        Ok(true)
+    }
+
+    async fn rename(&self, from: String, to: String) -> embedded::Result<()> {
+        let mut values = self.values.write().await;
+        let value = values.remove(&from).ok_or(err!(embedded, GetKeyFail))?;
+
+        values.insert(to, value);
+        Ok(())
+    }
+
+    async fn rename_nx(&self, from: String, to: String) -> embedded::Result<bool> {
+        let mut values = self.values.write().await;
+
+        if !values.contains_key(&from) {
+            return Err(err!(embedded, GetKeyFail));
+        }
+
+        if values.contains_key(&to) {
+            return Ok(false);
+        }
+
+        if let Some(value) = values.remove(&from) {
+            values.insert(to, value);
+        }
+
+        Ok(true)
+    }
+
+    async fn copy(&self, from: String, to: String) -> embedded::Result<bool> {
+        let mut values = self.values.write().await;
+        let value = values.get(&from).ok_or(err!(embedded, GetKeyFail))?;
+
+        if values.contains_key(&to) {
+            return Ok(false);
+        }
+
+        let value = Value::new(value.value.to_owned());
+        values.insert(to, value);
+
+        Ok(true)
+    }
+
+    async fn metadata(&self, key: String) -> embedded::Result<Metadata> {
+        let values = self.values.read().await;
+        let value = values.get(&key).ok_or(err!(embedded, GetKeyFail))?;
+
+        Ok(Metadata {
+            size: value.value.len(),
+            created: Some(value.created),
+            updated: Some(value.updated),
+            ttl: None,
+        })
+    }
 }
@@ -0,0 +1,350 @@
+//! Instrumented storage wrapper for eight.
+//!
+//! Wraps any [`Storage`] and records how many times each method is called, how many of the calls failed and how long they took.
+//! Recorded numbers can be read with [`Storage::metrics`], [`Server::stats`] includes them as well.
+//!
+//! [`Storage`]: super::Storage
+//! [`Storage::metrics`]: super::Storage::metrics
+//! [`Server::stats`]: crate::embedded::server::Server::stats
+
+use super::Operation;
+use crate::embedded;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Upper bounds of latency histogram buckets. Slower calls are counted in an extra, unbounded bucket.
+pub const BUCKETS: [Duration; 16] = [
+    Duration::from_micros(50),
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(5),
+];
+
+/// Latency histogram with [`BUCKETS`] as bucket bounds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [u64; BUCKETS.len() + 1],
+    sum: Duration,
+}
+
+impl Histogram {
+    /// How many calls are recorded.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Total time spent in recorded calls.
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// Average latency of recorded calls. [`None`] if there is no call.
+    pub fn mean(&self) -> Option<Duration> {
+        let count = self.count();
+
+        if count == 0 {
+            None
+        } else {
+            Some(self.sum.div_f64(count as f64))
+        }
+    }
+
+    /// Cumulative count of calls for each bucket, like Prometheus does. Last bucket has no upper bound.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        let bounds = BUCKETS.iter().copied().map(Some).chain([None]);
+
+        bounds.zip(self.counts.iter().scan(0, |total, count| {
+            *total += count;
+            Some(*total)
+        }))
+    }
+}
+
+/// Numbers recorded for one operation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// How many times operation is called.
+    pub calls: u64,
+    /// How many of the calls returned an error.
+    pub errors: u64,
+    /// How long calls took.
+    pub latency: Histogram,
+}
+
+/// Numbers recorded for every operation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metrics {
+    operations: [Summary; Operation::ALL.len()],
+}
+
+impl Metrics {
+    /// Numbers recorded for given operation.
+    pub fn operation(&self, operation: Operation) -> &Summary {
+        &self.operations[operation as usize]
+    }
+
+    /// Every operation with its numbers.
+    pub fn iter(&self) -> impl Iterator<Item = (Operation, &Summary)> {
+        Operation::ALL.into_iter().zip(self.operations.iter())
+    }
+}
+
+/// Lock-free counters behind a [`Summary`].
+#[derive(Debug, Default)]
+pub(crate) struct Recorder {
+    calls: AtomicU64,
+    errors: AtomicU64,
+    nanos: AtomicU64,
+    counts: [AtomicU64; BUCKETS.len() + 1],
+}
+
+impl Recorder {
+    pub fn record(&self, elapsed: Duration, failed: bool) {
+        let bucket = BUCKETS
+            .iter()
+            .position(|bound| elapsed <= *bound)
+            .unwrap_or(BUCKETS.len());
+
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.nanos.fetch_add(
+            elapsed.as_nanos().try_into().unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+
+        if failed {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn summary(&self) -> Summary {
+        Summary {
+            calls: self.calls.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            latency: Histogram {
+                counts: std::array::from_fn(|i| self.counts[i].load(Ordering::Relaxed)),
+                sum: Duration::from_nanos(self.nanos.load(Ordering::Relaxed)),
+            },
+        }
+    }
+}
+
+/// Instrumented storage wrapper. Behaves exactly like the inner storage while recording every call.
+///
+/// Latency of [`Storage::keys`] covers creating the stream, not consuming it.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use eight::embedded::storage::{Storage, Operation, metrics, memory};
+///
+/// let storage = metrics::Storage::new(memory::Storage::new());
+///
+/// storage.set("user".to_string(), "bob".to_string()).await.unwrap();
+/// storage.get("user".to_string()).await.unwrap();
+/// storage.get("missing".to_string()).await.unwrap_err();
+///
+/// let metrics = storage.metrics().unwrap();
+/// let get = metrics.operation(Operation::Get);
+///
+/// assert_eq!(get.calls, 2);
+/// assert_eq!(get.errors, 1);
+/// assert_eq!(get.latency.count(), 2);
+/// assert_eq!(metrics.operation(Operation::Set).calls, 1);
+/// # });
+/// ```
+///
+/// [`Storage::keys`]: super::Storage::keys
+#[derive(Debug)]
+pub struct Storage<S> {
+    inner: S,
+    recorders: [Recorder; Operation::ALL.len()],
+}
+
+impl<S> Storage<S>
+where
+    S: super::Storage,
+{
+    /// Create new instrumented storage on top of another storage.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            recorders: Default::default(),
+        }
+    }
+
+    /// Returns inner storage. Calls made on it directly are not recorded.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consumes instrumented storage and returns inner storage.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    async fn record<T, F>(&self, operation: Operation, future: F) -> embedded::Result<T>
+    where
+        F: std::future::Future<Output = embedded::Result<T>>,
+    {
+        let start = Instant::now();
+        let result = future.await;
+
+        self.recorders[operation as usize].record(start.elapsed(), result.is_err());
+        result
+    }
+}
+
+#[async_trait]
+impl<S> super::Storage for Storage<S>
+where
+    S: super::Storage,
+{
+    async fn set(&self, key: String, value: String) -> embedded::Result<()> {
+        self.record(Operation::Set, self.inner.set(key, value))
+            .await
+    }
+
+    async fn get(&self, key: String) -> embedded::Result<String> {
+        self.record(Operation::Get, self.inner.get(key)).await
+    }
+
+    async fn delete(&self, key: String) -> embedded::Result<()> {
+        self.record(Operation::Delete, self.inner.delete(key)).await
+    }
+
+    async fn exists(&self, key: String) -> embedded::Result<bool> {
+        self.record(Operation::Exists, self.inner.exists(key)).await
+    }
+
+    async fn increment(&self, key: String, num: usize) -> embedded::Result<usize> {
+        self.record(Operation::Increment, self.inner.increment(key, num))
+            .await
+    }
+
+    async fn decrement(&self, key: String, num: usize) -> embedded::Result<usize> {
+        self.record(Operation::Decrement, self.inner.decrement(key, num))
+            .await
+    }
+
+    async fn search(&self, key: String) -> embedded::Result<Vec<String>> {
+        self.record(Operation::Search, self.inner.search(key)).await
+    }
+
+    async fn flush(&self) -> embedded::Result<()> {
+        self.record(Operation::Flush, self.inner.flush()).await
+    }
+
+    async fn keys(
+        &self,
+        prefix: String,
+    ) -> embedded::Result<BoxStream<'static, embedded::Result<String>>> {
+        self.record(Operation::Keys, self.inner.keys(prefix)).await
+    }
+
+    async fn rename(&self, from: String, to: String) -> embedded::Result<()> {
+        self.record(Operation::Rename, self.inner.rename(from, to))
+            .await
+    }
+
+    async fn rename_nx(&self, from: String, to: String) -> embedded::Result<bool> {
+        self.record(Operation::RenameNx, self.inner.rename_nx(from, to))
+            .await
+    }
+
+    async fn copy(&self, from: String, to: String) -> embedded::Result<bool> {
+        self.record(Operation::Copy, self.inner.copy(from, to))
+            .await
+    }
+
+    async fn set_nx(&self, key: String, value: String) -> embedded::Result<bool> {
+        self.record(Operation::SetNx, self.inner.set_nx(key, value))
+            .await
+    }
+
+    async fn set_xx(&self, key: String, value: String) -> embedded::Result<bool> {
+        self.record(Operation::SetXx, self.inner.set_xx(key, value))
+            .await
+    }
+
+    async fn get_set(&self, key: String, value: String) -> embedded::Result<Option<String>> {
+        self.record(Operation::GetSet, self.inner.get_set(key, value))
+            .await
+    }
+
+    async fn get_delete(&self, key: String) -> embedded::Result<String> {
+        self.record(Operation::GetDelete, self.inner.get_delete(key))
+            .await
+    }
+
+    async fn append(&self, key: String, value: String) -> embedded::Result<usize> {
+        self.record(Operation::Append, self.inner.append(key, value))
+            .await
+    }
+
+    async fn length(&self, key: String) -> embedded::Result<usize> {
+        self.record(Operation::Length, self.inner.length(key)).await
+    }
+
+    async fn get_range(&self, key: String, start: isize, end: isize) -> embedded::Result<String> {
+        self.record(Operation::GetRange, self.inner.get_range(key, start, end))
+            .await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: String,
+        current: Option<String>,
+        value: String,
+    ) -> embedded::Result<bool> {
+        self.record(
+            Operation::CompareAndSwap,
+            self.inner.compare_and_swap(key, current, value),
+        )
+        .await
+    }
+
+    async fn compare_and_delete(&self, key: String, current: String) -> embedded::Result<bool> {
+        self.record(
+            Operation::CompareAndDelete,
+            self.inner.compare_and_delete(key, current),
+        )
+        .await
+    }
+
+    async fn metadata(&self, key: String) -> embedded::Result<super::Metadata> {
+        self.record(Operation::Metadata, self.inner.metadata(key))
+            .await
+    }
+
+    async fn history(&self, key: String) -> embedded::Result<Vec<super::Version>> {
+        self.record(Operation::History, self.inner.history(key))
+            .await
+    }
+
+    async fn get_at(&self, key: String, time: SystemTime) -> embedded::Result<String> {
+        self.record(Operation::GetAt, self.inner.get_at(key, time))
+            .await
+    }
+
+    fn metrics(&self) -> Option<Metrics> {
+        Some(Metrics {
+            operations: std::array::from_fn(|i| self.recorders[i].summary()),
+        })
+    }
+}
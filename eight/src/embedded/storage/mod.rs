@@ -8,7 +8,145 @@ pub mod filesystem;
 #[cfg_attr(docsrs, doc(cfg(feature = "in-memory-storage")))]
 pub mod memory;
 
+#[cfg(feature = "encrypted-storage")]
+#[cfg_attr(docsrs, doc(cfg(feature = "encrypted-storage")))]
+pub mod encrypted;
+
+#[cfg(feature = "sqlite-storage")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sqlite-storage")))]
+pub mod sqlite;
+
+#[cfg(feature = "chaos-storage")]
+#[cfg_attr(docsrs, doc(cfg(feature = "chaos-storage")))]
+pub mod chaos;
+
+#[cfg(feature = "history-storage")]
+#[cfg_attr(docsrs, doc(cfg(feature = "history-storage")))]
+pub mod history;
+
+pub mod metrics;
+
+#[cfg(any(test, feature = "storage-testing"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "storage-testing")))]
+pub mod testing;
+
+#[cfg(test)]
+mod tests;
+
 pub use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use std::time::{Duration, SystemTime};
+
+/// Methods of [`Storage`] trait, used by wrappers to tell operations apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Set,
+    Get,
+    Delete,
+    Exists,
+    Increment,
+    Decrement,
+    Search,
+    Flush,
+    Keys,
+    Rename,
+    RenameNx,
+    Copy,
+    SetNx,
+    SetXx,
+    GetSet,
+    GetDelete,
+    Append,
+    Length,
+    GetRange,
+    CompareAndSwap,
+    CompareAndDelete,
+    Metadata,
+    History,
+    GetAt,
+}
+
+impl Operation {
+    /// Every operation.
+    pub const ALL: [Operation; 24] = [
+        Operation::Set,
+        Operation::Get,
+        Operation::Delete,
+        Operation::Exists,
+        Operation::Increment,
+        Operation::Decrement,
+        Operation::Search,
+        Operation::Flush,
+        Operation::Keys,
+        Operation::Rename,
+        Operation::RenameNx,
+        Operation::Copy,
+        Operation::SetNx,
+        Operation::SetXx,
+        Operation::GetSet,
+        Operation::GetDelete,
+        Operation::Append,
+        Operation::Length,
+        Operation::GetRange,
+        Operation::CompareAndSwap,
+        Operation::CompareAndDelete,
+        Operation::Metadata,
+        Operation::History,
+        Operation::GetAt,
+    ];
+
+    /// Name of the trait method.
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Set => "set",
+            Operation::Get => "get",
+            Operation::Delete => "delete",
+            Operation::Exists => "exists",
+            Operation::Increment => "increment",
+            Operation::Decrement => "decrement",
+            Operation::Search => "search",
+            Operation::Flush => "flush",
+            Operation::Keys => "keys",
+            Operation::Rename => "rename",
+            Operation::RenameNx => "rename_nx",
+            Operation::Copy => "copy",
+            Operation::SetNx => "set_nx",
+            Operation::SetXx => "set_xx",
+            Operation::GetSet => "get_set",
+            Operation::GetDelete => "get_delete",
+            Operation::Append => "append",
+            Operation::Length => "length",
+            Operation::GetRange => "get_range",
+            Operation::CompareAndSwap => "compare_and_swap",
+            Operation::CompareAndDelete => "compare_and_delete",
+            Operation::Metadata => "metadata",
+            Operation::History => "history",
+            Operation::GetAt => "get_at",
+        }
+    }
+}
+
+/// Metadata of a key, see [`Storage::metadata`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Size of value in bytes.
+    pub size: usize,
+    /// Time key is created, if storage keeps it.
+    pub created: Option<SystemTime>,
+    /// Time value is last updated, if storage keeps it.
+    pub updated: Option<SystemTime>,
+    /// Remaining lifetime of key. Reserved for expiring keys, storages don't expire keys yet.
+    pub ttl: Option<Duration>,
+}
+
+/// Version of a key, see [`Storage::history`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    /// Time version is written.
+    pub time: SystemTime,
+    /// Value of key, [`None`] if key is deleted.
+    pub value: Option<String>,
+}
 
 /// Simple storage utility.
 ///
@@ -151,4 +289,389 @@ pub trait Storage: Send + Sync + 'static {
     /// # });
     /// ```
     async fn flush(&self) -> super::Result<()>;
+
+    /// Stream keys starting with given prefix.
+    ///
+    /// Default implementation collects keys with [`Storage::search`]. Storages holding large keyspaces should override it, so keys never have to fit in memory.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # use eight::embedded::storage::{Storage, filesystem};
+    /// use futures::TryStreamExt;
+    ///
+    /// # let storage = filesystem::Storage::from_path("./keys_stream_storage_test");
+    /// for i in 1..100 {
+    ///   storage.set(format!("result{}", i), "test".to_string()).await.unwrap();
+    /// }
+    ///
+    /// let keys = storage.keys("res".to_string()).await.unwrap();
+    /// let results = keys.try_collect::<Vec<_>>().await.unwrap();
+    /// assert_eq!(results.len(), 99);
+    ///
+    /// # storage.flush().await;
+    /// # });
+    /// ```
+    async fn keys(
+        &self,
+        prefix: String,
+    ) -> super::Result<BoxStream<'static, super::Result<String>>> {
+        let keys = self.search(prefix).await?;
+        Ok(stream::iter(keys.into_iter().map(Ok)).boxed())
+    }
+
+    /// Move value of a key to another key, replacing its value. Fails if source key doesn't exist.
+    ///
+    /// Default implementation gets, sets and deletes, so it is not atomic. Storages should override it when they can.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # use eight::embedded::storage::{Storage, filesystem};
+    /// # let storage = filesystem::Storage::from_path("./rename_storage_test");
+    /// storage.set("bob".to_string(), "some session id".to_string()).await.unwrap();
+    /// storage.rename("bob".to_string(), "alice".to_string()).await.unwrap();
+    ///
+    /// assert_eq!(storage.exists("bob".to_string()).await, Ok(false));
+    /// assert_eq!(storage.get("alice".to_string()).await.unwrap(), "some session id");
+    ///
+    /// # storage.flush().await;
+    /// # });
+    /// ```
+    async fn rename(&self, from: String, to: String) -> super::Result<()> {
+        let value = self.get(from.clone()).await?;
+
+        if from != to {
+            self.set(to, value).await?;
+            self.delete(from).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Same with [`Storage::rename`], but nothing is changed if target key exists. Returns true if key is renamed.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # use eight::embedded::storage::{Storage, filesystem};
+    /// # let storage = filesystem::Storage::from_path("./rename_nx_storage_test");
+    /// storage.set("bob".to_string(), "1".to_string()).await.unwrap();
+    /// storage.set("alice".to_string(), "2".to_string()).await.unwrap();
+    ///
+    /// assert_eq!(storage.rename_nx("bob".to_string(), "alice".to_string()).await, Ok(false));
+    /// assert_eq!(storage.rename_nx("bob".to_string(), "carol".to_string()).await, Ok(true));
+    ///
+    /// # storage.flush().await;
+    /// # });
+    /// ```
+    async fn rename_nx(&self, from: String, to: String) -> super::Result<bool> {
+        let value = self.get(from.clone()).await?;
+
+        if self.exists(to.clone()).await? {
+            return Ok(false);
+        }
+
+        self.set(to, value).await?;
+        self.delete(from).await?;
+
+        Ok(true)
+    }
+
+    /// Copy value of a key to another key, nothing is changed if target key exists. Returns true if key is copied.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # use eight::embedded::storage::{Storage, filesystem};
+    /// # let storage = filesystem::Storage::from_path("./copy_storage_test");
+    /// storage.set("template".to_string(), "hello".to_string()).await.unwrap();
+    ///
+    /// assert_eq!(storage.copy("template".to_string(), "greeting".to_string()).await, Ok(true));
+    /// assert_eq!(storage.get("template".to_string()).await.unwrap(), "hello");
+    /// assert_eq!(storage.get("greeting".to_string()).await.unwrap(), "hello");
+    ///
+    /// # storage.flush().await;
+    /// # });
+    /// ```
+    async fn copy(&self, from: String, to: String) -> super::Result<bool> {
+        let value = self.get(from).await?;
+
+        if self.exists(to.clone()).await? {
+            return Ok(false);
+        }
+
+        self.set(to, value).await?;
+        Ok(true)
+    }
+
+    /// Set a key only if it doesn't exist. Returns true if value is set.
+    ///
+    /// Default implementations of conditional and compound methods are made of other methods, so they are not atomic.
+    /// Storages should override them when they can.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # use eight::embedded::storage::{Storage, filesystem};
+    /// # let storage = filesystem::Storage::from_path("./set_nx_storage_test");
+    /// assert_eq!(storage.set_nx("lock".to_string(), "worker 1".to_string()).await, Ok(true));
+    /// assert_eq!(storage.set_nx("lock".to_string(), "worker 2".to_string()).await, Ok(false));
+    /// assert_eq!(storage.get("lock".to_string()).await.unwrap(), "worker 1");
+    ///
+    /// # storage.flush().await;
+    /// # });
+    /// ```
+    async fn set_nx(&self, key: String, value: String) -> super::Result<bool> {
+        if self.exists(key.clone()).await? {
+            return Ok(false);
+        }
+
+        self.set(key, value).await?;
+        Ok(true)
+    }
+
+    /// Delete a key only if its value is the expected one. Returns true if key is deleted.
+    ///
+    /// Default implementation is not atomic, storages should override it along with [`Storage::compare_and_swap`].
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # use eight::embedded::storage::{Storage, memory};
+    /// let storage = memory::Storage::new();
+    /// storage.set("state".to_string(), "1".to_string()).await.unwrap();
+    ///
+    /// assert_eq!(storage.compare_and_delete("state".to_string(), "2".to_string()).await, Ok(false));
+    /// assert_eq!(storage.compare_and_delete("state".to_string(), "1".to_string()).await, Ok(true));
+    /// assert_eq!(storage.exists("state".to_string()).await, Ok(false));
+    /// # });
+    /// ```
+    async fn compare_and_delete(&self, key: String, current: String) -> super::Result<bool> {
+        if !self.exists(key.clone()).await? || self.get(key.clone()).await? != current {
+            return Ok(false);
+        }
+
+        self.delete(key).await?;
+        Ok(true)
+    }
+
+    /// Set a key only if it exists. Returns true if value is set.
+    async fn set_xx(&self, key: String, value: String) -> super::Result<bool> {
+        if !self.exists(key.clone()).await? {
+            return Ok(false);
+        }
+
+        self.set(key, value).await?;
+        Ok(true)
+    }
+
+    /// Set a key and return its previous value. [`None`] if key didn't exist.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # use eight::embedded::storage::{Storage, filesystem};
+    /// # let storage = filesystem::Storage::from_path("./get_set_storage_test");
+    /// assert_eq!(storage.get_set("state".to_string(), "idle".to_string()).await, Ok(None));
+    /// assert_eq!(storage.get_set("state".to_string(), "busy".to_string()).await, Ok(Some("idle".to_string())));
+    ///
+    /// # storage.flush().await;
+    /// # });
+    /// ```
+    async fn get_set(&self, key: String, value: String) -> super::Result<Option<String>> {
+        let old = if self.exists(key.clone()).await? {
+            Some(self.get(key.clone()).await?)
+        } else {
+            None
+        };
+
+        self.set(key, value).await?;
+        Ok(old)
+    }
+
+    /// Delete a key and return its value. Fails if key doesn't exist.
+    async fn get_delete(&self, key: String) -> super::Result<String> {
+        let value = self.get(key.clone()).await?;
+
+        self.delete(key).await?;
+        Ok(value)
+    }
+
+    /// Append to value of a key, missing key is created. Returns new length of value in characters.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # use eight::embedded::storage::{Storage, filesystem};
+    /// # let storage = filesystem::Storage::from_path("./append_storage_test");
+    /// assert_eq!(storage.append("log".to_string(), "login;".to_string()).await, Ok(6));
+    /// assert_eq!(storage.append("log".to_string(), "logout;".to_string()).await, Ok(13));
+    /// assert_eq!(storage.get("log".to_string()).await.unwrap(), "login;logout;");
+    ///
+    /// # storage.flush().await;
+    /// # });
+    /// ```
+    async fn append(&self, key: String, value: String) -> super::Result<usize> {
+        let mut current = if self.exists(key.clone()).await? {
+            self.get(key.clone()).await?
+        } else {
+            String::new()
+        };
+
+        current.push_str(&value);
+        let length = current.chars().count();
+
+        self.set(key, current).await?;
+        Ok(length)
+    }
+
+    /// Length of value in characters. Missing key has zero (0) length.
+    async fn length(&self, key: String) -> super::Result<usize> {
+        if !self.exists(key.clone()).await? {
+            return Ok(0);
+        }
+
+        Ok(self.get(key).await?.chars().count())
+    }
+
+    /// Part of value between start and end characters, both included. Negative positions count from the end, `-1` is the last character.
+    /// Missing key is an empty value.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # use eight::embedded::storage::{Storage, filesystem};
+    /// # let storage = filesystem::Storage::from_path("./get_range_storage_test");
+    /// storage.set("greeting".to_string(), "hello world".to_string()).await.unwrap();
+    ///
+    /// assert_eq!(storage.get_range("greeting".to_string(), 0, 4).await.unwrap(), "hello");
+    /// assert_eq!(storage.get_range("greeting".to_string(), -5, -1).await.unwrap(), "world");
+    /// assert_eq!(storage.get_range("greeting".to_string(), 6, 100).await.unwrap(), "world");
+    ///
+    /// # storage.flush().await;
+    /// # });
+    /// ```
+    async fn get_range(&self, key: String, start: isize, end: isize) -> super::Result<String> {
+        if !self.exists(key.clone()).await? {
+            return Ok(String::new());
+        }
+
+        Ok(range(&self.get(key).await?, start, end))
+    }
+
+    /// Set a key only if its value is the expected one, [`None`] expects key to not exist. Returns true if value is set.
+    ///
+    /// Default implementation is not atomic, storages should override it so concurrent callers can build on it.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # use eight::embedded::storage::{Storage, filesystem};
+    /// # let storage = filesystem::Storage::from_path("./compare_and_swap_storage_test");
+    /// assert_eq!(storage.compare_and_swap("state".to_string(), None, "1".to_string()).await, Ok(true));
+    /// assert_eq!(storage.compare_and_swap("state".to_string(), None, "2".to_string()).await, Ok(false));
+    /// assert_eq!(storage.compare_and_swap("state".to_string(), Some("1".to_string()), "2".to_string()).await, Ok(true));
+    /// assert_eq!(storage.get("state".to_string()).await.unwrap(), "2");
+    ///
+    /// # storage.flush().await;
+    /// # });
+    /// ```
+    async fn compare_and_swap(
+        &self,
+        key: String,
+        current: Option<String>,
+        value: String,
+    ) -> super::Result<bool> {
+        let actual = if self.exists(key.clone()).await? {
+            Some(self.get(key.clone()).await?)
+        } else {
+            None
+        };
+
+        if actual != current {
+            return Ok(false);
+        }
+
+        self.set(key, value).await?;
+        Ok(true)
+    }
+
+    /// Size and timestamps of key, fails if key doesn't exist.
+    ///
+    /// Default implementation only knows the size of value, timestamps are [`None`].
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # use eight::embedded::storage::{Storage, memory};
+    /// let storage = memory::Storage::new();
+    /// storage.set("greeting".to_string(), "hello".to_string()).await.unwrap();
+    ///
+    /// let metadata = storage.metadata("greeting".to_string()).await.unwrap();
+    ///
+    /// assert_eq!(metadata.size, 5);
+    /// assert_eq!(metadata.created, metadata.updated);
+    /// # });
+    /// ```
+    async fn metadata(&self, key: String) -> super::Result<Metadata> {
+        let value = self.get(key).await?;
+
+        Ok(Metadata {
+            size: value.len(),
+            ..Default::default()
+        })
+    }
+
+    /// Versions of key, oldest first. Only storages keeping history, like [`history::Storage`], return versions.
+    ///
+    /// Default implementation fails with [`HistoryDisabled`]. Wrappers should return history of the storage they wrap.
+    ///
+    /// [`history::Storage`]: history::Storage
+    /// [`HistoryDisabled`]: super::Error::HistoryDisabled
+    async fn history(&self, key: String) -> super::Result<Vec<Version>> {
+        let _ = key;
+        Err(crate::err!(embedded, HistoryDisabled))
+    }
+
+    /// Value of key at given time, read from [`Storage::history`].
+    /// Fails with [`GetKeyFail`] if key is deleted at that time, and with [`VersionMissing`] if no version is kept for that time.
+    ///
+    /// [`GetKeyFail`]: super::Error::GetKeyFail
+    /// [`VersionMissing`]: super::Error::VersionMissing
+    async fn get_at(&self, key: String, time: SystemTime) -> super::Result<String> {
+        let versions = self.history(key).await?;
+
+        match versions
+            .into_iter()
+            .rev()
+            .find(|version| version.time <= time)
+        {
+            Some(Version {
+                value: Some(value), ..
+            }) => Ok(value),
+            Some(_) => Err(crate::err!(embedded, GetKeyFail)),
+            None => Err(crate::err!(embedded, VersionMissing)),
+        }
+    }
+
+    /// Metrics recorded by storage, see [`metrics::Storage`].
+    ///
+    /// Default implementation returns [`None`]. Wrappers should return metrics of the storage they wrap.
+    fn metrics(&self) -> Option<metrics::Metrics> {
+        None
+    }
+}
+
+// characters between start and end, negative positions count from the end
+fn range(value: &str, start: isize, end: isize) -> String {
+    let length = value.chars().count() as isize;
+    let resolve = |position: isize| {
+        if position < 0 {
+            (length + position).max(0)
+        } else {
+            position
+        }
+    };
+
+    let (start, end) = (resolve(start), resolve(end).min(length - 1));
+
+    if start > end {
+        return String::new();
+    }
+
+    value
+        .chars()
+        .skip(start as usize)
+        .take((end - start + 1) as usize)
+        .collect()
 }
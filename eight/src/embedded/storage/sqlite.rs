@@ -0,0 +1,515 @@
+//! Official SQLite based storage implementation for eight.
+//!
+//! Every key-value is kept in a single table of one database file. Database runs in WAL mode for durability,
+//! but all calls share one connection, so they run one at a time. Every blocking call is moved off the async runtime.
+
+use crate::{embedded, err};
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+const KEYS_PAGE_SIZE: usize = 256;
+
+/// SQLite based storage. Preferred when you need a single-file, transactional storage on disk.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use eight::embedded::storage::{Storage, sqlite};
+///
+/// let storage = sqlite::Storage::open("./sqlite_storage_test.db").unwrap();
+///
+/// for key in ["user:1", "user:2", "session"] {
+///   storage.set(key.to_string(), "10".to_string()).await.unwrap();
+/// }
+///
+/// assert_eq!(storage.increment("user:1".to_string(), 5).await.unwrap(), 15);
+///
+/// let results = storage.search("user:".to_string()).await.unwrap();
+/// assert_eq!(results, vec!["user:1".to_string(), "user:2".to_string()]);
+///
+/// # storage.flush().await;
+/// # drop(storage);
+/// # for suffix in ["", "-wal", "-shm"] { std::fs::remove_file(format!("./sqlite_storage_test.db{suffix}")).ok(); }
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Storage {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl Storage {
+    /// Open database file from path, it is created if it doesn't exist.
+    ///
+    /// ```no_run
+    /// use eight::embedded::storage::sqlite;
+    ///
+    /// sqlite::Storage::open("/tmp/test.db").unwrap();
+    /// ```
+    pub fn open<T>(path: T) -> embedded::Result<Self>
+    where
+        T: AsRef<Path>,
+    {
+        let connection = Connection::open(path).map_err(|_| err!(embedded, DatabaseOpenFail))?;
+
+        let mode: String = connection
+            .query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))
+            .map_err(|_| err!(embedded, DatabaseOpenFail))?;
+
+        if !mode.eq_ignore_ascii_case("wal") {
+            return Err(err!(embedded, DatabaseOpenFail));
+        }
+
+        Self::setup(connection)
+    }
+
+    /// Open a private in-memory database. Everything is lost when storage is dropped.
+    ///
+    /// ```
+    /// use eight::embedded::storage::sqlite;
+    ///
+    /// sqlite::Storage::open_in_memory().unwrap();
+    /// ```
+    pub fn open_in_memory() -> embedded::Result<Self> {
+        let connection =
+            Connection::open_in_memory().map_err(|_| err!(embedded, DatabaseOpenFail))?;
+        Self::setup(connection)
+    }
+
+    fn setup(connection: Connection) -> embedded::Result<Self> {
+        // primary key is the index used by prefix search
+        connection
+            .execute_batch(
+                "PRAGMA synchronous = NORMAL;
+                 CREATE TABLE IF NOT EXISTS eight (
+                     key TEXT PRIMARY KEY NOT NULL,
+                     value TEXT NOT NULL
+                 ) WITHOUT ROWID;",
+            )
+            .map_err(|_| err!(embedded, DatabaseOpenFail))?;
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    async fn run<T, F>(&self, function: F) -> embedded::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> embedded::Result<T> + Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection
+                .lock()
+                .map_err(|_| err!(embedded, DatabaseQueryFail))?;
+
+            function(&mut connection)
+        })
+        .await
+        .map_err(|_| err!(embedded, DatabaseQueryFail))?
+    }
+
+    async fn update<F>(&self, key: String, function: F) -> embedded::Result<usize>
+    where
+        F: FnOnce(usize) -> Option<usize> + Send + 'static,
+    {
+        self.run(move |connection| {
+            // immediate transaction takes the write lock before reading, so no one can change value in between
+            let transaction = connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(|_| err!(embedded, SetKeyFail))?;
+
+            let raw: String = transaction
+                .query_row("SELECT value FROM eight WHERE key = ?1", [&key], |row| {
+                    row.get(0)
+                })
+                .map_err(|_| err!(embedded, GetKeyFail))?;
+
+            let new = raw
+                .parse::<usize>()
+                .ok()
+                .and_then(function)
+                .ok_or(err!(embedded, UIntParseFail))?;
+
+            transaction
+                .execute(
+                    "UPDATE eight SET value = ?2 WHERE key = ?1",
+                    params![key, new.to_string()],
+                )
+                .and_then(|_| transaction.commit())
+                .map_err(|_| err!(embedded, SetKeyFail))?;
+
+            Ok(new)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl super::Storage for Storage {
+    async fn set(&self, key: String, value: String) -> embedded::Result<()> {
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "INSERT INTO eight (key, value) VALUES (?1, ?2)
+                     ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                    params![key, value],
+                )
+                .map_err(|_| err!(embedded, SetKeyFail))?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get(&self, key: String) -> embedded::Result<String> {
+        self.run(move |connection| {
+            connection
+                .query_row("SELECT value FROM eight WHERE key = ?1", [key], |row| {
+                    row.get(0)
+                })
+                .map_err(|_| err!(embedded, GetKeyFail))
+        })
+        .await
+    }
+
+    async fn delete(&self, key: String) -> embedded::Result<()> {
+        self.run(move |connection| {
+            match connection.execute("DELETE FROM eight WHERE key = ?1", [key]) {
+                Ok(1) => Ok(()),
+                _ => Err(err!(embedded, DeleteKeyFail)),
+            }
+        })
+        .await
+    }
+
+    async fn exists(&self, key: String) -> embedded::Result<bool> {
+        self.run(move |connection| key_exists(connection, &key))
+            .await
+    }
+
+    async fn increment(&self, key: String, num: usize) -> embedded::Result<usize> {
+        self.update(key, move |value| value.checked_add(num)).await
+    }
+
+    async fn decrement(&self, key: String, num: usize) -> embedded::Result<usize> {
+        self.update(key, move |value| value.checked_sub(num)).await
+    }
+
+    async fn search(&self, key: String) -> embedded::Result<Vec<String>> {
+        self.run(move |connection| select_keys(connection, &key, None, None))
+            .await
+    }
+
+    async fn flush(&self) -> embedded::Result<()> {
+        self.run(|connection| {
+            connection
+                .execute("DELETE FROM eight", [])
+                .map_err(|_| err!(embedded, DatabaseQueryFail))?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn rename(&self, from: String, to: String) -> embedded::Result<()> {
+        self.run(move |connection| {
+            let transaction = connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(|_| err!(embedded, SetKeyFail))?;
+
+            if !key_exists(&transaction, &from)? {
+                return Err(err!(embedded, GetKeyFail));
+            }
+
+            if from != to {
+                transaction
+                    .execute("DELETE FROM eight WHERE key = ?1", [&to])
+                    .and_then(|_| {
+                        transaction.execute(
+                            "UPDATE eight SET key = ?2 WHERE key = ?1",
+                            params![from, to],
+                        )
+                    })
+                    .and_then(|_| transaction.commit())
+                    .map_err(|_| err!(embedded, SetKeyFail))?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn rename_nx(&self, from: String, to: String) -> embedded::Result<bool> {
+        self.run(move |connection| {
+            let transaction = connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(|_| err!(embedded, SetKeyFail))?;
+
+            if !key_exists(&transaction, &from)? {
+                return Err(err!(embedded, GetKeyFail));
+            }
+
+            if key_exists(&transaction, &to)? {
+                return Ok(false);
+            }
+
+            transaction
+                .execute(
+                    "UPDATE eight SET key = ?2 WHERE key = ?1",
+                    params![from, to],
+                )
+                .and_then(|_| transaction.commit())
+                .map_err(|_| err!(embedded, SetKeyFail))?;
+
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn copy(&self, from: String, to: String) -> embedded::Result<bool> {
+        self.run(move |connection| {
+            let transaction = connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(|_| err!(embedded, SetKeyFail))?;
+
+            if !key_exists(&transaction, &from)? {
+                return Err(err!(embedded, GetKeyFail));
+            }
+
+            if key_exists(&transaction, &to)? {
+                return Ok(false);
+            }
+
+            transaction
+                .execute(
+                    "INSERT INTO eight (key, value) SELECT ?2, value FROM eight WHERE key = ?1",
+                    params![from, to],
+                )
+                .and_then(|_| transaction.commit())
+                .map_err(|_| err!(embedded, SetKeyFail))?;
+
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn set_nx(&self, key: String, value: String) -> embedded::Result<bool> {
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "INSERT INTO eight (key, value) VALUES (?1, ?2) ON CONFLICT (key) DO NOTHING",
+                    params![key, value],
+                )
+                .map(|changed| changed == 1)
+                .map_err(|_| err!(embedded, SetKeyFail))
+        })
+        .await
+    }
+
+    async fn set_xx(&self, key: String, value: String) -> embedded::Result<bool> {
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "UPDATE eight SET value = ?2 WHERE key = ?1",
+                    params![key, value],
+                )
+                .map(|changed| changed == 1)
+                .map_err(|_| err!(embedded, SetKeyFail))
+        })
+        .await
+    }
+
+    async fn get_set(&self, key: String, value: String) -> embedded::Result<Option<String>> {
+        self.run(move |connection| {
+            let transaction = connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(|_| err!(embedded, SetKeyFail))?;
+
+            let old = transaction
+                .query_row("SELECT value FROM eight WHERE key = ?1", [&key], |row| {
+                    row.get(0)
+                })
+                .optional()
+                .map_err(|_| err!(embedded, GetKeyFail))?;
+
+            transaction
+                .execute(
+                    "INSERT INTO eight (key, value) VALUES (?1, ?2)
+                     ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                    params![key, value],
+                )
+                .and_then(|_| transaction.commit())
+                .map_err(|_| err!(embedded, SetKeyFail))?;
+
+            Ok(old)
+        })
+        .await
+    }
+
+    async fn get_delete(&self, key: String) -> embedded::Result<String> {
+        self.run(move |connection| {
+            connection
+                .query_row(
+                    "DELETE FROM eight WHERE key = ?1 RETURNING value",
+                    [key],
+                    |row| row.get(0),
+                )
+                .map_err(|_| err!(embedded, GetKeyFail))
+        })
+        .await
+    }
+
+    async fn append(&self, key: String, value: String) -> embedded::Result<usize> {
+        self.run(move |connection| {
+            // length counts characters of text values
+            connection
+                .query_row(
+                    "INSERT INTO eight (key, value) VALUES (?1, ?2)
+                     ON CONFLICT (key) DO UPDATE SET value = value || excluded.value
+                     RETURNING length(value)",
+                    params![key, value],
+                    |row| row.get::<_, i64>(0),
+                )
+                .map(|length| length as usize)
+                .map_err(|_| err!(embedded, SetKeyFail))
+        })
+        .await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: String,
+        current: Option<String>,
+        value: String,
+    ) -> embedded::Result<bool> {
+        self.run(move |connection| {
+            let changed = match current {
+                Some(current) => connection.execute(
+                    "UPDATE eight SET value = ?3 WHERE key = ?1 AND value = ?2",
+                    params![key, current, value],
+                ),
+                None => connection.execute(
+                    "INSERT INTO eight (key, value) VALUES (?1, ?2) ON CONFLICT (key) DO NOTHING",
+                    params![key, value],
+                ),
+            };
+
+            changed
+                .map(|changed| changed == 1)
+                .map_err(|_| err!(embedded, SetKeyFail))
+        })
+        .await
+    }
+
+    async fn compare_and_delete(&self, key: String, current: String) -> embedded::Result<bool> {
+        self.run(move |connection| {
+            connection
+                .execute(
+                    "DELETE FROM eight WHERE key = ?1 AND value = ?2",
+                    params![key, current],
+                )
+                .map(|changed| changed == 1)
+                .map_err(|_| err!(embedded, DeleteKeyFail))
+        })
+        .await
+    }
+
+    async fn keys(
+        &self,
+        prefix: String,
+    ) -> embedded::Result<BoxStream<'static, embedded::Result<String>>> {
+        let storage = self.clone();
+
+        // keys are read page by page after the last key seen, connection is never held between pages
+        let pages = stream::unfold(Some(None), move |after: Option<Option<String>>| {
+            let (storage, prefix) = (storage.clone(), prefix.clone());
+
+            async move {
+                let after = after?;
+                let page = storage
+                    .run(move |connection| {
+                        select_keys(connection, &prefix, after.as_deref(), Some(KEYS_PAGE_SIZE))
+                    })
+                    .await;
+
+                let next = match &page {
+                    Ok(keys) if keys.len() == KEYS_PAGE_SIZE => Some(keys.last().cloned()),
+                    _ => None,
+                };
+
+                let page = match page {
+                    Ok(keys) => keys.into_iter().map(Ok).collect::<Vec<_>>(),
+                    Err(error) => vec![Err(error)],
+                };
+
+                Some((stream::iter(page), next))
+            }
+        });
+
+        Ok(pages.flatten().boxed())
+    }
+}
+
+fn key_exists(connection: &Connection, key: &str) -> embedded::Result<bool> {
+    connection
+        .query_row("SELECT 1 FROM eight WHERE key = ?1", [key], |_| Ok(()))
+        .optional()
+        .map(|found| found.is_some())
+        .map_err(|_| err!(embedded, CheckExistsFail))
+}
+
+fn select_keys(
+    connection: &Connection,
+    prefix: &str,
+    after: Option<&str>,
+    limit: Option<usize>,
+) -> embedded::Result<Vec<String>> {
+    // text is compared byte by byte, so keys with given prefix are a single range of the primary key
+    let (lower, operator) = match after {
+        Some(after) => (after, ">"),
+        None => (prefix, ">="),
+    };
+
+    let upper = prefix_end(prefix);
+    let condition = if upper.is_some() {
+        "key < ?2"
+    } else {
+        "?2 IS NULL"
+    };
+    let limit = limit.map_or(-1, |limit| limit as i64);
+
+    let mut statement = connection
+        .prepare_cached(&format!(
+            "SELECT key FROM eight WHERE key {operator} ?1 AND {condition} ORDER BY key LIMIT ?3"
+        ))
+        .map_err(|_| err!(embedded, DatabaseQueryFail))?;
+
+    let keys = statement
+        .query_map(params![lower, upper, limit], |row| row.get(0))
+        .and_then(|rows| rows.collect::<Result<Vec<String>, _>>())
+        .map_err(|_| err!(embedded, DatabaseQueryFail))?;
+
+    Ok(keys)
+}
+
+/// Smallest string that is greater than every string starting with prefix. `None` if there is no such string.
+fn prefix_end(prefix: &str) -> Option<String> {
+    let mut chars = prefix.chars().collect::<Vec<_>>();
+
+    while let Some(last) = chars.pop() {
+        let next = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32);
+
+        if let Some(next) = next {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+
+    None
+}
@@ -0,0 +1,793 @@
+//! Conformance tests for [`Storage`] implementations.
+//!
+//! Every check takes an empty storage and panics if storage doesn't follow the contract.
+//! Use [`storage_conformance!`] to create one test for each check, or [`run`] to run all of them at once.
+//!
+//! ```
+//! mod memory_conformance {
+//!     use eight::embedded::storage::memory;
+//!
+//!     // factory is called with the name of the check, useful for creating a separate path for each
+//!     eight::storage_conformance!(|_name| memory::Storage::new());
+//! }
+//! ```
+//!
+//! # Implementation-defined behaviour
+//!
+//! Checks don't cover following behaviours, storages are free to choose:
+//!
+//! - Which [`Error`] variant is returned on failure, checks only expect an error.
+//! - Decrementing a value below zero or incrementing it beyond [`usize::MAX`].
+//! - Empty keys.
+//! - Order of keys returned by [`Storage::search`] and [`Storage::keys`].
+//! - Whether concurrent increments and decrements of the same key are atomic. Use a storage that guarantees it (like SQLite storage) if you need exact counters.
+//! - Whether concurrent appends of the same key are atomic.
+//! - Whether keys written while a [`Storage::keys`] stream is consumed are returned.
+//! - Whether values survive a restart.
+//! - Which timestamps [`Storage::metadata`] returns.
+//!
+//! [`Storage`]: super::Storage
+//! [`Storage::search`]: super::Storage::search
+//! [`Storage::keys`]: super::Storage::keys
+//! [`Storage::metadata`]: super::Storage::metadata
+//! [`Error`]: crate::embedded::Error
+//! [`storage_conformance!`]: crate::storage_conformance
+
+use super::Storage;
+use futures::{future, TryStreamExt};
+use std::future::Future;
+
+const CONCURRENT_TASKS: usize = 64;
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __storage_conformance_checks {
+    ($callback:ident, $($args:tt)*) => {
+        $crate::$callback! {
+            ($($args)*)
+            set_get
+            set_overwrites
+            get_missing
+            delete
+            delete_missing
+            exists
+            increment
+            decrement
+            increment_missing
+            increment_not_number
+            search
+            search_empty_prefix
+            search_no_match
+            keys_stream
+            flush
+            flush_empty
+            rename
+            rename_replaces
+            rename_missing
+            rename_nx
+            copy
+            set_nx
+            set_xx
+            get_set
+            get_delete
+            append
+            length
+            get_range
+            compare_and_swap
+            compare_and_delete
+            metadata
+            short_keys
+            special_keys
+            values
+            concurrent_sets
+            concurrent_deletes
+            concurrent_increments
+            concurrent_set_nx
+            concurrent_compare_and_swap
+            concurrent_reads_while_writing
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __storage_conformance_tests {
+    (($factory:expr) $($check:ident)*) => {
+        $(
+            #[test]
+            fn $check() {
+                $crate::embedded::storage::testing::block_on(async {
+                    let storage = ($factory)(stringify!($check));
+
+                    $crate::embedded::storage::testing::$check(&storage).await;
+                    $crate::embedded::storage::Storage::flush(&storage).await.ok();
+                });
+            }
+        )*
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __storage_conformance_run {
+    (($factory:ident) $($check:ident)*) => {
+        $(
+            let storage = $factory(stringify!($check));
+
+            $crate::embedded::storage::testing::$check(&storage).await;
+            $crate::embedded::storage::Storage::flush(&storage).await.ok();
+        )*
+    };
+}
+
+/// Create a test for every conformance check of [`Storage`] contract.
+///
+/// Takes a factory, a closure that is called with the name of the check and returns an empty storage.
+/// Storage is flushed after check is done. Tests are created in the current module, so call it in its own module.
+///
+/// ```
+/// mod filesystem_conformance {
+///     use eight::embedded::storage::filesystem;
+///
+///     eight::storage_conformance!(|name| {
+///         filesystem::Storage::from_path(std::env::temp_dir().join("conformance_example").join(name))
+///     });
+/// }
+/// ```
+///
+/// [`Storage`]: crate::embedded::storage::Storage
+#[macro_export]
+#[cfg_attr(docsrs, doc(cfg(feature = "storage-testing")))]
+macro_rules! storage_conformance {
+    ($factory:expr) => {
+        $crate::__storage_conformance_checks!(__storage_conformance_tests, $factory);
+    };
+}
+
+/// Run every conformance check one by one, each with a new storage from factory. Panics on the first failing check.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use eight::embedded::storage::{memory, testing};
+///
+/// testing::run(|_name| memory::Storage::new()).await;
+/// # });
+/// ```
+pub async fn run<S, F>(factory: F)
+where
+    S: Storage,
+    F: Fn(&'static str) -> S,
+{
+    crate::__storage_conformance_checks!(__storage_conformance_run, factory);
+}
+
+#[doc(hidden)]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Creating runtime for conformance test failed")
+        .block_on(future)
+}
+
+fn sorted(mut keys: Vec<String>) -> Vec<String> {
+    keys.sort();
+    keys
+}
+
+/// Value set is returned back.
+pub async fn set_get(storage: &dyn Storage) {
+    storage
+        .set("bob".into(), "some value".into())
+        .await
+        .unwrap();
+    assert_eq!(storage.get("bob".into()).await.unwrap(), "some value");
+}
+
+/// Setting an existing key replaces its value.
+pub async fn set_overwrites(storage: &dyn Storage) {
+    storage.set("bob".into(), "first".into()).await.unwrap();
+    storage.set("bob".into(), "second".into()).await.unwrap();
+
+    assert_eq!(storage.get("bob".into()).await.unwrap(), "second");
+    assert_eq!(storage.search(String::new()).await.unwrap().len(), 1);
+}
+
+/// Getting a missing key fails.
+pub async fn get_missing(storage: &dyn Storage) {
+    assert!(storage.get("missing".into()).await.is_err());
+}
+
+/// Deleted key is gone, other keys are kept.
+pub async fn delete(storage: &dyn Storage) {
+    storage.set("bob".into(), "value".into()).await.unwrap();
+    storage.set("bobby".into(), "value".into()).await.unwrap();
+    storage.delete("bob".into()).await.unwrap();
+
+    assert!(storage.get("bob".into()).await.is_err());
+    assert!(!storage.exists("bob".into()).await.unwrap());
+    assert_eq!(storage.get("bobby".into()).await.unwrap(), "value");
+}
+
+/// Deleting a missing key fails.
+pub async fn delete_missing(storage: &dyn Storage) {
+    assert!(storage.delete("missing".into()).await.is_err());
+}
+
+/// Exists reports whether key is set.
+pub async fn exists(storage: &dyn Storage) {
+    assert!(!storage.exists("bob".into()).await.unwrap());
+
+    storage.set("bob".into(), "value".into()).await.unwrap();
+    assert!(storage.exists("bob".into()).await.unwrap());
+    assert!(!storage.exists("bo".into()).await.unwrap());
+}
+
+/// Increment adds to value, stores and returns the result.
+pub async fn increment(storage: &dyn Storage) {
+    storage.set("points".into(), "10".into()).await.unwrap();
+
+    assert_eq!(storage.increment("points".into(), 5).await.unwrap(), 15);
+    assert_eq!(storage.increment("points".into(), 0).await.unwrap(), 15);
+    assert_eq!(storage.get("points".into()).await.unwrap(), "15");
+}
+
+/// Decrement subtracts from value, stores and returns the result.
+pub async fn decrement(storage: &dyn Storage) {
+    storage.set("points".into(), "10".into()).await.unwrap();
+
+    assert_eq!(storage.decrement("points".into(), 4).await.unwrap(), 6);
+    assert_eq!(storage.decrement("points".into(), 6).await.unwrap(), 0);
+    assert_eq!(storage.get("points".into()).await.unwrap(), "0");
+}
+
+/// Incrementing or decrementing a missing key fails and doesn't create it.
+pub async fn increment_missing(storage: &dyn Storage) {
+    assert!(storage.increment("missing".into(), 1).await.is_err());
+    assert!(storage.decrement("missing".into(), 1).await.is_err());
+    assert!(!storage.exists("missing".into()).await.unwrap());
+}
+
+/// Incrementing or decrementing a value that is not an unsigned integer fails and keeps the value.
+pub async fn increment_not_number(storage: &dyn Storage) {
+    for value in ["text", "-1", "1.5", ""] {
+        storage.set("value".into(), value.into()).await.unwrap();
+
+        assert!(storage.increment("value".into(), 1).await.is_err());
+        assert!(storage.decrement("value".into(), 1).await.is_err());
+        assert_eq!(storage.get("value".into()).await.unwrap(), value);
+    }
+}
+
+/// Search returns every key starting with prefix and nothing else.
+pub async fn search(storage: &dyn Storage) {
+    for key in ["user1", "user2", "user10", "use", "admin", "usr"] {
+        storage.set(key.into(), "value".into()).await.unwrap();
+    }
+
+    let results = sorted(storage.search("user".into()).await.unwrap());
+    assert_eq!(results, vec!["user1", "user10", "user2"]);
+
+    let results = sorted(storage.search("user1".into()).await.unwrap());
+    assert_eq!(results, vec!["user1", "user10"]);
+
+    let results = storage.search("user10".into()).await.unwrap();
+    assert_eq!(results, vec!["user10"]);
+}
+
+/// Empty prefix returns every key.
+pub async fn search_empty_prefix(storage: &dyn Storage) {
+    assert!(storage.search(String::new()).await.unwrap().is_empty());
+
+    for i in 0..50 {
+        storage
+            .set(format!("key{i}"), "value".into())
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(storage.search(String::new()).await.unwrap().len(), 50);
+}
+
+/// Search without a match returns nothing, it doesn't fail.
+pub async fn search_no_match(storage: &dyn Storage) {
+    storage.set("bob".into(), "value".into()).await.unwrap();
+
+    assert!(storage.search("alice".into()).await.unwrap().is_empty());
+    assert!(storage.search("bobby".into()).await.unwrap().is_empty());
+}
+
+/// Keys stream returns the same keys with search.
+pub async fn keys_stream(storage: &dyn Storage) {
+    for i in 0..300 {
+        storage
+            .set(format!("key{i}"), "value".into())
+            .await
+            .unwrap();
+    }
+
+    storage.set("other".into(), "value".into()).await.unwrap();
+
+    for prefix in ["", "key", "key1", "other", "none"] {
+        let keys = storage.keys(prefix.into()).await.unwrap();
+        let streamed = keys.try_collect::<Vec<_>>().await.unwrap();
+
+        let searched = storage.search(prefix.into()).await.unwrap();
+        assert_eq!(sorted(streamed), sorted(searched));
+    }
+}
+
+/// Flush removes every key, storage stays usable.
+pub async fn flush(storage: &dyn Storage) {
+    for i in 0..50 {
+        storage
+            .set(format!("key{i}"), "value".into())
+            .await
+            .unwrap();
+    }
+
+    storage.flush().await.unwrap();
+
+    assert!(storage.search(String::new()).await.unwrap().is_empty());
+    assert!(!storage.exists("key1".into()).await.unwrap());
+
+    storage.set("key1".into(), "value".into()).await.unwrap();
+    assert_eq!(storage.get("key1".into()).await.unwrap(), "value");
+}
+
+/// Flushing an empty storage succeeds, even twice.
+pub async fn flush_empty(storage: &dyn Storage) {
+    storage.flush().await.unwrap();
+    storage.flush().await.unwrap();
+}
+
+/// Renamed value is moved to new key, old key is gone.
+pub async fn rename(storage: &dyn Storage) {
+    storage.set("bob".into(), "value".into()).await.unwrap();
+    storage.rename("bob".into(), "bobby".into()).await.unwrap();
+
+    assert!(!storage.exists("bob".into()).await.unwrap());
+    assert_eq!(storage.get("bobby".into()).await.unwrap(), "value");
+
+    // renaming into itself keeps the value
+    storage
+        .rename("bobby".into(), "bobby".into())
+        .await
+        .unwrap();
+    assert_eq!(storage.get("bobby".into()).await.unwrap(), "value");
+
+    storage.rename("bobby".into(), "b".into()).await.unwrap();
+    assert_eq!(
+        sorted(storage.search(String::new()).await.unwrap()),
+        vec!["b"]
+    );
+}
+
+/// Rename replaces value of an existing target.
+pub async fn rename_replaces(storage: &dyn Storage) {
+    storage.set("new".into(), "new value".into()).await.unwrap();
+    storage.set("old".into(), "old value".into()).await.unwrap();
+    storage.rename("new".into(), "old".into()).await.unwrap();
+
+    assert_eq!(storage.get("old".into()).await.unwrap(), "new value");
+    assert_eq!(
+        sorted(storage.search(String::new()).await.unwrap()),
+        vec!["old"]
+    );
+}
+
+/// Renaming, conditionally renaming or copying a missing key fails and doesn't create target.
+pub async fn rename_missing(storage: &dyn Storage) {
+    assert!(storage
+        .rename("missing".into(), "target".into())
+        .await
+        .is_err());
+    assert!(storage
+        .rename_nx("missing".into(), "target".into())
+        .await
+        .is_err());
+    assert!(storage
+        .copy("missing".into(), "target".into())
+        .await
+        .is_err());
+    assert!(!storage.exists("target".into()).await.unwrap());
+}
+
+/// Conditional rename only moves value if target is missing.
+pub async fn rename_nx(storage: &dyn Storage) {
+    storage.set("bob".into(), "first".into()).await.unwrap();
+    storage.set("alice".into(), "second".into()).await.unwrap();
+
+    assert!(!storage
+        .rename_nx("bob".into(), "alice".into())
+        .await
+        .unwrap());
+    assert!(!storage.rename_nx("bob".into(), "bob".into()).await.unwrap());
+    assert_eq!(storage.get("bob".into()).await.unwrap(), "first");
+    assert_eq!(storage.get("alice".into()).await.unwrap(), "second");
+
+    assert!(storage
+        .rename_nx("bob".into(), "carol".into())
+        .await
+        .unwrap());
+    assert!(!storage.exists("bob".into()).await.unwrap());
+    assert_eq!(storage.get("carol".into()).await.unwrap(), "first");
+}
+
+/// Copy keeps source and only writes target if it is missing.
+pub async fn copy(storage: &dyn Storage) {
+    storage.set("bob".into(), "first".into()).await.unwrap();
+    storage.set("alice".into(), "second".into()).await.unwrap();
+
+    assert!(!storage.copy("bob".into(), "alice".into()).await.unwrap());
+    assert!(!storage.copy("bob".into(), "bob".into()).await.unwrap());
+    assert_eq!(storage.get("alice".into()).await.unwrap(), "second");
+
+    assert!(storage.copy("bob".into(), "bobby".into()).await.unwrap());
+    assert_eq!(storage.get("bob".into()).await.unwrap(), "first");
+    assert_eq!(storage.get("bobby".into()).await.unwrap(), "first");
+
+    // copies are independent of each other
+    storage.set("bob".into(), "changed".into()).await.unwrap();
+    assert_eq!(storage.get("bobby".into()).await.unwrap(), "first");
+
+    let results = sorted(storage.search(String::new()).await.unwrap());
+    assert_eq!(results, vec!["alice", "bob", "bobby"]);
+}
+
+/// Conditional set only writes a missing key.
+pub async fn set_nx(storage: &dyn Storage) {
+    assert!(storage.set_nx("lock".into(), "first".into()).await.unwrap());
+    assert!(!storage
+        .set_nx("lock".into(), "second".into())
+        .await
+        .unwrap());
+    assert_eq!(storage.get("lock".into()).await.unwrap(), "first");
+}
+
+/// Conditional set only writes an existing key.
+pub async fn set_xx(storage: &dyn Storage) {
+    assert!(!storage.set_xx("key".into(), "first".into()).await.unwrap());
+    assert!(!storage.exists("key".into()).await.unwrap());
+
+    storage.set("key".into(), "first".into()).await.unwrap();
+    assert!(storage.set_xx("key".into(), "second".into()).await.unwrap());
+    assert_eq!(storage.get("key".into()).await.unwrap(), "second");
+}
+
+/// Get and set returns previous value, or nothing for a missing key.
+pub async fn get_set(storage: &dyn Storage) {
+    assert_eq!(
+        storage.get_set("key".into(), "first".into()).await.unwrap(),
+        None
+    );
+    assert_eq!(
+        storage
+            .get_set("key".into(), "second".into())
+            .await
+            .unwrap(),
+        Some("first".into())
+    );
+    assert_eq!(storage.get("key".into()).await.unwrap(), "second");
+}
+
+/// Get and delete returns value and removes key, missing key fails.
+pub async fn get_delete(storage: &dyn Storage) {
+    assert!(storage.get_delete("missing".into()).await.is_err());
+
+    storage.set("key".into(), "value".into()).await.unwrap();
+    assert_eq!(storage.get_delete("key".into()).await.unwrap(), "value");
+    assert!(!storage.exists("key".into()).await.unwrap());
+}
+
+/// Append creates missing key and returns length in characters.
+pub async fn append(storage: &dyn Storage) {
+    assert_eq!(storage.append("log".into(), "ünï".into()).await.unwrap(), 3);
+    assert_eq!(
+        storage.append("log".into(), "cödé".into()).await.unwrap(),
+        7
+    );
+    assert_eq!(
+        storage.append("log".into(), String::new()).await.unwrap(),
+        7
+    );
+    assert_eq!(storage.get("log".into()).await.unwrap(), "ünïcödé");
+}
+
+/// Length counts characters, missing key has no length.
+pub async fn length(storage: &dyn Storage) {
+    assert_eq!(storage.length("missing".into()).await.unwrap(), 0);
+
+    storage.set("key".into(), "日本語".into()).await.unwrap();
+    assert_eq!(storage.length("key".into()).await.unwrap(), 3);
+}
+
+/// Range includes both ends, counts characters and accepts positions from the end.
+pub async fn get_range(storage: &dyn Storage) {
+    storage.set("key".into(), "ünïcödé".into()).await.unwrap();
+
+    for (start, end, expected) in [
+        (0, 2, "ünï"),
+        (3, -1, "cödé"),
+        (-2, -1, "dé"),
+        (-100, 0, "ü"),
+        (5, 100, "dé"),
+        (4, 2, ""),
+        (100, 200, ""),
+    ] {
+        assert_eq!(
+            storage.get_range("key".into(), start, end).await.unwrap(),
+            expected
+        );
+    }
+
+    assert_eq!(
+        storage.get_range("missing".into(), 0, -1).await.unwrap(),
+        ""
+    );
+}
+
+/// Value is swapped only if current value matches, missing key matches [`None`].
+pub async fn compare_and_swap(storage: &dyn Storage) {
+    assert!(storage
+        .compare_and_swap("key".into(), None, "1".into())
+        .await
+        .unwrap());
+    assert!(!storage
+        .compare_and_swap("key".into(), None, "2".into())
+        .await
+        .unwrap());
+    assert!(!storage
+        .compare_and_swap("key".into(), Some("2".into()), "3".into())
+        .await
+        .unwrap());
+    assert!(storage
+        .compare_and_swap("key".into(), Some("1".into()), "ünïcödé".into())
+        .await
+        .unwrap());
+
+    assert_eq!(storage.get("key".into()).await.unwrap(), "ünïcödé");
+}
+
+/// Key is deleted only if current value matches, missing key never matches.
+pub async fn compare_and_delete(storage: &dyn Storage) {
+    assert!(!storage
+        .compare_and_delete("key".into(), "".into())
+        .await
+        .unwrap());
+
+    storage.set("key".into(), "ünïcödé".into()).await.unwrap();
+
+    assert!(!storage
+        .compare_and_delete("key".into(), "other".into())
+        .await
+        .unwrap());
+    assert!(storage.exists("key".into()).await.unwrap());
+    assert!(storage
+        .compare_and_delete("key".into(), "ünïcödé".into())
+        .await
+        .unwrap());
+    assert!(!storage.exists("key".into()).await.unwrap());
+}
+
+/// Size follows value, timestamps are optional but never go back. Missing key fails.
+pub async fn metadata(storage: &dyn Storage) {
+    storage.set("key".into(), "ünïcödé".into()).await.unwrap();
+    let first = storage.metadata("key".into()).await.unwrap();
+
+    assert_eq!(first.size, "ünïcödé".len());
+
+    storage.set("key".into(), "a".into()).await.unwrap();
+    let second = storage.metadata("key".into()).await.unwrap();
+
+    assert_eq!(second.size, 1);
+    assert!(second.updated >= first.updated);
+    assert_eq!(second.created, first.created);
+
+    assert!(storage.metadata("missing".into()).await.is_err());
+}
+
+/// One and two character keys are valid and don't collide with longer keys.
+pub async fn short_keys(storage: &dyn Storage) {
+    for key in ["a", "ab", "abc", "b"] {
+        storage.set(key.into(), key.into()).await.unwrap();
+    }
+
+    for key in ["a", "ab", "abc", "b"] {
+        assert_eq!(storage.get(key.into()).await.unwrap(), key);
+    }
+
+    let results = sorted(storage.search("a".into()).await.unwrap());
+    assert_eq!(results, vec!["a", "ab", "abc"]);
+
+    storage.delete("a".into()).await.unwrap();
+    assert_eq!(storage.get("ab".into()).await.unwrap(), "ab");
+}
+
+/// Any non-empty key is valid, including separators, dots, percent signs and unicode.
+pub async fn special_keys(storage: &dyn Storage) {
+    let keys = [
+        "user:42",
+        "a/b",
+        "a\\b",
+        "..",
+        ".",
+        "with space",
+        "%41",
+        "A",
+        "a.b",
+        "a_b",
+        "ünïcödé",
+        "日本語",
+        "$",
+    ];
+
+    for key in keys {
+        storage
+            .set(key.into(), format!("value of {key}"))
+            .await
+            .unwrap();
+    }
+
+    for key in keys {
+        assert_eq!(
+            storage.get(key.into()).await.unwrap(),
+            format!("value of {key}")
+        );
+    }
+
+    let mut expected = keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+    expected.sort();
+
+    assert_eq!(
+        sorted(storage.search(String::new()).await.unwrap()),
+        expected
+    );
+    assert_eq!(
+        storage.search("user:".into()).await.unwrap(),
+        vec!["user:42"]
+    );
+    assert_eq!(storage.search("a/".into()).await.unwrap(), vec!["a/b"]);
+    assert_eq!(storage.search("ünï".into()).await.unwrap(), vec!["ünïcödé"]);
+}
+
+/// Values are stored as is.
+pub async fn values(storage: &dyn Storage) {
+    let values = [
+        String::new(),
+        " leading and trailing spaces ".into(),
+        "multiple\nlines\r\n".into(),
+        "ünïcödé 日本語".into(),
+        "eight:crc32:00000000:looks like a header".into(),
+        "x".repeat(256 * 1024),
+    ];
+
+    for (i, value) in values.iter().enumerate() {
+        storage
+            .set(format!("value{i}"), value.clone())
+            .await
+            .unwrap();
+    }
+
+    for (i, value) in values.iter().enumerate() {
+        assert_eq!(&storage.get(format!("value{i}")).await.unwrap(), value);
+    }
+}
+
+/// Concurrent sets of different keys are all stored.
+pub async fn concurrent_sets(storage: &dyn Storage) {
+    let sets = (0..CONCURRENT_TASKS).map(|i| storage.set(format!("key{i}"), i.to_string()));
+
+    for result in future::join_all(sets).await {
+        result.unwrap();
+    }
+
+    for i in 0..CONCURRENT_TASKS {
+        assert_eq!(storage.get(format!("key{i}")).await.unwrap(), i.to_string());
+    }
+}
+
+/// Concurrent deletes of different keys with shared prefix don't affect each other.
+pub async fn concurrent_deletes(storage: &dyn Storage) {
+    for i in 0..CONCURRENT_TASKS {
+        storage
+            .set(format!("key{i}"), "value".into())
+            .await
+            .unwrap();
+        storage
+            .set(format!("key{i}kept"), "value".into())
+            .await
+            .unwrap();
+    }
+
+    let deletes = (0..CONCURRENT_TASKS).map(|i| storage.delete(format!("key{i}")));
+
+    for result in future::join_all(deletes).await {
+        result.unwrap();
+    }
+
+    let results = storage.search(String::new()).await.unwrap();
+    assert_eq!(results.len(), CONCURRENT_TASKS);
+    assert!(results.iter().all(|key| key.ends_with("kept")));
+}
+
+/// Only one of concurrent conditional sets of the same key succeeds.
+pub async fn concurrent_set_nx(storage: &dyn Storage) {
+    let sets = (0..CONCURRENT_TASKS).map(|i| storage.set_nx("lock".into(), i.to_string()));
+    let results = future::join_all(sets).await;
+
+    let winners = results
+        .iter()
+        .enumerate()
+        .filter(|(_, result)| *result.as_ref().unwrap())
+        .map(|(i, _)| i.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(winners.len(), 1);
+    assert_eq!(storage.get("lock".into()).await.unwrap(), winners[0]);
+}
+
+/// Counting with compare-and-swap retries from concurrent tasks loses no update.
+pub async fn concurrent_compare_and_swap(storage: &dyn Storage) {
+    let tasks = (0..CONCURRENT_TASKS).map(|_| async {
+        loop {
+            let current = storage.get("counter".into()).await.ok();
+            let next = current
+                .as_deref()
+                .map_or(0, |value| value.parse::<usize>().unwrap())
+                + 1;
+
+            if storage
+                .compare_and_swap("counter".into(), current, next.to_string())
+                .await
+                .unwrap()
+            {
+                break;
+            }
+        }
+    });
+
+    future::join_all(tasks).await;
+
+    assert_eq!(
+        storage.get("counter".into()).await.unwrap(),
+        CONCURRENT_TASKS.to_string()
+    );
+}
+
+/// Concurrent increments of different keys are all applied.
+pub async fn concurrent_increments(storage: &dyn Storage) {
+    for i in 0..CONCURRENT_TASKS {
+        storage
+            .set(format!("counter{i}"), "0".into())
+            .await
+            .unwrap();
+    }
+
+    let increments = (0..CONCURRENT_TASKS).map(|i| storage.increment(format!("counter{i}"), i));
+
+    for (i, result) in future::join_all(increments).await.into_iter().enumerate() {
+        assert_eq!(result.unwrap(), i);
+    }
+}
+
+/// A key read while it is overwritten returns either old or new value, never a partial one.
+pub async fn concurrent_reads_while_writing(storage: &dyn Storage) {
+    let (old, new) = ("a".repeat(64 * 1024), "b".repeat(64 * 1024));
+    storage.set("key".into(), old.clone()).await.unwrap();
+
+    let writes = async {
+        for i in 0..CONCURRENT_TASKS {
+            let value = if i % 2 == 0 { &new } else { &old };
+            storage.set("key".into(), value.clone()).await.unwrap();
+        }
+    };
+
+    let reads = future::join_all((0..CONCURRENT_TASKS).map(|_| storage.get("key".into())));
+    let (_, reads) = future::join(writes, reads).await;
+
+    for value in reads {
+        let value = value.unwrap();
+        assert!(value == old || value == new);
+    }
+}
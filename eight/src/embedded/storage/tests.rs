@@ -0,0 +1,348 @@
+#[cfg(any(feature = "filesystem-storage", feature = "sqlite-storage"))]
+fn temporary_path(storage: &str, check: &str) -> std::path::PathBuf {
+    std::env::temp_dir()
+        .join("eight_conformance")
+        .join(storage)
+        .join(check)
+}
+
+#[cfg(feature = "in-memory-storage")]
+mod memory {
+    use crate::embedded::storage::memory;
+
+    crate::storage_conformance!(|_| memory::Storage::new());
+}
+
+#[cfg(feature = "in-memory-storage")]
+mod metrics {
+    use crate::embedded::storage::{memory, metrics};
+
+    crate::storage_conformance!(|_| metrics::Storage::new(memory::Storage::new()));
+}
+
+#[cfg(feature = "filesystem-storage")]
+mod filesystem {
+    use crate::embedded::storage::filesystem::{self, Layout};
+
+    crate::storage_conformance!(|check| {
+        filesystem::Storage::from_path(super::temporary_path("filesystem", check))
+    });
+
+    mod hashed {
+        use super::*;
+
+        crate::storage_conformance!(|check| {
+            filesystem::Storage::from_path(super::super::temporary_path("hashed", check))
+                .set_layout(Layout::Hashed { buckets: 16 })
+        });
+    }
+
+    #[tokio::test]
+    async fn legacy_values_need_opt_in() {
+        use crate::embedded::{
+            storage::{filesystem::Repair, Storage},
+            Error,
+        };
+
+        let path = super::temporary_path("filesystem", "legacy_values");
+        let storage = filesystem::Storage::from_path(&path);
+        storage.flush().await.unwrap();
+
+        // value written before checksums
+        std::fs::create_dir_all(path.join("ke/y")).unwrap();
+        std::fs::write(path.join("ke/y/$"), "old value").unwrap();
+
+        assert_eq!(storage.get("key".into()).await, Err(Error::CorruptedValue));
+
+        let legacy = filesystem::Storage::from_path(&path).set_legacy_values(true);
+        assert_eq!(legacy.get("key".into()).await.unwrap(), "old value");
+
+        let report = storage.fsck(Repair::Fix).await.unwrap();
+        assert_eq!(report.unverified.len(), 1);
+        assert_eq!(storage.get("key".into()).await.unwrap(), "old value");
+
+        storage.flush().await.unwrap();
+    }
+}
+
+#[cfg(feature = "sqlite-storage")]
+mod sqlite {
+    use crate::embedded::storage::sqlite;
+
+    crate::storage_conformance!(|_| sqlite::Storage::open_in_memory().unwrap());
+
+    mod file {
+        use super::*;
+
+        crate::storage_conformance!(|check| {
+            let path = super::super::temporary_path("sqlite", check);
+            std::fs::create_dir_all(&path).unwrap();
+
+            sqlite::Storage::open(path.join("eight.db")).unwrap()
+        });
+    }
+}
+
+#[cfg(all(feature = "encrypted-storage", feature = "in-memory-storage"))]
+mod encrypted {
+    use crate::embedded::storage::{
+        encrypted::{self, Keyring},
+        memory,
+    };
+
+    crate::storage_conformance!(|_| {
+        encrypted::Storage::new(memory::Storage::new(), Keyring::new("main", [7; 32]))
+    });
+
+    #[tokio::test]
+    async fn counters_fail_instead_of_wrapping() {
+        use crate::embedded::{storage::Storage, Error};
+
+        let storage =
+            encrypted::Storage::new(memory::Storage::new(), Keyring::new("main", [7; 32]));
+
+        storage.set("count".into(), "1".into()).await.unwrap();
+
+        assert_eq!(
+            storage.decrement("count".into(), 2).await,
+            Err(Error::UIntParseFail)
+        );
+        assert_eq!(
+            storage.increment("count".into(), usize::MAX).await,
+            Err(Error::UIntParseFail)
+        );
+        assert_eq!(storage.get("count".into()).await.unwrap(), "1");
+    }
+
+    #[tokio::test]
+    async fn concurrent_appends_keep_every_value() {
+        use crate::embedded::storage::Storage;
+
+        let storage =
+            encrypted::Storage::new(memory::Storage::new(), Keyring::new("main", [7; 32]));
+
+        let appends = (0..50).map(|_| storage.append("log".into(), "a".into()));
+        futures::future::try_join_all(appends).await.unwrap();
+
+        assert_eq!(storage.get("log".into()).await.unwrap(), "a".repeat(50));
+    }
+
+    #[cfg(feature = "chaos-storage")]
+    #[tokio::test]
+    async fn rotation_keeps_concurrent_writes() {
+        use crate::embedded::storage::{
+            chaos::{self, Latency, Operation},
+            Storage,
+        };
+        use std::time::Duration;
+
+        let old = encrypted::Storage::new(memory::Storage::new(), Keyring::new("old", [1; 32]));
+        let keys = (0..20).map(|i| format!("key{i:02}")).collect::<Vec<_>>();
+
+        for key in &keys {
+            old.set(key.clone(), "old".into()).await.unwrap();
+        }
+
+        // slow writes keep records in flight while they change
+        let latency = Latency::Fixed(Duration::from_millis(5));
+        let inner = chaos::Storage::new(old.into_inner(), 3)
+            .set_latency(Operation::Set, latency)
+            .set_latency(Operation::CompareAndSwap, latency);
+
+        let keyring = Keyring::new("old", [1; 32])
+            .add_key("new", [2; 32])
+            .set_primary("new");
+        let storage = encrypted::Storage::new(inner, keyring);
+
+        let (rotated, _) = futures::join!(storage.rotate(), async {
+            tokio::time::sleep(Duration::from_millis(12)).await;
+
+            let changes = keys.iter().enumerate().map(|(i, key)| {
+                let storage = &storage;
+
+                async move {
+                    match i % 2 {
+                        0 => storage.set(key.clone(), "new".into()).await,
+                        _ => storage.delete(key.clone()).await,
+                    }
+                }
+            });
+
+            futures::future::try_join_all(changes).await.unwrap();
+        });
+
+        rotated.unwrap();
+
+        for (i, key) in keys.iter().enumerate() {
+            match i % 2 {
+                0 => assert_eq!(storage.get(key.clone()).await.unwrap(), "new"),
+                _ => assert!(!storage.exists(key.clone()).await.unwrap()),
+            }
+        }
+
+        assert_eq!(storage.rotate().await.unwrap(), 0);
+    }
+}
+
+#[cfg(all(feature = "chaos-storage", feature = "in-memory-storage"))]
+mod chaos {
+    use crate::embedded::storage::{
+        chaos::{self, Latency, Operation},
+        memory,
+    };
+    use std::time::Duration;
+
+    // only latency, so every check still passes
+    crate::storage_conformance!(|_| {
+        let latency = Latency::Exponential {
+            mean: Duration::from_micros(100),
+        };
+
+        Operation::ALL.into_iter().fold(
+            chaos::Storage::new(memory::Storage::new(), 8),
+            |storage, operation| storage.set_latency(operation, latency),
+        )
+    });
+}
+
+#[cfg(all(feature = "history-storage", feature = "in-memory-storage"))]
+mod history {
+    use crate::embedded::{
+        storage::{
+            history::{self, Retention},
+            memory, Storage,
+        },
+        Error,
+    };
+    use std::time::{Duration, SystemTime};
+
+    crate::storage_conformance!(|_| {
+        history::Storage::new(memory::Storage::new()).track("", Retention::Versions(3))
+    });
+
+    #[cfg(feature = "filesystem-storage")]
+    mod filesystem {
+        use super::*;
+        use crate::embedded::storage::filesystem;
+
+        crate::storage_conformance!(|check| {
+            let path = super::super::temporary_path("history", check);
+            history::Storage::new(filesystem::Storage::from_path(path))
+                .track("", Retention::Versions(3))
+        });
+    }
+
+    #[tokio::test]
+    async fn versions_retention() {
+        let storage =
+            history::Storage::new(memory::Storage::new()).track("a", Retention::Versions(2));
+
+        for value in ["1", "2", "3"] {
+            storage.set("a".into(), value.into()).await.unwrap();
+        }
+
+        storage.delete("a".into()).await.unwrap();
+        storage.set("b".into(), "1".into()).await.unwrap();
+
+        let values = storage
+            .history("a".into())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|version| version.value)
+            .collect::<Vec<_>>();
+
+        assert_eq!(values, vec![Some("3".to_string()), None]);
+        assert_eq!(
+            storage.get_at("a".into(), SystemTime::now()).await,
+            Err(Error::GetKeyFail)
+        );
+        assert_eq!(
+            storage.get_at("a".into(), SystemTime::UNIX_EPOCH).await,
+            Err(Error::VersionMissing)
+        );
+        assert_eq!(
+            storage.history("b".into()).await,
+            Err(Error::HistoryDisabled)
+        );
+    }
+
+    #[tokio::test]
+    async fn conditional_writes_record_changes_only() {
+        let storage =
+            history::Storage::new(memory::Storage::new()).track("", Retention::Versions(10));
+
+        storage.set("a".into(), "1".into()).await.unwrap();
+        assert!(!storage.set_nx("a".into(), "2".into()).await.unwrap());
+        storage.set("a".into(), "1".into()).await.unwrap();
+
+        assert_eq!(storage.history("a".into()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn concurrent_writes_record_every_version() {
+        let storage = std::sync::Arc::new(
+            history::Storage::new(memory::Storage::new()).track("", Retention::Versions(100)),
+        );
+
+        let tasks: Vec<_> = (0..50)
+            .map(|value| {
+                let storage = storage.clone();
+                tokio::spawn(async move { storage.set("a".into(), value.to_string()).await })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let versions = storage.history("a".into()).await.unwrap();
+
+        assert_eq!(versions.len(), 50);
+        assert_eq!(
+            versions.last().unwrap().value,
+            Some(storage.get("a".into()).await.unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn history_keys_are_reserved() {
+        let storage =
+            history::Storage::new(memory::Storage::new()).track("", Retention::Versions(10));
+        let key = format!("{}a", history::HISTORY_PREFIX);
+
+        assert_eq!(
+            storage.set(key.clone(), "forged".into()).await,
+            Err(Error::ReservedKey)
+        );
+        assert_eq!(
+            storage.rename(key, "a".into()).await,
+            Err(Error::ReservedKey)
+        );
+    }
+
+    #[tokio::test]
+    async fn age_retention_cleanup() {
+        let storage = history::Storage::new(memory::Storage::new())
+            .track("", Retention::Age(Duration::from_millis(50)))
+            .cleanup_every(Duration::from_millis(10));
+
+        for value in ["1", "2", "3"] {
+            storage.set("a".into(), value.into()).await.unwrap();
+        }
+
+        storage.set("b".into(), "1".into()).await.unwrap();
+        storage.delete("b".into()).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+
+        // latest value of an existing key is kept, deleted keys are forgotten
+        assert_eq!(storage.history("a".into()).await.unwrap().len(), 1);
+        assert!(!storage
+            .inner()
+            .exists(format!("{}b", history::HISTORY_PREFIX))
+            .await
+            .unwrap());
+        assert_eq!(storage.cleanup().await, Ok(0));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{
+    client::messaging::Response,
+    embedded::{
+        messaging,
+        server::{Permission, Server},
+    },
+    err,
+};
+use axum::{
+    body::StreamBody,
+    extract::{BodyStream, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response as HttpResponse},
+    Json,
+};
+use futures::StreamExt;
+use std::io;
+use tokio_util::io::{ReaderStream, StreamReader};
+use tracing::{info, warn};
+
+const DUMP_BUFFER_SIZE: usize = 64 * 1024;
+
+pub(super) async fn dump(State(database): State<Server>) -> HttpResponse {
+    if let Err(response) = check_permission(&database).await {
+        return response.into_response();
+    }
+
+    info!("Starting dump");
+
+    // dump is written into one end of the pipe while response streams the other end
+    let (writer, reader) = tokio::io::duplex(DUMP_BUFFER_SIZE);
+
+    tokio::spawn(async move {
+        match database.dump(writer).await {
+            Ok(count) => info!("Dumped {count} entries"),
+            Err(error) => warn!("Dump failed: {error}"),
+        }
+    });
+
+    (
+        [(header::CONTENT_TYPE, "application/octet-stream")],
+        StreamBody::new(ReaderStream::new(reader)),
+    )
+        .into_response()
+}
+
+pub(super) async fn restore(
+    State(database): State<Server>,
+    body: BodyStream,
+) -> (StatusCode, Json<Response>) {
+    if let Err(response) = check_permission(&database).await {
+        return response;
+    }
+
+    let body = body.map(|chunk| chunk.map_err(|_| io::Error::from(io::ErrorKind::InvalidData)));
+
+    match database.restore(StreamReader::new(body)).await {
+        Ok(count) => {
+            info!("Restored {count} entries");
+            respond(StatusCode::OK, messaging::Response::Number(count as usize))
+        }
+        Err(error) => respond(StatusCode::BAD_REQUEST, error.as_response()),
+    }
+}
+
+async fn check_permission(database: &Server) -> Result<(), (StatusCode, Json<Response>)> {
+    if database.permission().await == Permission::Guest {
+        Err(respond(
+            StatusCode::FORBIDDEN,
+            err!(embedded, PermissionFailure).as_response(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn respond(status: StatusCode, result: messaging::Response) -> (StatusCode, Json<Response>) {
+    (
+        status,
+        Json(Response {
+            id: String::new(),
+            results: vec![result],
+        }),
+    )
+}
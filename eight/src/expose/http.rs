@@ -1,5 +1,5 @@
 use crate::client::messaging::{Request, Response};
-use crate::embedded::server::Server;
+use crate::embedded::{server::Server, Error};
 use axum::{extract::State, http::StatusCode, Json};
 use tracing::{debug, info};
 
@@ -16,7 +16,10 @@ pub(super) async fn run_query(
     match response {
         Ok(results) => (StatusCode::OK, Json(Response { id, results })),
         Err(error) => (
-            StatusCode::BAD_REQUEST,
+            match error {
+                Error::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::BAD_REQUEST,
+            },
             Json(Response {
                 id,
                 results: vec![error.as_response()],
@@ -0,0 +1,100 @@
+use crate::embedded::{server::Server, storage::metrics::Summary};
+use axum::{extract::State, http::header, response::IntoResponse};
+use std::fmt::Write;
+
+pub(super) async fn metrics(State(database): State<Server>) -> impl IntoResponse {
+    let stats = database.stats();
+    let mut output = String::new();
+
+    write_summaries(
+        &mut output,
+        "eight_request",
+        "eight_requests_total",
+        &[(None, stats.requests)],
+    );
+
+    writeln!(output, "# TYPE eight_queued_requests gauge").ok();
+    writeln!(output, "eight_queued_requests {}", stats.queued).ok();
+    writeln!(output, "# TYPE eight_in_flight_requests gauge").ok();
+    writeln!(output, "eight_in_flight_requests {}", stats.in_flight).ok();
+    writeln!(output, "# TYPE eight_blocking_requests gauge").ok();
+    writeln!(output, "eight_blocking_requests {}", stats.blocking).ok();
+
+    if let Some(storage) = stats.storage {
+        let summaries = storage
+            .iter()
+            .map(|(operation, summary)| {
+                let label = format!("operation=\"{}\"", operation.name());
+                (Some(label), summary.clone())
+            })
+            .collect::<Vec<_>>();
+
+        write_summaries(
+            &mut output,
+            "eight_storage",
+            "eight_storage_operations_total",
+            &summaries,
+        );
+    }
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        output,
+    )
+}
+
+// prometheus text format, every sample of a metric is written together under its type
+fn write_summaries(
+    output: &mut String,
+    name: &str,
+    calls: &str,
+    summaries: &[(Option<String>, Summary)],
+) {
+    writeln!(output, "# TYPE {calls} counter").ok();
+
+    for (label, summary) in summaries {
+        let labels = labels(label, None);
+        writeln!(output, "{calls}{labels} {}", summary.calls).ok();
+    }
+
+    writeln!(output, "# TYPE {name}_errors_total counter").ok();
+
+    for (label, summary) in summaries {
+        let labels = labels(label, None);
+        writeln!(output, "{name}_errors_total{labels} {}", summary.errors).ok();
+    }
+
+    writeln!(output, "# TYPE {name}_duration_seconds histogram").ok();
+
+    for (label, summary) in summaries {
+        let histogram = &summary.latency;
+
+        for (bound, count) in histogram.buckets() {
+            let bound = bound.map_or("+Inf".to_string(), |bound| bound.as_secs_f64().to_string());
+            let labels = labels(label, Some(format!("le=\"{bound}\"")));
+
+            writeln!(output, "{name}_duration_seconds_bucket{labels} {count}").ok();
+        }
+
+        let labels = labels(label, None);
+        let sum = histogram.sum().as_secs_f64();
+
+        writeln!(output, "{name}_duration_seconds_sum{labels} {sum}").ok();
+        writeln!(
+            output,
+            "{name}_duration_seconds_count{labels} {}",
+            histogram.count()
+        )
+        .ok();
+    }
+}
+
+fn labels(label: &Option<String>, extra: Option<String>) -> String {
+    let labels = label.iter().cloned().chain(extra).collect::<Vec<_>>();
+
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
@@ -1,6 +1,17 @@
 //! Create web server for [`Server`]. This web server allows you to host embedded server over the network. Supports both HTTP and WebSocket connections to run queries.
+//!
+//! Server can be backed up from `/dump` path and restored by posting a dump to `/restore` path. Both require admin permission.
+//! Request and storage metrics are served from `/metrics` path in Prometheus text format, see [`Server::stats`].
+//! Queries rejected because request queue is full are answered with `503 Service Unavailable`, see [`Limits`].
+//!
+//! WebSocket connections on `/rpc` path can subscribe to channels. Published messages are pushed to them as [`Message`] frames, without an id.
+//!
+//! [`Message`]: crate::embedded::messaging::Message
+//! [`Limits`]: crate::embedded::server::Limits
 
+mod dump;
 mod http;
+mod metrics;
 mod websocket;
 
 use crate::embedded::server::{Permission, Server};
@@ -31,6 +42,9 @@ pub async fn expose(config: Config) -> bool {
     let mut app = Router::new()
         .route("/query", post(http::run_query))
         .route("/rpc", get(websocket::handle_connection))
+        .route("/dump", get(dump::dump))
+        .route("/restore", post(dump::restore))
+        .route("/metrics", get(metrics::metrics))
         .with_state(server);
 
     if let Some(fallback_path) = fallback_path {
@@ -106,3 +120,6 @@ impl ConfigBuilder {
         self.config
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,172 @@
+use crate::{
+    embedded::{
+        messaging::{Request, Response},
+        server::{Permission, Server},
+        storage::memory,
+    },
+    expose,
+};
+use reqwest::StatusCode;
+use std::net::SocketAddr;
+
+#[tokio::test]
+async fn dump_and_restore() {
+    let source = Server::new(memory::Storage::new());
+    let target = Server::new(memory::Storage::new());
+
+    for (server, port) in [(source.clone(), 42071), (target.clone(), 42072)] {
+        let config = expose::ConfigBuilder::from_server(server)
+            .bind(SocketAddr::from(([127, 0, 0, 1], port)))
+            .collect();
+
+        tokio::spawn(expose::expose(config));
+    }
+
+    tokio::task::yield_now().await;
+
+    for i in 0..100 {
+        source
+            .call(Request::Set(format!("key{i}"), i.to_string()))
+            .await
+            .unwrap();
+    }
+
+    let client = reqwest::Client::new();
+
+    let dump = client
+        .get("http://localhost:42071/dump")
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+
+    let response = client
+        .post("http://localhost:42072/restore")
+        .body(dump.clone())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        target.call(Request::Get("key42".into())).await.unwrap(),
+        Response::Text("42".into())
+    );
+
+    // truncated dump is rejected
+    let response = client
+        .post("http://localhost:42072/restore")
+        .body(dump.slice(..dump.len() - 1))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    target.set_permission(Permission::Guest).await;
+
+    let response = client
+        .post("http://localhost:42072/restore")
+        .body(dump)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[cfg(feature = "chaos-storage")]
+#[tokio::test]
+async fn storage_failure_mid_query() {
+    use crate::{
+        client::messaging,
+        embedded::{
+            storage::chaos::{self, Operation},
+            Error,
+        },
+    };
+
+    let storage = chaos::Storage::new(memory::Storage::new(), 0)
+        .fail_nth(Operation::Set, 2)
+        .set_error_rate(Operation::Get, 1.0);
+
+    let config = expose::ConfigBuilder::from_server(Server::new(storage))
+        .bind(SocketAddr::from(([127, 0, 0, 1], 42073)))
+        .collect();
+
+    tokio::spawn(expose::expose(config));
+    tokio::task::yield_now().await;
+
+    let request = messaging::QueryBuilder::new()
+        .add_query("set a 1; set b 2; exists b; get a;")
+        .collect();
+
+    let response = reqwest::Client::new()
+        .post("http://localhost:42073/query")
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+
+    // failing commands don't stop the query
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response
+            .json::<messaging::Response>()
+            .await
+            .unwrap()
+            .results,
+        vec![
+            Response::Ok,
+            Response::Error(Error::SetKeyFail),
+            Response::Boolean(false),
+            Response::Error(Error::GetKeyFail),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn metrics() {
+    use crate::embedded::storage::metrics;
+
+    let server = Server::new(metrics::Storage::new(memory::Storage::new()));
+
+    let config = expose::ConfigBuilder::from_server(server.clone())
+        .bind(SocketAddr::from(([127, 0, 0, 1], 42074)))
+        .collect();
+
+    tokio::spawn(expose::expose(config));
+    tokio::task::yield_now().await;
+
+    server
+        .call(Request::Set("key".into(), "value".into()))
+        .await
+        .unwrap();
+    server.call(Request::Get("missing".into())).await.unwrap();
+
+    let body = reqwest::get("http://localhost:42074/metrics")
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(body.contains("eight_requests_total 2\n"));
+    assert!(body.contains("eight_request_errors_total 1\n"));
+    assert!(body.contains("eight_request_duration_seconds_count 2\n"));
+    assert!(body.contains("eight_queued_requests 0\n"));
+    assert!(body.contains("# TYPE eight_in_flight_requests gauge\n"));
+    assert!(body.contains("# TYPE eight_blocking_requests gauge\n"));
+    assert!(body.contains("eight_storage_operations_total{operation=\"set\"} 1\n"));
+    assert!(body.contains("eight_storage_errors_total{operation=\"get\"} 1\n"));
+    assert!(
+        body.contains("eight_storage_duration_seconds_bucket{operation=\"get\",le=\"+Inf\"} 1\n")
+    );
+    assert_eq!(
+        body.matches("# TYPE eight_storage_duration_seconds")
+            .count(),
+        1
+    );
+}
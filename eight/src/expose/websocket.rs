@@ -1,5 +1,8 @@
 use crate::client::messaging::{Request, Response};
-use crate::embedded::server::Server;
+use crate::embedded::{
+    messaging,
+    server::{Server, Subscription},
+};
 use axum::{
     extract::{
         ws::{Message, WebSocket},
@@ -9,10 +12,10 @@ use axum::{
 };
 use futures::{
     sink::SinkExt,
-    stream::{SplitSink, StreamExt},
+    stream::{SplitSink, Stream, StreamExt},
 };
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::{sync::Mutex, task::JoinHandle};
 use tracing::{debug, info};
 
 pub(super) async fn handle_connection(
@@ -29,15 +32,54 @@ async fn execute_loop(database: Arc<Server>, socket: WebSocket) {
     let (sender, mut receiver) = socket.split();
     let sender = Arc::new(Mutex::new(sender));
 
+    // published messages are pushed as unsolicited frames
+    let (subscription, messages) = database.subscription();
+    let publisher = tokio::spawn(push_messages(Arc::clone(&sender), messages));
+
+    // every request runs on its own, so a blocking command doesn't hold the others back
+    let mut requests: Vec<JoinHandle<()>> = Vec::new();
+
     while let Some(Ok(message)) = receiver.next().await {
-        match message {
-            Message::Text(raw_value) => {
-                let sender = Arc::clone(&sender);
-                let database = Arc::clone(&database);
-
-                tokio::spawn(message_process(database, sender, raw_value));
-            }
-            _ => {}
+        if let Message::Text(raw_value) = message {
+            let sender = Arc::clone(&sender);
+            let database = Arc::clone(&database);
+            let subscription = subscription.clone();
+
+            requests.retain(|request| !request.is_finished());
+            requests.push(tokio::spawn(message_process(
+                database,
+                sender,
+                subscription,
+                raw_value,
+            )));
+        }
+    }
+
+    // nobody is left to read responses, so blocking commands stop waiting
+    for request in requests {
+        request.abort();
+    }
+
+    publisher.abort();
+}
+
+async fn push_messages(
+    sender: Arc<Mutex<SplitSink<WebSocket, Message>>>,
+    messages: impl Stream<Item = messaging::Message>,
+) {
+    let mut messages = Box::pin(messages);
+
+    while let Some(message) = messages.next().await {
+        let raw_message = serde_json::to_string(&message).unwrap_or_default();
+
+        if sender
+            .lock()
+            .await
+            .send(Message::Text(raw_message))
+            .await
+            .is_err()
+        {
+            break;
         }
     }
 }
@@ -45,6 +87,7 @@ async fn execute_loop(database: Arc<Server>, socket: WebSocket) {
 async fn message_process(
     database: Arc<Server>,
     sender: Arc<Mutex<SplitSink<WebSocket, Message>>>,
+    subscription: Subscription,
     raw_value: String,
 ) {
     let Ok(payload) = serde_json::from_str::<Request>(&raw_value) else {
@@ -54,7 +97,9 @@ async fn message_process(
     let Request { query, vars, id } = payload;
     info!("Incoming request with ID:{id}");
 
-    let response = database.query(query, vars).await;
+    let response = database
+        .query_with_subscription(query, vars, &subscription)
+        .await;
     debug!("Sending response for {id} -> {response:?}");
 
     let response = match response {